@@ -0,0 +1,4 @@
+ALTER TABLE song ADD COLUMN bitrate integer;
+ALTER TABLE song ADD COLUMN total_samples bigint;
+ALTER TABLE song ADD COLUMN encoder_delay integer;
+ALTER TABLE song ADD COLUMN encoder_padding integer;
@@ -0,0 +1,3 @@
+ALTER TABLE song ADD COLUMN duration_ms integer;
+UPDATE song SET duration_ms = duration * 1000;
+ALTER TABLE song ALTER COLUMN duration_ms SET NOT NULL;
@@ -0,0 +1,11 @@
+-- Lyrics read from embedded tags or a sidecar .lrc file, one row per song.
+-- `synced` holds [{"time_ms": 1234, "text": "..."}] sorted by time.
+CREATE TABLE song_lyrics (
+  song integer primary key,
+  plain text,
+  synced jsonb,
+  source varchar not null,
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone,
+  FOREIGN KEY (song) REFERENCES song (id) ON DELETE CASCADE
+);
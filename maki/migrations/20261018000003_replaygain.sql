@@ -0,0 +1,8 @@
+ALTER TABLE song ADD COLUMN replaygain_track_gain real;
+ALTER TABLE song ADD COLUMN replaygain_track_peak real;
+ALTER TABLE song ADD COLUMN replaygain_album_gain real;
+ALTER TABLE song ADD COLUMN replaygain_album_peak real;
+-- track values were measured by maki rather than read from tags
+ALTER TABLE song ADD COLUMN replaygain_computed boolean NOT NULL DEFAULT false;
+-- album values were derived from the album's track values rather than read from tags
+ALTER TABLE song ADD COLUMN replaygain_album_derived boolean NOT NULL DEFAULT false;
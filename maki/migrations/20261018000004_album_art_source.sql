@@ -0,0 +1,7 @@
+-- where the art came from: embedded, sidecar, coverartarchive or deezer.
+-- NULL for art stored before this was tracked.
+ALTER TABLE album_art ADD COLUMN source varchar;
+-- sidecar file the art was read from, with its size and mtime so unchanged files are skipped
+ALTER TABLE album_art ADD COLUMN source_path text;
+ALTER TABLE album_art ADD COLUMN source_size bigint;
+ALTER TABLE album_art ADD COLUMN source_mtime_ns bigint;
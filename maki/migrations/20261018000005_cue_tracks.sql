@@ -0,0 +1,6 @@
+-- CUE virtual tracks: several songs cut from one source file
+ALTER TABLE song ADD COLUMN cue_track integer;
+ALTER TABLE song ADD COLUMN start_ms integer;
+-- NULL runs to the end of the file
+ALTER TABLE song ADD COLUMN end_ms integer;
+CREATE UNIQUE INDEX idx_song_path_cue_track ON song (path, cue_track) WHERE cue_track IS NOT NULL;
@@ -0,0 +1,3 @@
+-- compilation albums are grouped under the configured "Various Artists" artist
+ALTER TABLE album ADD COLUMN compilation boolean NOT NULL DEFAULT false;
+CREATE INDEX idx_album_compilation ON album (compilation);
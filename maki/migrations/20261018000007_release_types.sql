@@ -0,0 +1,9 @@
+-- MusicBrainz release-group types, lowercased: primary is album, single, ep, broadcast or other,
+-- secondary types include compilation, live, remix and soundtrack
+ALTER TABLE album ADD COLUMN release_type varchar;
+ALTER TABLE album ADD COLUMN secondary_types varchar[] NOT NULL DEFAULT '{}';
+-- first release date of the release group, as precise as MusicBrainz knows it (YYYY[-MM[-DD]])
+ALTER TABLE album ADD COLUMN original_date varchar;
+-- whether MusicBrainz has been asked for the above, so albums predating them are looked up once
+ALTER TABLE album ADD COLUMN release_checked boolean NOT NULL DEFAULT false;
+CREATE INDEX idx_album_release_type ON album (release_type);
@@ -0,0 +1,26 @@
+-- composers of classical works, one row per distinct (case-insensitive) name
+CREATE TABLE composer (
+  id serial PRIMARY KEY,
+  slug varchar NOT NULL UNIQUE,
+  name varchar NOT NULL,
+  created_at timestamp with time zone not null
+);
+
+-- a work such as a symphony; every song tagged with it is one of its movements
+-- in some recording, so performances can be compared side by side
+CREATE TABLE work (
+  id serial PRIMARY KEY,
+  slug varchar NOT NULL UNIQUE,
+  name varchar NOT NULL,
+  composer integer REFERENCES composer (id) ON DELETE CASCADE,
+  created_at timestamp with time zone not null
+);
+CREATE INDEX idx_work_composer ON work (composer);
+
+ALTER TABLE song ADD COLUMN work integer REFERENCES work (id) ON DELETE SET NULL;
+ALTER TABLE song ADD COLUMN movement varchar;
+ALTER TABLE song ADD COLUMN movement_number integer;
+ALTER TABLE song ADD COLUMN movement_total integer;
+ALTER TABLE song ADD COLUMN conductor varchar;
+ALTER TABLE song ADD COLUMN orchestra varchar;
+CREATE INDEX idx_song_work ON song (work);
@@ -0,0 +1,13 @@
+-- people credited on a song besides its performing artists. people are artist rows, so
+-- someone who both performs and produces has a single page
+CREATE TABLE song_credit (
+  song integer NOT NULL REFERENCES song (id) ON DELETE CASCADE,
+  artist integer NOT NULL REFERENCES artist (id) ON DELETE CASCADE,
+  -- lower case: performer, producer, engineer, mixer, remixer, lyricist, ...
+  role varchar NOT NULL,
+  -- instrument or vocal part for performers, empty otherwise
+  detail varchar NOT NULL DEFAULT '',
+  created_at timestamp with time zone not null,
+  PRIMARY KEY (song, artist, role, detail)
+);
+CREATE INDEX idx_song_credit_artist ON song_credit (artist);
@@ -0,0 +1,20 @@
+-- songs without a track number were stored as 0, or 25565 for files with ID3 tags
+UPDATE song SET number = NULL WHERE number IN (0, 25565);
+
+-- multi-disc releases: the title of each disc and how many tracks and discs to expect
+ALTER TABLE song ADD COLUMN disc_subtitle varchar;
+ALTER TABLE song ADD COLUMN track_total integer;
+ALTER TABLE song ADD COLUMN disc_total integer;
+
+-- cover art for one disc of an album, from an image inside the disc's folder.
+-- disc is 1 for songs without a disc number, matching how the API groups them
+CREATE TABLE disc_art (
+  album integer NOT NULL REFERENCES album (id) ON DELETE CASCADE,
+  disc integer NOT NULL,
+  path varchar NOT NULL,
+  source_path varchar NOT NULL,
+  source_size bigint NOT NULL,
+  source_mtime_ns bigint NOT NULL,
+  created_at timestamp with time zone not null,
+  PRIMARY KEY (album, disc)
+);
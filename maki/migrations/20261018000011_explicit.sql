@@ -0,0 +1,6 @@
+-- parental advisory from ITUNESADVISORY, EXPLICIT or rtng: true for explicit, false for
+-- clean, null when the file isn't rated
+ALTER TABLE song ADD COLUMN explicit boolean;
+
+-- per-user preference to leave explicit songs out of browsing, search and playlists
+ALTER TABLE users ADD COLUMN hide_explicit bool NOT NULL DEFAULT false;
@@ -0,0 +1,12 @@
+-- genres are matched on a folded key so "Hip Hop", "Hip-Hop" and "hiphop" are one genre.
+-- the scanner recomputes keys at startup; this backfill only approximates its folding
+ALTER TABLE genre ADD COLUMN key varchar;
+UPDATE genre SET key = lower(regexp_replace(name, '[^[:alnum:]]+', '', 'g'));
+CREATE INDEX idx_genre_key ON genre (key);
+
+-- spellings an admin merged into another genre, by key, so rescans don't bring them back
+CREATE TABLE genre_alias (
+  key varchar PRIMARY KEY,
+  genre integer NOT NULL REFERENCES genre (id) ON DELETE CASCADE,
+  created_at timestamp with time zone not null
+);
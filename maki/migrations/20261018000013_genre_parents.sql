@@ -0,0 +1,4 @@
+-- sub-genres, e.g. Shoegaze under Rock, set from genre_parents in config.maki.json.
+-- browsing a genre includes everything below it
+ALTER TABLE genre ADD COLUMN parent integer REFERENCES genre (id) ON DELETE SET NULL;
+CREATE INDEX idx_genre_parent ON genre (parent);
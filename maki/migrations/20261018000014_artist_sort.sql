@@ -0,0 +1,18 @@
+-- artists are ordered by sort_key: the tagged or MusicBrainz sort name ("Beatles, The",
+-- "Utada, Hikaru") folded like album sort names, or the plain name when there is none
+ALTER TABLE artist ADD COLUMN sort_name varchar;
+ALTER TABLE artist ADD COLUMN sort_key varchar;
+-- set once MusicBrainz has been asked for the artist's aliases
+ALTER TABLE artist ADD COLUMN aliases_checked_at timestamp with time zone;
+CREATE INDEX idx_artist_sort_key ON artist (sort_key);
+
+-- other names an artist goes by, e.g. romanised and native-script spellings, for search
+CREATE TABLE artist_alias (
+  artist integer NOT NULL REFERENCES artist (id) ON DELETE CASCADE,
+  name varchar NOT NULL,
+  sort_name varchar,
+  locale varchar,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  PRIMARY KEY (artist, name)
+);
+CREATE INDEX idx_artist_alias_name ON artist_alias (lower(name));
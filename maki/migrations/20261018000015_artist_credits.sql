@@ -0,0 +1,19 @@
+-- the track artist exactly as tagged, so songs can be found again when the way it is
+-- split into artists changes
+ALTER TABLE song ADD COLUMN artist_tag varchar;
+CREATE INDEX idx_song_artist_tag ON song (artist_tag);
+
+-- recording MBID -> credited artist names, in credit order. An empty list means
+-- MusicBrainz doesn't know the recording, so it isn't asked again.
+CREATE TABLE mb_artist_credit (
+  recording varchar PRIMARY KEY,
+  artists varchar[] NOT NULL,
+  fetched_at timestamp with time zone NOT NULL DEFAULT now()
+);
+
+-- artist tags that are one artist even though they look like several, managed by admins
+-- on top of artist_split_exceptions in config.maki.json
+CREATE TABLE artist_split_exception (
+  name varchar PRIMARY KEY,
+  created_at timestamp with time zone NOT NULL DEFAULT now()
+);
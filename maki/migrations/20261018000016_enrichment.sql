@@ -0,0 +1,19 @@
+-- network lookups now run after scanning instead of during it; these record what has
+-- been looked up so each is done once
+
+-- bio, picture, tags and similar artists fetched. Existing artists were looked up when
+-- they were created.
+ALTER TABLE artist ADD COLUMN enriched_at timestamp with time zone;
+UPDATE artist SET enriched_at = COALESCE(updated_at, created_at);
+
+-- Cover Art Archive and Deezer searched for an album without local art
+ALTER TABLE album ADD COLUMN remote_art_checked boolean NOT NULL DEFAULT false;
+UPDATE album SET remote_art_checked = true;
+
+-- MusicBrainz searched for an untagged song's MBID. Until now every scan searched, so
+-- existing MBIDs are kept as searched; tagged ones are corrected on the next scan.
+ALTER TABLE song ADD COLUMN mbid_searched boolean NOT NULL DEFAULT false;
+UPDATE song SET mbid_searched = true WHERE mbid IS NOT NULL;
+
+CREATE INDEX idx_artist_unenriched ON artist (id) WHERE enriched_at IS NULL;
+CREATE INDEX idx_song_unsearched ON song (id) WHERE NOT mbid_searched;
@@ -0,0 +1,3 @@
+-- md5 of the first and last 64 KiB of the file, compared before skipping an unchanged
+-- file on rescan when scan_partial_hash is enabled
+ALTER TABLE song ADD COLUMN partial_hash varchar;
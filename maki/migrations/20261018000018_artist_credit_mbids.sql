@@ -0,0 +1,5 @@
+-- artist MBIDs alongside the credited artists, in the same order. Cached credits were
+-- stored by their "credited as" names and could be empty after a failed lookup, so they
+-- are fetched again.
+DELETE FROM mb_artist_credit;
+ALTER TABLE mb_artist_credit ADD COLUMN mbids varchar[] NOT NULL DEFAULT '{}';
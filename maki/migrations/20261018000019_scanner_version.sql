@@ -0,0 +1,3 @@
+-- Version of the scanner that last read the file. A rescan skips unchanged files only when
+-- it matches, so what newer scanners store gets filled in for files indexed before them.
+ALTER TABLE song ADD COLUMN scanner_version integer NOT NULL DEFAULT 0;
@@ -0,0 +1,6 @@
+-- loudness is now measured after scanning instead of during it. A file ffmpeg couldn't
+-- measure is left alone until it changes, instead of being decoded again every pass.
+ALTER TABLE song ADD COLUMN replaygain_failed boolean NOT NULL DEFAULT false;
+
+CREATE INDEX idx_song_unmeasured ON song (id)
+  WHERE replaygain_track_gain IS NULL AND NOT replaygain_failed;
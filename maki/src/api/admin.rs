@@ -1,19 +1,37 @@
+use std::{collections::HashMap, time::Duration};
+
 use axum::{
-    extract::{Extension, Query},
+    extract::{Extension, Path, Query},
     http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
     Json,
 };
+use futures::Stream;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use tracing::info;
+use tracing::{error, info};
+
+use crate::{
+    index::progress::{self, ScanJob, ScanProgress},
+    metadata::{
+        get_filetype, scan_format,
+        write::{self, AlbumTagEdit, TagChange, TagEdit},
+    },
+};
 
-use super::middleware::jwt::AdminUser;
+use super::{middleware::jwt::AdminUser, resolve_album_id, resolve_song_id};
 
 #[derive(Serialize, utoipa::ToSchema)]
 pub struct RescanResponse {
     pub status: &'static str,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RescanParams {
+    /// Re-read files whose size and modification time haven't changed
+    pub force: Option<bool>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct AnalyzeParams {
     pub kind: Option<crate::analysis::AnalysisKind>,
@@ -25,31 +43,87 @@ pub struct AnalyzeParams {
     post,
     path = "/api/v1/admin/rescan",
     tag = "admin",
+    params(
+        ("force" = Option<bool>, Query, description = "Re-read unchanged files too, e.g. after editing artist_split_exceptions"),
+    ),
     responses(
         (status = 202, description = "Rescan started", body = RescanResponse),
         (status = 403, description = "Admin access required"),
+        (status = 409, description = "A scan is already running"),
     ),
     security(("bearer_token" = []))
 )]
 /// POST /admin/rescan — trigger a full library rescan in the background.
-/// Requires admin privileges. Returns 202 Accepted immediately.
+/// Files unchanged since they were indexed are skipped unless `force` is set.
+/// Requires admin privileges. Returns 202 Accepted immediately, follow it with GET /admin/scan.
 pub async fn post_rescan(
     Extension(pool): Extension<PgPool>,
+    Query(params): Query<RescanParams>,
     AdminUser { .. }: AdminUser,
 ) -> Result<(StatusCode, Json<RescanResponse>), (StatusCode, String)> {
     let mount = std::env::var("MOUNT")
         .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "MOUNT not set".to_string()))?;
+    let force = params.force.unwrap_or(false);
+    let job = ScanJob::begin(force)
+        .ok_or_else(|| (StatusCode::CONFLICT, "a scan is already running".to_string()))?;
 
-    info!(target: "admin", "manual rescan triggered");
+    info!(target: "admin", "manual rescan triggered (force: {})", force);
 
     tokio::spawn(async move {
         let cfg = crate::config::load_or_create_config("config/config.maki.json");
-        crate::index::scan(&mount, pool, false, &cfg).await;
+        crate::index::scan_with(job, &mount, pool, false, &cfg).await;
     });
 
     Ok((StatusCode::ACCEPTED, Json(RescanResponse { status: "scanning" })))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/scan",
+    tag = "admin",
+    responses(
+        (status = 200, description = "The running or last finished scan", body = ScanProgress),
+        (status = 403, description = "Admin access required"),
+    ),
+    security(("bearer_token" = []))
+)]
+/// GET /admin/scan — progress of the running scan, or totals of the last one.
+pub async fn get_scan(AdminUser { .. }: AdminUser) -> Json<ScanProgress> {
+    Json(progress::current())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/scan/events",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Server-sent `progress` events with a ScanProgress, at most two a second", content_type = "text/event-stream", body = ScanProgress),
+        (status = 403, description = "Admin access required"),
+    ),
+    security(("bearer_token" = []))
+)]
+/// GET /admin/scan/events — stream scan progress as server-sent events, starting with the
+/// current state.
+pub async fn get_scan_events(
+    AdminUser { .. }: AdminUser,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let stream = futures::stream::unfold(
+        (progress::subscribe(), true),
+        |(mut rx, first)| async move {
+            if !first {
+                // coalesce the per-file updates of a busy scan
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                rx.changed().await.ok()?;
+            }
+            let event = Event::default()
+                .event("progress")
+                .json_data(&*rx.borrow_and_update());
+            Some((event, (rx, false)))
+        },
+    );
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
 #[utoipa::path(
     post,
     path = "/api/v1/admin/analyze",
@@ -98,3 +172,417 @@ pub async fn post_analyze(
         }),
     ))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct TagEditParams {
+    /// Return the tag diff without writing any files
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TrackTagChanges {
+    pub track_id: i32,
+    pub path: String,
+    pub changes: Vec<TagChange>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TagEditResponse {
+    pub dry_run: bool,
+    pub tracks: Vec<TrackTagChanges>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/track/{id}",
+    tag = "admin",
+    params(
+        ("id" = String, Path, description = "Track ID or slug"),
+        ("dry_run" = Option<bool>, Query, description = "Return the tag diff without writing"),
+    ),
+    request_body = TagEdit,
+    responses(
+        (status = 200, description = "Tags written, or the diff for a dry run", body = TagEditResponse),
+        (status = 403, description = "Admin access required"),
+        (status = 404, description = "Track not found"),
+        (status = 409, description = "Track is a CUE virtual track"),
+        (status = 422, description = "The file's tag format cannot be written: MP4/M4A, DSF and DFF are read-only"),
+    ),
+    security(("bearer_token" = []))
+)]
+/// PATCH /track/:id — write changed fields into the file's tags, then rescan it.
+pub async fn patch_track(
+    Path(id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<crate::config::Config>,
+    Query(params): Query<TagEditParams>,
+    AdminUser { .. }: AdminUser,
+    Json(edit): Json<TagEdit>,
+) -> Result<Json<TagEditResponse>, (StatusCode, String)> {
+    let id = resolve_song_id(&id, &pool).await?;
+    let songs: Vec<(i32, String, Option<i32>)> =
+        sqlx::query_as("SELECT id, path, cue_track FROM song WHERE id = $1")
+            .bind(id)
+            .fetch_all(&pool)
+            .await
+            .map_err(internal_error)?;
+    if songs.is_empty() {
+        return Err((StatusCode::NOT_FOUND, format!("song not found: {}", id)));
+    }
+    edit_tags(songs, &edit, params.dry_run.unwrap_or(false), &pool, &config).await
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/album/{id}",
+    tag = "admin",
+    params(
+        ("id" = String, Path, description = "Album ID or slug"),
+        ("dry_run" = Option<bool>, Query, description = "Return the tag diff without writing"),
+    ),
+    request_body = AlbumTagEdit,
+    responses(
+        (status = 200, description = "Tags written, or the diff for a dry run", body = TagEditResponse),
+        (status = 403, description = "Admin access required"),
+        (status = 404, description = "Album not found"),
+        (status = 409, description = "Album contains CUE virtual tracks"),
+        (status = 422, description = "A file's tag format cannot be written: MP4/M4A, DSF and DFF are read-only"),
+    ),
+    security(("bearer_token" = []))
+)]
+/// PATCH /album/:id — write album-wide fields into every track's tags, then rescan them.
+pub async fn patch_album(
+    Path(id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<crate::config::Config>,
+    Query(params): Query<TagEditParams>,
+    AdminUser { .. }: AdminUser,
+    Json(edit): Json<AlbumTagEdit>,
+) -> Result<Json<TagEditResponse>, (StatusCode, String)> {
+    let id = resolve_album_id(&id, &pool).await?;
+    let songs: Vec<(i32, String, Option<i32>)> = sqlx::query_as(
+        "SELECT id, path, cue_track FROM song WHERE album = $1 ORDER BY disc, number, id",
+    )
+    .bind(id)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?;
+    if songs.is_empty() {
+        return Err((StatusCode::NOT_FOUND, format!("album not found: {}", id)));
+    }
+    edit_tags(songs, &edit.into(), params.dry_run.unwrap_or(false), &pool, &config).await
+}
+
+/// Diff every file against the edit before writing any of them, so a bulk edit that
+/// can't be applied to one file leaves all of them untouched.
+async fn edit_tags(
+    songs: Vec<(i32, String, Option<i32>)>,
+    edit: &TagEdit,
+    dry_run: bool,
+    pool: &PgPool,
+    cfg: &crate::config::Config,
+) -> Result<Json<TagEditResponse>, (StatusCode, String)> {
+    if songs.iter().any(|(_, _, cue_track)| cue_track.is_some()) {
+        return Err((
+            StatusCode::CONFLICT,
+            "CUE virtual tracks take their tags from the cue sheet".to_string(),
+        ));
+    }
+
+    let mut planned = Vec::with_capacity(songs.len());
+    for (track_id, path, _) in songs {
+        let file = std::path::PathBuf::from(&path);
+        let format = get_filetype(&file).ok_or_else(|| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("{} is not a recognized audio file", path),
+            )
+        })?;
+        if !write::supports(&format) {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!(
+                    "writing tags to {:?} files is not supported, edit {} with another tagger and rescan",
+                    format, path
+                ),
+            ));
+        }
+        let current = scan_format(format, &file, cfg)
+            .await
+            .map_err(internal_error)?;
+        let changes = edit.diff(&current);
+        planned.push((file, format, TrackTagChanges { track_id, path, changes }));
+    }
+
+    if !dry_run {
+        for (file, format, track) in &planned {
+            if track.changes.is_empty() {
+                continue;
+            }
+            let (target, format, edit, changes) =
+                (file.clone(), *format, edit.clone(), track.changes.clone());
+            tokio::task::spawn_blocking(move || {
+                write::write_tags(&target, &format, &edit, &changes)
+            })
+            .await
+            .map_err(internal_error)?
+            .map_err(internal_error)?;
+            info!(target: "admin", "wrote {} tag changes to {}", track.changes.len(), track.path);
+            // rescan right away so the database never disagrees with the file
+            crate::metadata::scan_file(file, pool.clone(), false, cfg).await;
+        }
+        // renaming an album or artist can leave the old one empty
+        if let Err(e) = crate::index::db::cleanup_orphans(pool).await {
+            error!("failed to clean up after tag edit: {}", e);
+        }
+        crate::index::enrich::enqueue(pool.clone(), cfg.clone());
+    }
+
+    Ok(Json(TagEditResponse {
+        dry_run,
+        tracks: planned.into_iter().map(|(_, _, track)| track).collect(),
+    }))
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct MergeGenresRequest {
+    /// Genres to fold into `into`; their spellings become aliases of it
+    pub from: Vec<i32>,
+    pub into: i32,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct MergeGenresResponse {
+    pub genre: i32,
+    pub merged: Vec<i32>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/genres/merge",
+    tag = "admin",
+    request_body = MergeGenresRequest,
+    responses(
+        (status = 200, description = "Genres merged", body = MergeGenresResponse),
+        (status = 403, description = "Admin access required"),
+        (status = 404, description = "Genre not found"),
+    ),
+    security(("bearer_token" = []))
+)]
+/// POST /admin/genres/merge — move songs and albums into one genre and delete the others.
+/// Later scans file the merged spellings under the remaining genre.
+pub async fn post_merge_genres(
+    Extension(pool): Extension<PgPool>,
+    AdminUser { .. }: AdminUser,
+    Json(req): Json<MergeGenresRequest>,
+) -> Result<Json<MergeGenresResponse>, (StatusCode, String)> {
+    let into = req.into;
+    let mut merged: Vec<i32> = req.from.into_iter().filter(|&id| id != into).collect();
+    merged.sort_unstable();
+    merged.dedup();
+
+    let mut ids = merged.clone();
+    ids.push(into);
+    let found: Vec<i32> = sqlx::query_scalar("SELECT id FROM genre WHERE id = ANY($1)")
+        .bind(&ids)
+        .fetch_all(&pool)
+        .await
+        .map_err(internal_error)?;
+    if let Some(missing) = ids.iter().find(|id| !found.contains(id)) {
+        return Err((StatusCode::NOT_FOUND, format!("genre not found: {}", missing)));
+    }
+
+    crate::index::db::merge_genres(&merged, into, &pool)
+        .await
+        .map_err(internal_error)?;
+    info!(target: "admin", "merged genres {:?} into {}", merged, into);
+
+    Ok(Json(MergeGenresResponse {
+        genre: into,
+        merged,
+    }))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SplitException {
+    /// Artist tag kept as one artist
+    pub name: String,
+    /// `config` for exceptions from config.maki.json, which can't be removed here
+    pub source: &'static str,
+    /// Songs currently tagged with this artist
+    pub songs: i64,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct SplitExceptionRequest {
+    pub name: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SplitExceptionResponse {
+    pub name: String,
+    /// Files rescanned so their songs are split the new way
+    pub rescanned: usize,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/artist-splits",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Artist tags that are never split", body = [SplitException]),
+        (status = 403, description = "Admin access required"),
+    ),
+    security(("bearer_token" = []))
+)]
+/// GET /admin/artist-splits — list artist split exceptions from the config and the database.
+pub async fn get_split_exceptions(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<crate::config::Config>,
+    AdminUser { .. }: AdminUser,
+) -> Result<Json<Vec<SplitException>>, (StatusCode, String)> {
+    let stored: Vec<String> =
+        sqlx::query_scalar("SELECT name FROM artist_split_exception ORDER BY lower(name)")
+            .fetch_all(&pool)
+            .await
+            .map_err(internal_error)?;
+
+    let names: Vec<(String, &'static str)> = config
+        .artist_split_exceptions
+        .into_iter()
+        .map(|n| (n, "config"))
+        .chain(stored.into_iter().map(|n| (n, "admin")))
+        .collect();
+    let tags: Vec<&str> = names.iter().map(|(name, _)| name.as_str()).collect();
+    let counts: HashMap<String, i64> = sqlx::query_as(
+        "SELECT artist_tag, COUNT(*) FROM song WHERE artist_tag = ANY($1) GROUP BY artist_tag",
+    )
+    .bind(&tags)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?
+    .into_iter()
+    .collect();
+
+    let exceptions = names
+        .into_iter()
+        .map(|(name, source)| SplitException {
+            songs: counts.get(&name).copied().unwrap_or(0),
+            name,
+            source,
+        })
+        .collect();
+    Ok(Json(exceptions))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/artist-splits",
+    tag = "admin",
+    request_body = SplitExceptionRequest,
+    responses(
+        (status = 200, description = "Exception added and affected songs rescanned", body = SplitExceptionResponse),
+        (status = 400, description = "Empty artist name"),
+        (status = 403, description = "Admin access required"),
+    ),
+    security(("bearer_token" = []))
+)]
+/// POST /admin/artist-splits — keep an artist tag whole instead of splitting it on
+/// " feat. ", " x " and the like, then rescan the songs tagged with it. Songs with a
+/// recording MBID follow their MusicBrainz artist credit regardless.
+pub async fn post_split_exception(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<crate::config::Config>,
+    AdminUser { .. }: AdminUser,
+    Json(req): Json<SplitExceptionRequest>,
+) -> Result<Json<SplitExceptionResponse>, (StatusCode, String)> {
+    let name = req.name.trim().to_string();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "artist name is empty".to_string()));
+    }
+    sqlx::query("INSERT INTO artist_split_exception (name) VALUES ($1) ON CONFLICT DO NOTHING")
+        .bind(&name)
+        .execute(&pool)
+        .await
+        .map_err(internal_error)?;
+    info!(target: "admin", "added artist split exception {}", name);
+
+    let rescanned = rescan_artist_tag(&name, &pool, &config).await?;
+    Ok(Json(SplitExceptionResponse { name, rescanned }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/artist-splits",
+    tag = "admin",
+    request_body = SplitExceptionRequest,
+    responses(
+        (status = 200, description = "Exception removed and affected songs rescanned", body = SplitExceptionResponse),
+        (status = 403, description = "Admin access required"),
+        (status = 404, description = "No such exception"),
+        (status = 409, description = "Exception comes from config.maki.json"),
+    ),
+    security(("bearer_token" = []))
+)]
+/// DELETE /admin/artist-splits — split an artist tag again and rescan the songs tagged with it.
+pub async fn delete_split_exception(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<crate::config::Config>,
+    AdminUser { .. }: AdminUser,
+    Json(req): Json<SplitExceptionRequest>,
+) -> Result<Json<SplitExceptionResponse>, (StatusCode, String)> {
+    let name = req.name.trim().to_string();
+    if config.artist_split_exceptions.contains(&name) {
+        return Err((
+            StatusCode::CONFLICT,
+            "exception is set in config.maki.json".to_string(),
+        ));
+    }
+    let removed = sqlx::query("DELETE FROM artist_split_exception WHERE name = $1")
+        .bind(&name)
+        .execute(&pool)
+        .await
+        .map_err(internal_error)?
+        .rows_affected();
+    if removed == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("no split exception for {}", name),
+        ));
+    }
+    info!(target: "admin", "removed artist split exception {}", name);
+
+    let rescanned = rescan_artist_tag(&name, &pool, &config).await?;
+    Ok(Json(SplitExceptionResponse { name, rescanned }))
+}
+
+/// Rescan every file with a song tagged with `artist_tag`. Songs scanned before artist tags
+/// were stored are only picked up by a full rescan.
+async fn rescan_artist_tag(
+    artist_tag: &str,
+    pool: &PgPool,
+    cfg: &crate::config::Config,
+) -> Result<usize, (StatusCode, String)> {
+    let paths: Vec<String> =
+        sqlx::query_scalar("SELECT DISTINCT path FROM song WHERE artist_tag = $1")
+            .bind(artist_tag)
+            .fetch_all(pool)
+            .await
+            .map_err(internal_error)?;
+    for path in &paths {
+        crate::metadata::scan_file(&std::path::PathBuf::from(path), pool.clone(), false, cfg).await;
+    }
+    // the old split can leave artists without songs
+    if let Err(e) = crate::index::db::cleanup_orphans(pool).await {
+        error!("failed to clean up after artist split change: {}", e);
+    }
+    crate::index::enrich::enqueue(pool.clone(), cfg.clone());
+    Ok(paths.len())
+}
+
+fn internal_error<E: std::fmt::Debug>(err: E) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Internal error: {:?}", err),
+    )
+}
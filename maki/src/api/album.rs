@@ -10,12 +10,16 @@ use sqlx::{PgPool, Postgres, QueryBuilder};
 use tracing::debug;
 
 use crate::api::{
-    build_default_art_url, resolve_album_id, Album, AlbumPartialRaw, AlbumRaw, ArtistPartial,
+    build_default_art_url, resolve_album_id, Album, AlbumPartialRaw, AlbumRaw, ArtistPartial, Disc,
     Track, TrackRaw,
 };
 
-use super::{middleware::jwt::OptionalAuthUser, AlbumPartial, AllAlbumsPartial};
-use crate::api::song::liked_ids_for_user;
+use super::{middleware::jwt::OptionalAuthUser, AlbumPartial, AllAlbumsPartial, ARTIST_SORT_KEY};
+use crate::api::song::{hides_explicit_for_user, liked_ids_for_user};
+
+/// SQL expression albums are ordered by original release date. Albums without MusicBrainz
+/// data have none and sort before the rest, so paging past them works like any other date.
+const ORIGINAL_DATE_KEY: &str = "COALESCE(album.original_date, '')";
 
 #[utoipa::path(
     get,
@@ -42,7 +46,8 @@ pub async fn get_album(
 
     let album = match sqlx::query_as!(AlbumRaw, r#"
         SELECT album.id, album.slug, album.name, album.disambiguation, year,
-            album.copyright, album.label,
+            album.copyright, album.label, album.compilation,
+            album.release_type, album.secondary_types, album.original_date,
             album.created_at, album.updated_at,
             artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture, artist.bio as artist_bio,
             artist.created_at as artist_created_at, artist.updated_at as artist_updated_at,
@@ -68,6 +73,10 @@ pub async fn get_album(
                     year: e.year,
                     copyright: e.copyright,
                     label: e.label,
+                    compilation: e.compilation,
+                    release_type: e.release_type,
+                    secondary_types: e.secondary_types,
+                    original_date: e.original_date,
                     created_at: e.created_at,
                     updated_at: e.updated_at,
                     artist_id: e.artist_id,
@@ -83,30 +92,53 @@ pub async fn get_album(
         Err(e) => return Err(internal_error(e)),
     };
 
-    let tracks = match sqlx::query_as!(TrackRaw,
-            r#"
-        SELECT song.id, song.slug, disc, number, song.name, song.album, song.album_artist, liked, duration, plays, lossless,
-            sample_rate, bits_per_sample, num_channels, composer, album.isrc, bpm,
+    let mut tracks = match sqlx::query_as!(
+        TrackRaw,
+        r#"
+        SELECT song.id, song.slug, disc, number, song.name, song.album, song.album_artist, liked, duration, song.duration_ms as "duration_ms!", plays, lossless,
+            sample_rate, bits_per_sample, num_channels, song.bitrate, song.total_samples,
+            song.encoder_delay, song.encoder_padding,
+            song.replaygain_track_gain, song.replaygain_track_peak,
+            song.replaygain_album_gain, song.replaygain_album_peak, composer, album.isrc, bpm,
+            song.work, song.movement, song.movement_number, song.movement_total,
+            song.conductor, song.orchestra, song.explicit,
             song.created_at, song.updated_at, last_play, year,
-            album.name as album_name,
-            artist.name as artist_name,
+            album.name as "album_name!",
+            artist.name as "artist_name!",
             (SELECT album_art.path FROM album_art WHERE album_art.album = song.album LIMIT 1) AS art_path
         FROM song
         LEFT JOIN album ON song.album = album.id
         LEFT JOIN artist ON song.album_artist = artist.id
         WHERE song.album = $1
-        GROUP BY disc, number, song.name, song.album, song.id, artist.id, song.album_artist, liked, duration, plays, lossless,
-            sample_rate, bits_per_sample, num_channels, composer, album.isrc, bpm,
-            song.created_at, song.updated_at, last_play, year,
-            album.name, artist.name
         ORDER BY disc ASC, number ASC
-    "#, id_parsed
-        )
-        .fetch_all(&pool)
-        .await{
-            Ok(e) => e,
-            Err(e) => return Err(internal_error(e)),
-        };
+        "#,
+        id_parsed
+    )
+    .fetch_all(&pool)
+    .await
+    {
+        Ok(e) => e,
+        Err(e) => return Err(internal_error(e)),
+    };
+
+    // numbering gaps are judged on every track, including any hidden below
+    let mut numbers_by_disc: std::collections::HashMap<i32, Vec<i32>> =
+        std::collections::HashMap::new();
+    for track in &tracks {
+        if let Some(number) = track.number {
+            numbers_by_disc
+                .entry(track.disc.unwrap_or(1))
+                .or_default()
+                .push(number);
+        }
+    }
+    let explicit = tracks.iter().any(|t| t.explicit == Some(true));
+    if hides_explicit_for_user(&pool, user_id)
+        .await
+        .map_err(internal_error)?
+    {
+        tracks.retain(|t| t.explicit != Some(true));
+    }
 
     // Get the artists for each song
     let song_ids: Vec<i32> = tracks.iter().map(|track| track.id).collect();
@@ -176,14 +208,30 @@ pub async fn get_album(
                 album_artist: track.album_artist,
                 liked: user_id.map(|_| liked_ids.contains(&track_id)),
                 duration: track.duration,
+                duration_ms: track.duration_ms,
                 plays: track.plays,
                 lossless: track.lossless,
                 sample_rate: track.sample_rate,
                 bits_per_sample: track.bits_per_sample,
                 num_channels: track.num_channels,
+                bitrate: track.bitrate,
+                total_samples: track.total_samples,
+                encoder_delay: track.encoder_delay,
+                encoder_padding: track.encoder_padding,
+                replaygain_track_gain: track.replaygain_track_gain,
+                replaygain_track_peak: track.replaygain_track_peak,
+                replaygain_album_gain: track.replaygain_album_gain,
+                replaygain_album_peak: track.replaygain_album_peak,
                 composer: track.composer,
                 isrc: track.isrc,
                 bpm: track.bpm,
+                work: track.work,
+                movement: track.movement,
+                movement_number: track.movement_number,
+                movement_total: track.movement_total,
+                conductor: track.conductor,
+                orchestra: track.orchestra,
+                explicit: track.explicit,
                 created_at: track.created_at,
                 updated_at: track.updated_at,
                 last_play: track.last_play,
@@ -196,6 +244,51 @@ pub async fn get_album(
         })
         .collect();
 
+    let disc_rows = sqlx::query_as::<_, DiscRow>(
+        r#"
+        SELECT COALESCE(song.disc, 1) AS disc, MAX(song.disc_subtitle) AS subtitle,
+               MAX(song.track_total) AS track_total, MAX(song.disc_total) AS disc_total,
+               MAX(disc_art.path) AS art_path
+        FROM song
+        LEFT JOIN disc_art
+          ON disc_art.album = song.album AND disc_art.disc = COALESCE(song.disc, 1)
+        WHERE song.album = $1
+        GROUP BY COALESCE(song.disc, 1)
+        ORDER BY 1
+        "#,
+    )
+    .bind(id_parsed)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?;
+
+    let disc_total = disc_rows.iter().filter_map(|d| d.disc_total).max();
+    let discs: Vec<Disc> = disc_rows
+        .into_iter()
+        .map(|row| {
+            let tracks: Vec<Track> = tracks_with_artists
+                .iter()
+                .filter(|t| t.disc.unwrap_or(1) == row.disc)
+                .cloned()
+                .collect();
+            let numbers = numbers_by_disc
+                .get(&row.disc)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            Disc {
+                disc: row.disc,
+                subtitle: row.subtitle,
+                art_url: row.art_path.map(|p| format!("{}{}", art_url, p)),
+                track_total: row.track_total,
+                missing: missing_numbers(numbers, row.track_total),
+                tracks,
+            }
+        })
+        .filter(|disc| !disc.tracks.is_empty())
+        .collect();
+    let missing_tracks = discs.iter().any(|d| !d.missing.is_empty())
+        || disc_total.map_or(false, |total| (discs.len() as i32) < total);
+
     let genres: Vec<String> = sqlx::query_scalar!(
         r#"
         SELECT genre.name FROM album_genre
@@ -225,6 +318,11 @@ pub async fn get_album(
         genres,
         copyright: album.copyright,
         label: album.label,
+        compilation: album.compilation,
+        explicit,
+        release_type: album.release_type,
+        secondary_types: album.secondary_types,
+        original_date: album.original_date,
         created_at: album.created_at,
         updated_at: album.updated_at,
         artist: ArtistPartial {
@@ -238,9 +336,29 @@ pub async fn get_album(
             num_albums: None,
         },
         tracks: Some(tracks_with_artists),
+        disc_total,
+        discs,
+        missing_tracks,
     }))
 }
 
+#[derive(sqlx::FromRow)]
+struct DiscRow {
+    disc: i32,
+    subtitle: Option<String>,
+    track_total: Option<i32>,
+    disc_total: Option<i32>,
+    art_path: Option<String>,
+}
+
+/// Track numbers missing from a disc: the gaps up to its track total, or up to its highest
+/// track number when the total isn't tagged. Numbering is only checked up to 999.
+fn missing_numbers(numbers: &[i32], total: Option<i32>) -> Vec<i32> {
+    let highest = numbers.iter().copied().max().unwrap_or(0);
+    let last = total.unwrap_or(0).max(highest).min(999);
+    (1..=last).filter(|n| !numbers.contains(n)).collect()
+}
+
 #[derive(Deserialize, Default)]
 pub struct GetAlbumParams {
     #[serde(default)]
@@ -253,6 +371,12 @@ pub struct GetAlbumParams {
     cursor: Option<i32>, // Single cursor based on album.id
     #[serde(default)]
     filter: Option<String>,
+    /// Only compilations when true, no compilations when false
+    #[serde(default)]
+    compilation: Option<bool>,
+    /// Release type, matching either the primary or a secondary type
+    #[serde(default, rename = "type")]
+    release_type: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -265,6 +389,8 @@ enum SortByAlbumOptions {
     AlbumName,
     #[serde(alias = "year")]
     Year,
+    #[serde(alias = "original_date")]
+    OriginalDate,
 }
 
 #[derive(Deserialize, PartialEq)]
@@ -289,11 +415,13 @@ impl DirOptions {
     path = "/api/v1/album",
     tag = "albums",
     params(
-        ("sortby" = Option<String>, Query, description = "Sort field: id, artist, album, year"),
+        ("sortby" = Option<String>, Query, description = "Sort field: id, artist, album, year, original_date"),
         ("dir" = Option<String>, Query, description = "Sort direction: asc, desc"),
         ("limit" = Option<i32>, Query, description = "Max results (default 20)"),
         ("cursor" = Option<i32>, Query, description = "Pagination cursor (album ID)"),
         ("filter" = Option<String>, Query, description = "Filter by album or artist name"),
+        ("compilation" = Option<bool>, Query, description = "Only compilations (true) or no compilations (false)"),
+        ("type" = Option<String>, Query, description = "Release type: album, single, ep, live, compilation, remix, ..."),
     ),
     responses(
         (status = 200, description = "Paginated album list", body = AllAlbumsPartial),
@@ -309,10 +437,14 @@ pub async fn get_albums(
         limit,
         cursor, // Single cursor based on album.id
         filter,
+        compilation,
+        release_type,
     }): Query<GetAlbumParams>,
     Host(host): Host,
+    OptionalAuthUser { payload }: OptionalAuthUser,
 ) -> Result<axum::Json<AllAlbumsPartial>, (StatusCode, String)> {
     let art_url = build_default_art_url(host);
+    let user_id = payload.and_then(|p| p.sub.parse::<i32>().ok());
     let limit_value = limit.unwrap_or(20); // Default limit
 
     // If no cursor is provided, use a fallback
@@ -320,7 +452,7 @@ pub async fn get_albums(
 
     // Step 1: Fetch the album details based on the cursor (album.id)
     let current_album = sqlx::query_as!(AlbumPartialRaw, r#"
-        SELECT album.id, album.slug, album.name, album.disambiguation, album.year, count(song.id), artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture,
+        SELECT album.id, album.slug, album.name, album.disambiguation, album.year, album.release_type, album.secondary_types, album.original_date, count(song.id), artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture,
         STRING_AGG(CAST(album_art.path AS VARCHAR), ',') as arts
         FROM album
         LEFT JOIN song ON song.album = album.id
@@ -341,7 +473,7 @@ pub async fn get_albums(
 
     // Build the query
     let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
-        "SELECT album.id, album.slug, album.name, album.disambiguation, album.year, count(song.id), artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture,
+        "SELECT album.id, album.slug, album.name, album.disambiguation, album.year, album.release_type, album.secondary_types, album.original_date, count(song.id), artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture,
         STRING_AGG(CAST(album_art.path AS VARCHAR), ',') as arts
         FROM album
         LEFT JOIN song ON song.album = album.id
@@ -351,9 +483,10 @@ pub async fn get_albums(
     );
 
     let primary_value_column = match sortby {
-        Some(SortByAlbumOptions::ArtistName) => "artist.name",
+        Some(SortByAlbumOptions::ArtistName) => ARTIST_SORT_KEY,
         Some(SortByAlbumOptions::AlbumName) => "album.name",
         Some(SortByAlbumOptions::Year) => "album.year",
+        Some(SortByAlbumOptions::OriginalDate) => ORIGINAL_DATE_KEY,
         _ => "album.id", // Default to album.id
     };
 
@@ -365,11 +498,25 @@ pub async fn get_albums(
             " < "
         };
         match primary_value_column {
-            "artist.name" => {
+            ARTIST_SORT_KEY => {
+                // artists share sort keys, so the album id breaks ties
+                let sort_key = sqlx::query_scalar!(
+                    r#"SELECT COALESCE(sort_key, lower(name)) AS "sort_key!" FROM artist WHERE id = $1"#,
+                    album.artist_id
+                )
+                .fetch_optional(&pool)
+                .await
+                .map_err(internal_error)?;
                 query_builder
-                    .push(" AND artist.name")
+                    .push(" AND (")
+                    .push(ARTIST_SORT_KEY)
+                    .push(", album.id)")
                     .push(cmp)
-                    .push_bind(album.artist_name.clone());
+                    .push("(")
+                    .push_bind(sort_key)
+                    .push(", ")
+                    .push_bind(album.id)
+                    .push(")");
             }
             "album.name" => {
                 query_builder
@@ -383,6 +530,19 @@ pub async fn get_albums(
                     .push(cmp)
                     .push_bind(album.year);
             }
+            ORIGINAL_DATE_KEY => {
+                // many albums share a date, so the album id breaks ties
+                query_builder
+                    .push(" AND (")
+                    .push(ORIGINAL_DATE_KEY)
+                    .push(", album.id)")
+                    .push(cmp)
+                    .push("(")
+                    .push_bind(album.original_date.clone().unwrap_or_default())
+                    .push(", ")
+                    .push_bind(album.id)
+                    .push(")");
+            }
             _ => {
                 query_builder
                     .push(" AND album.id")
@@ -397,8 +557,34 @@ pub async fn get_albums(
             .push_bind(format!("%{}%", filter))
             .push(") OR lower(artist.name) ilike lower(")
             .push_bind(format!("%{}%", filter))
+            .push(") OR artist.sort_name ilike ")
+            .push_bind(format!("%{}%", filter))
+            .push(" OR EXISTS (SELECT 1 FROM artist_alias WHERE artist_alias.artist = artist.id AND artist_alias.name ilike ")
+            .push_bind(format!("%{}%", filter))
             .push("))");
     }
+    if hides_explicit_for_user(&pool, user_id)
+        .await
+        .map_err(internal_error)?
+    {
+        query_builder.push(
+            " AND EXISTS (SELECT 1 FROM song visible WHERE visible.album = album.id AND visible.explicit IS NOT TRUE)",
+        );
+    }
+    if let Some(release_type) = release_type {
+        let release_type = release_type.to_lowercase();
+        query_builder
+            .push(" AND (album.release_type = ")
+            .push_bind(release_type.clone())
+            .push(" OR ")
+            .push_bind(release_type)
+            .push(" = ANY(album.secondary_types))");
+    }
+    if let Some(compilation) = compilation {
+        query_builder
+            .push(" AND album.compilation = ")
+            .push_bind(compilation);
+    }
     query_builder.push(" GROUP BY album.id, album.name, artist.name, artist.id");
 
     if order_direction == "asc" {
@@ -433,6 +619,9 @@ pub async fn get_albums(
                 .map(|i| art_url.clone() + i)
                 .collect(),
             year: i.year,
+            release_type: i.release_type.clone(),
+            secondary_types: i.secondary_types.clone(),
+            original_date: i.original_date.clone(),
             count: i.count,
             artist: Some(ArtistPartial {
                 id: i.artist_id,
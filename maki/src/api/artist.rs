@@ -1,6 +1,7 @@
 use super::{
-    build_default_art_url, picture_url, resolve_artist_id, AlbumPartial, AlbumPartialRaw, Artist,
-    ArtistPartial,
+    build_default_art_url, middleware::jwt::OptionalAuthUser, picture_url, resolve_artist_id,
+    song::hides_explicit_for_user, AlbumPartial, AlbumPartialRaw, Artist, ArtistPartial,
+    DiscographySection, ARTIST_SORT_KEY,
 };
 use crate::api::ArtistRaw;
 use axum::{
@@ -21,14 +22,20 @@ use tracing::debug;
     responses(
         (status = 200, description = "Artist with albums", body = Artist),
         (status = 404, description = "Artist not found"),
-    )
+    ),
+    security(("bearer_token" = []))
 )]
 pub async fn get_artist(
     Path(id): Path<String>,
     Extension(pool): Extension<PgPool>,
     Host(host): Host,
+    OptionalAuthUser { payload }: OptionalAuthUser,
 ) -> Result<Json<Artist>, (StatusCode, String)> {
     let id = resolve_artist_id(&id, &pool).await?;
+    let user_id = payload.and_then(|p| p.sub.parse::<i32>().ok());
+    let hide_explicit = hides_explicit_for_user(&pool, user_id)
+        .await
+        .map_err(internal_error)?;
     match sqlx::query_as!(ArtistRaw, r#"
     SELECT artist.id, artist.slug, artist.name, artist.picture, artist.tags, artist.bio, artist.created_at, artist.updated_at
 
@@ -45,7 +52,7 @@ pub async fn get_artist(
         Ok(e) => {
                     // fetch albums
                     let albums_raw: Vec<AlbumPartialRaw> = sqlx::query_as!(AlbumPartialRaw, r#"
-                        SELECT album.id, album.slug, album.name, album.disambiguation, album.year, count(song.id), artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture,
+                        SELECT album.id, album.slug, album.name, album.disambiguation, album.year, album.release_type, album.secondary_types, album.original_date, count(song.id), artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture,
                         STRING_AGG(CAST(album_art.path AS VARCHAR), ',') as arts
 
                         FROM album
@@ -54,9 +61,10 @@ pub async fn get_artist(
                         LEFT JOIN album_art ON album.id = album_art.album
 
                         WHERE album.artist = $1
+                          AND (NOT $2::bool OR EXISTS (SELECT 1 FROM song visible WHERE visible.album = album.id AND visible.explicit IS NOT TRUE))
                         GROUP BY album.id, album.name, artist.id
                         order by album.created_at desc
-                        "#, id
+                        "#, id, hide_explicit
                     )
                     .fetch_all(&pool)
                     .await
@@ -64,7 +72,7 @@ pub async fn get_artist(
 
                     let art_url = build_default_art_url(host.clone());
 
-                    let albums = albums_raw.iter().map(|i| {
+                    let albums: Vec<AlbumPartial> = albums_raw.iter().map(|i| {
                         return AlbumPartial {
                             id: i.id,
                             slug: i.slug.clone(),
@@ -72,6 +80,9 @@ pub async fn get_artist(
                             disambiguation: i.disambiguation.clone(),
                             art: i.arts.clone().unwrap_or("".to_string()).split(',').map(|i| art_url.clone() + i).collect(),
                             year: i.year,
+                            release_type: i.release_type.clone(),
+                            secondary_types: i.secondary_types.clone(),
+                            original_date: i.original_date.clone(),
                             count: i.count,
                             artist: Some(ArtistPartial {
                                 id: i.artist_id,
@@ -94,6 +105,7 @@ pub async fn get_artist(
                         bio: e.bio,
                         created_at: e.created_at,
                         updated_at: e.updated_at,
+                        discography: group_discography(&albums),
                         albums,
                     }))
         },
@@ -101,6 +113,63 @@ pub async fn get_artist(
     }
 }
 
+/// Order of the sections in an artist's discography.
+const DISCOGRAPHY_ORDER: [&str; 9] = [
+    "album",
+    "ep",
+    "single",
+    "live",
+    "compilation",
+    "remix",
+    "soundtrack",
+    "other",
+    "unknown",
+];
+
+/// Which discography section an album belongs in. Secondary types win over the primary
+/// type, so a live EP is listed with the live releases.
+fn discography_section(album: &AlbumPartial) -> &'static str {
+    for secondary in ["live", "compilation", "remix", "soundtrack"] {
+        if album.secondary_types.iter().any(|t| t == secondary) {
+            return secondary;
+        }
+    }
+    match album.release_type.as_deref() {
+        Some("album") => "album",
+        Some("ep") => "ep",
+        Some("single") => "single",
+        Some(_) => "other",
+        None => "unknown",
+    }
+}
+
+/// Group albums by release type, newest first within each group.
+fn group_discography(albums: &[AlbumPartial]) -> Vec<DiscographySection> {
+    let release_date = |a: &AlbumPartial| {
+        a.original_date
+            .clone()
+            .or_else(|| a.year.map(|y| format!("{:04}", y)))
+    };
+    let mut sorted: Vec<&AlbumPartial> = albums.iter().collect();
+    // undated releases sort last
+    sorted.sort_by(|a, b| release_date(b).cmp(&release_date(a)));
+
+    DISCOGRAPHY_ORDER
+        .iter()
+        .filter_map(|&section| {
+            let albums: Vec<AlbumPartial> = sorted
+                .iter()
+                .filter(|a| discography_section(a) == section)
+                .map(|a| (*a).clone())
+                .collect();
+            (!albums.is_empty()).then(|| DiscographySection {
+                release_type: section.to_string(),
+                albums,
+            })
+        })
+        .collect()
+}
+
 #[derive(Deserialize, Default)]
 pub struct GetArtistParams {
     #[serde(default)]
@@ -127,7 +196,8 @@ impl SortByArtistOptions {
     fn as_str(&self) -> &str {
         match self {
             Self::Id => "artist.id",
-            Self::ArtistName => "artist.name",
+            // sort names put "The Beatles" under B and native-script names by their reading
+            Self::ArtistName => ARTIST_SORT_KEY,
         }
     }
 }
@@ -186,20 +256,15 @@ pub async fn get_artists(
 ) -> Result<axum::Json<AllArtistsPartial>, (StatusCode, String)> {
     let cursor_val: i32 = cursor.unwrap_or(0); // Default cursor to 0 if None
 
-    let current_artist = sqlx::query_as!(
-        ArtistPartial,
-        "SELECT artist.id, artist.slug, artist.name, artist.picture, COUNT(album) as num_albums
-        FROM artist
-        LEFT JOIN album ON album.artist = artist.id
-        WHERE artist.id = $1
-        GROUP BY artist.id, artist.name",
+    let current_sort_key = sqlx::query_scalar!(
+        r#"SELECT COALESCE(sort_key, lower(name)) AS "sort_key!" FROM artist WHERE id = $1"#,
         cursor_val
     )
     .fetch_optional(&pool)
     .await
     .map_err(internal_error)?;
 
-    debug!("Artist: {:?}", current_artist);
+    debug!("Artist sort key: {:?}", current_sort_key);
     let order_dir = dir.unwrap_or(DirOptions::Asc);
     let sort_column_typed = sortby.unwrap_or(SortByArtistOptions::ArtistName);
     let order_dir = order_dir.as_str(); // Default to ascending order
@@ -213,31 +278,45 @@ pub async fn get_artists(
         WHERE (SELECT(COUNT(album) > 0) FROM album WHERE artist.id = album.artist)", // Only show artists with albums
     );
 
-    // Add dynamic WHERE clause if current_artist exists
-    if let Some(e) = current_artist {
-        debug!("Artist: {:?}", &e);
-        if order_dir == "asc" {
-            query_builder
-                .push(" AND ")
-                // we can't bind a column here b/c autoescape :(
-                .push(sort_column)
-                .push(" > ")
-                .push_bind(e.name);
-        } else {
-            query_builder
-                .push(" AND ")
-                .push_bind(e.name)
-                .push(" > ")
-                .push(sort_column);
-        };
+    // Add dynamic WHERE clause if current_artist exists, keyed on (sort value, id) since
+    // sort keys aren't unique
+    if let Some(sort_key) = current_sort_key {
+        let cmp = if order_dir == "asc" { " > " } else { " < " };
+        match &sort_column_typed {
+            SortByArtistOptions::Id => {
+                query_builder
+                    .push(" AND artist.id")
+                    .push(cmp)
+                    .push_bind(cursor_val);
+            }
+            SortByArtistOptions::ArtistName => {
+                query_builder
+                    .push(" AND (")
+                    // we can't bind a column here b/c autoescape :(
+                    .push(sort_column)
+                    .push(", artist.id)")
+                    .push(cmp)
+                    .push("(")
+                    .push_bind(sort_key)
+                    .push(", ")
+                    .push_bind(cursor_val)
+                    .push(")");
+            }
+        }
     }
 
-    // where lower(name) ilike lower($1)
+    // matches the name, the sort name or any alias, so romanised searches find artists
+    // tagged in their native script and vice versa
     if let Some(filter) = filter {
+        let filter = format!("%{}%", filter);
         query_builder
-            .push(" AND lower(artist.name) ilike lower(")
-            .push_bind(format!("%{}%", filter))
-            .push(")");
+            .push(" AND (artist.name ILIKE ")
+            .push_bind(filter.clone())
+            .push(" OR artist.sort_name ILIKE ")
+            .push_bind(filter.clone())
+            .push(" OR EXISTS (SELECT 1 FROM artist_alias WHERE artist_alias.artist = artist.id AND artist_alias.name ILIKE ")
+            .push_bind(filter)
+            .push("))");
     }
 
     // Add GROUP BY clause
@@ -248,12 +327,12 @@ pub async fn get_artists(
             .push(" ORDER BY ")
             // again, we can't bind columns b/c autoescape
             .push(sort_column)
-            .push(" ASC");
+            .push(" ASC, artist.id ASC");
     } else {
         query_builder
             .push(" ORDER BY ")
             .push(sort_column)
-            .push(" DESC");
+            .push(" DESC, artist.id DESC");
     };
 
     // Add LIMIT clause
@@ -4,21 +4,32 @@ use utoipa::{
 };
 
 use crate::api::{
-    admin::RescanResponse,
+    admin::{
+        MergeGenresRequest, MergeGenresResponse, RescanResponse, SplitException,
+        SplitExceptionRequest, SplitExceptionResponse, TagEditResponse, TrackTagChanges,
+    },
     artist::AllArtistsPartial,
+    genre::{Genre, GenreArtist, GenrePartial, GenreTrack},
     home::{HomeRow, HomeRowType},
     index::{GenreEntry, IndexSong, SearchSong},
-    me::MeResponse,
+    me::{MeResponse, UpdateMeRequest},
+    person::{Person, PersonCredit},
     playlist::{
         AddTrackRequest, CreatePlaylistRequest, PlaylistDetail, PlaylistSummary, PlaylistTrack,
         ReorderTrackRequest, UpdatePlaylistRequest,
     },
     sign::{BatchSignRequest, SignResult},
     song::{
-        LikedResponse, MixProfileResponse, PlayHistoryEntry, SimilarTrack, TrackListItem,
-        TracksResponse,
+        LikedResponse, LyricsResponse, MixProfileResponse, PlayHistoryEntry, SimilarTrack,
+        TrackListItem, TracksResponse,
     },
-    Album, AlbumPartial, AllAlbumsPartial, Artist, ArtistPartial, Track,
+    work::{AllComposers, Composer, ComposerPartial, Movement, Recording, Work, WorkPartial},
+    Album, AlbumPartial, AllAlbumsPartial, Artist, ArtistPartial, Disc, DiscographySection, Track,
+};
+use crate::index::progress::ScanProgress;
+use crate::metadata::{
+    lyrics::LyricLine,
+    write::{AlbumTagEdit, TagChange, TagEdit},
 };
 
 struct BearerAuth;
@@ -45,15 +56,29 @@ impl Modify for BearerAuth {
     modifiers(&BearerAuth),
     paths(
         crate::api::me::get_me,
+        crate::api::me::patch_me,
         crate::api::admin::post_rescan,
+        crate::api::admin::get_scan,
+        crate::api::admin::get_scan_events,
         crate::api::admin::post_analyze,
+        crate::api::admin::patch_track,
+        crate::api::admin::patch_album,
+        crate::api::admin::post_merge_genres,
+        crate::api::admin::get_split_exceptions,
+        crate::api::admin::post_split_exception,
+        crate::api::admin::delete_split_exception,
         crate::api::album::get_album,
         crate::api::album::get_albums,
         crate::api::artist::get_artist,
         crate::api::artist::get_artists,
+        crate::api::person::get_person,
+        crate::api::work::get_composers,
+        crate::api::work::get_composer,
+        crate::api::work::get_work,
         crate::api::song::get_song,
         crate::api::song::get_similar_songs,
         crate::api::song::get_mix_profile,
+        crate::api::song::get_lyrics,
         crate::api::song::like_song,
         crate::api::song::scrobble_song,
         crate::api::song::set_playing,
@@ -64,6 +89,7 @@ impl Modify for BearerAuth {
         crate::api::index::index_songs,
         crate::api::index::search_songs,
         crate::api::index::get_genres,
+        crate::api::genre::get_genre,
         crate::api::home::home,
         crate::api::playlist::list_playlists,
         crate::api::playlist::create_playlist,
@@ -77,24 +103,53 @@ impl Modify for BearerAuth {
     components(schemas(
         Track,
         Album,
+        Disc,
         AlbumPartial,
+        DiscographySection,
         AllAlbumsPartial,
         Artist,
         ArtistPartial,
         AllArtistsPartial,
+        Person,
+        PersonCredit,
+        ComposerPartial,
+        AllComposers,
+        Composer,
+        WorkPartial,
+        Work,
+        Recording,
+        Movement,
         TrackListItem,
         TracksResponse,
         SimilarTrack,
         MixProfileResponse,
+        LyricsResponse,
+        LyricLine,
         LikedResponse,
         PlayHistoryEntry,
         SignResult,
         BatchSignRequest,
         MeResponse,
+        UpdateMeRequest,
         RescanResponse,
+        ScanProgress,
+        TagEdit,
+        AlbumTagEdit,
+        TagChange,
+        TrackTagChanges,
+        TagEditResponse,
+        MergeGenresRequest,
+        MergeGenresResponse,
+        SplitException,
+        SplitExceptionRequest,
+        SplitExceptionResponse,
         IndexSong,
         SearchSong,
         GenreEntry,
+        Genre,
+        GenrePartial,
+        GenreArtist,
+        GenreTrack,
         PlaylistSummary,
         PlaylistTrack,
         PlaylistDetail,
@@ -0,0 +1,285 @@
+use axum::{
+    extract::{Host, Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use utoipa::ToSchema;
+
+use super::{
+    build_default_art_url, middleware::jwt::OptionalAuthUser, picture_url,
+    song::hides_explicit_for_user, AlbumPartial, AlbumPartialRaw, ArtistPartial,
+};
+use crate::metadata::genre::genre_key;
+
+/// Every genre below `$1`, including `$1` itself. UNION stops at cycles in the hierarchy.
+const SUBGENRES: &str = r#"
+    WITH RECURSIVE subgenre(id) AS (
+        SELECT $1::integer
+        UNION
+        SELECT genre.id FROM genre JOIN subgenre ON genre.parent = subgenre.id
+    )
+"#;
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct GenrePartial {
+    id: i32,
+    name: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Genre {
+    id: i32,
+    name: String,
+    parent: Option<GenrePartial>,
+    /// Direct sub-genres, whose albums and tracks are included below
+    children: Vec<GenrePartial>,
+    album_count: i64,
+    song_count: i64,
+    albums: Vec<AlbumPartial>,
+    /// Album artists with the most tracks in the genre
+    artists: Vec<GenreArtist>,
+    /// Most played tracks first
+    tracks: Vec<GenreTrack>,
+    limit: i64,
+    offset: i64,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct GenreArtist {
+    id: i32,
+    slug: Option<String>,
+    name: String,
+    picture: Option<String>,
+    num_tracks: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct GenreTrack {
+    id: i32,
+    slug: String,
+    name: String,
+    duration_ms: i32,
+    plays: Option<i32>,
+    explicit: Option<bool>,
+    album: i32,
+    album_name: String,
+    artist_name: String,
+    #[serde(skip)]
+    art_path: Option<String>,
+    art_url: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct GetGenreParams {
+    #[serde(default)]
+    limit: Option<i64>,
+    #[serde(default)]
+    offset: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/genre/{id}",
+    tag = "search",
+    params(
+        ("id" = String, Path, description = "Genre ID or name"),
+        ("limit" = Option<i64>, Query, description = "Max albums, artists and tracks (default 50)"),
+        ("offset" = Option<i64>, Query, description = "Albums and tracks to skip"),
+    ),
+    responses(
+        (status = 200, description = "Genre with its sub-genres' albums, artists and tracks", body = Genre),
+        (status = 404, description = "Genre not found"),
+    ),
+    security(("bearer_token" = []))
+)]
+pub async fn get_genre(
+    Path(id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Query(GetGenreParams { limit, offset }): Query<GetGenreParams>,
+    Host(host): Host,
+    OptionalAuthUser { payload }: OptionalAuthUser,
+) -> Result<Json<Genre>, (StatusCode, String)> {
+    let limit = limit.unwrap_or(50).clamp(1, 500);
+    let offset = offset.unwrap_or(0).max(0);
+    let user_id = payload.and_then(|p| p.sub.parse::<i32>().ok());
+    let hide_explicit = hides_explicit_for_user(&pool, user_id)
+        .await
+        .map_err(internal_error)?;
+
+    let (id, name, parent): (i32, Option<String>, Option<i32>) = match id.parse::<i32>() {
+        Ok(id) => sqlx::query_as("SELECT id, name, parent FROM genre WHERE id = $1").bind(id),
+        Err(_) => sqlx::query_as("SELECT id, name, parent FROM genre WHERE key = $1 LIMIT 1")
+            .bind(genre_key(&id)),
+    }
+    .fetch_optional(&pool)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| (StatusCode::NOT_FOUND, format!("genre not found: {}", id)))?;
+
+    let parent = match parent {
+        Some(parent) => sqlx::query_as::<_, GenrePartial>(
+            "SELECT id, COALESCE(name, '') AS name FROM genre WHERE id = $1",
+        )
+        .bind(parent)
+        .fetch_optional(&pool)
+        .await
+        .map_err(internal_error)?,
+        None => None,
+    };
+    let children = sqlx::query_as::<_, GenrePartial>(
+        "SELECT id, COALESCE(name, '') AS name FROM genre WHERE parent = $1 ORDER BY lower(name)",
+    )
+    .bind(id)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?;
+
+    let (album_count, song_count): (i64, i64) = sqlx::query_as(&format!(
+        r#"
+        {}
+        SELECT
+            (SELECT COUNT(DISTINCT album) FROM album_genre WHERE genre IN (SELECT id FROM subgenre)),
+            (SELECT COUNT(DISTINCT song) FROM song_genre WHERE genre IN (SELECT id FROM subgenre))
+        "#,
+        SUBGENRES
+    ))
+    .bind(id)
+    .fetch_one(&pool)
+    .await
+    .map_err(internal_error)?;
+
+    let art_url = build_default_art_url(host.clone());
+
+    let albums: Vec<AlbumPartial> = sqlx::query_as::<_, AlbumPartialRaw>(&format!(
+        r#"
+        {}
+        SELECT album.id, album.slug, album.name, album.disambiguation, album.year,
+               album.release_type, album.secondary_types, album.original_date, count(song.id),
+               artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture,
+               (SELECT STRING_AGG(CAST(album_art.path AS VARCHAR), ',') FROM album_art
+                WHERE album_art.album = album.id) as arts
+        FROM album
+        LEFT JOIN song ON song.album = album.id
+        LEFT JOIN artist ON album.artist = artist.id
+        WHERE album.id IN (
+            SELECT album FROM album_genre WHERE genre IN (SELECT id FROM subgenre)
+        )
+          AND (NOT $4::bool OR EXISTS (SELECT 1 FROM song visible WHERE visible.album = album.id AND visible.explicit IS NOT TRUE))
+        GROUP BY album.id, artist.id
+        ORDER BY album.year DESC NULLS LAST, album.name, album.id
+        LIMIT $2 OFFSET $3
+        "#,
+        SUBGENRES
+    ))
+    .bind(id)
+    .bind(limit)
+    .bind(offset)
+    .bind(hide_explicit)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?
+    .into_iter()
+    .map(|a| AlbumPartial {
+        id: a.id,
+        slug: a.slug,
+        name: a.name,
+        disambiguation: a.disambiguation,
+        art: a
+            .arts
+            .map(|arts| arts.split(',').map(|p| art_url.clone() + p).collect())
+            .unwrap_or_default(),
+        year: a.year,
+        release_type: a.release_type,
+        secondary_types: a.secondary_types,
+        original_date: a.original_date,
+        count: a.count,
+        artist: Some(ArtistPartial {
+            id: a.artist_id,
+            slug: None,
+            name: a.artist_name,
+            picture: picture_url(host.clone(), a.artist_picture),
+            num_albums: None,
+        }),
+    })
+    .collect();
+
+    let mut artists = sqlx::query_as::<_, GenreArtist>(&format!(
+        r#"
+        {}
+        SELECT artist.id, artist.slug, artist.name, artist.picture,
+               COUNT(DISTINCT song.id) AS num_tracks
+        FROM song
+        JOIN artist ON song.album_artist = artist.id
+        WHERE song.id IN (
+            SELECT song FROM song_genre WHERE genre IN (SELECT id FROM subgenre)
+        )
+          AND (NOT $3::bool OR song.explicit IS NOT TRUE)
+        GROUP BY artist.id
+        ORDER BY num_tracks DESC, artist.name
+        LIMIT $2
+        "#,
+        SUBGENRES
+    ))
+    .bind(id)
+    .bind(limit)
+    .bind(hide_explicit)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?;
+    for artist in &mut artists {
+        artist.picture = picture_url(host.clone(), artist.picture.take());
+    }
+
+    let mut tracks = sqlx::query_as::<_, GenreTrack>(&format!(
+        r#"
+        {}
+        SELECT song.id, song.slug, song.name, song.duration_ms, song.plays, song.explicit,
+               song.album, album.name AS album_name, artist.name AS artist_name,
+               (SELECT album_art.path FROM album_art WHERE album_art.album = album.id LIMIT 1) AS art_path,
+               NULL::varchar AS art_url
+        FROM song
+        JOIN album ON song.album = album.id
+        JOIN artist ON song.album_artist = artist.id
+        WHERE song.id IN (
+            SELECT song FROM song_genre WHERE genre IN (SELECT id FROM subgenre)
+        )
+          AND (NOT $4::bool OR song.explicit IS NOT TRUE)
+        ORDER BY song.plays DESC NULLS LAST, song.name, song.id
+        LIMIT $2 OFFSET $3
+        "#,
+        SUBGENRES
+    ))
+    .bind(id)
+    .bind(limit)
+    .bind(offset)
+    .bind(hide_explicit)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?;
+    for track in &mut tracks {
+        track.art_url = track.art_path.as_ref().map(|p| format!("{}{}", art_url, p));
+    }
+
+    Ok(Json(Genre {
+        id,
+        name: name.unwrap_or_default(),
+        parent,
+        children,
+        album_count,
+        song_count,
+        albums,
+        artists,
+        tracks,
+        limit,
+        offset,
+    }))
+}
+
+fn internal_error<E: std::fmt::Debug>(err: E) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Internal error: {:?}", err),
+    )
+}
@@ -12,7 +12,13 @@ use tower::util::ServiceExt;
 use tower_http::services::fs::ServeFile;
 use tracing::{error, info};
 
-use crate::{api::resolve_song_id, config::HlsProfile, error::AppError, HlsState};
+use crate::{
+    api::{resolve_song_id, serve::song_source},
+    config::HlsProfile,
+    error::AppError,
+    metadata::{cue::CueSpan, formats::dsd::pcm_filter},
+    HlsState,
+};
 
 use std::time::Instant;
 
@@ -116,16 +122,21 @@ async fn ensure_segments(song_id: i32, state: &HlsState, pool: &PgPool) -> Resul
         return Ok(());
     }
 
-    let file_path = sqlx::query_scalar!("SELECT path FROM song WHERE id = $1", song_id)
-        .fetch_one(pool)
-        .await?;
+    let source = song_source(song_id, pool).await?;
 
     info!(
         "hls: transcoding song {} ({} profiles)",
         song_id,
         state.profiles.len()
     );
-    run_ffmpeg(&file_path, &state.profiles, &dir).await?;
+    run_ffmpeg(
+        &source.path,
+        source.span.as_ref(),
+        source.dsd,
+        &state.profiles,
+        &dir,
+    )
+    .await?;
     tokio::fs::write(&done, b"").await?;
     touch_access(state, song_id);
     Ok(())
@@ -133,6 +144,8 @@ async fn ensure_segments(song_id: i32, state: &HlsState, pool: &PgPool) -> Resul
 
 async fn run_ffmpeg(
     file_path: &str,
+    span: Option<&CueSpan>,
+    dsd: bool,
     profiles: &[HlsProfile],
     cache_dir: &PathBuf,
 ) -> Result<(), AppError> {
@@ -141,16 +154,26 @@ async fn run_ffmpeg(
         tokio::fs::create_dir_all(cache_dir.join(&profile.name)).await?;
     }
 
-    // [0:a]asplit=N[a0][a1]...[aN-1]
+    // [0:a]asplit=N[a0][a1]...[aN-1], with DSD converted to PCM before the split
     let filter = format!(
-        "[0:a]asplit={}{}",
+        "[0:a]{}asplit={}{}",
+        if dsd {
+            format!("{},", pcm_filter())
+        } else {
+            String::new()
+        },
         n,
         (0..n).map(|i| format!("[a{i}]")).collect::<String>()
     );
 
     info!("hls: spawning ffmpeg for {:?}", file_path);
     let mut cmd = Command::new("ffmpeg");
-    cmd.arg("-y").arg("-i").arg(file_path);
+    cmd.arg("-y");
+    // CUE virtual tracks only segment their own span of the source file
+    if let Some(span) = span {
+        cmd.args(span.input_args());
+    }
+    cmd.arg("-i").arg(file_path);
     cmd.arg("-filter_complex").arg(&filter);
 
     for (i, profile) in profiles.iter().enumerate() {
@@ -8,7 +8,9 @@ use sqlx::PgPool;
 
 use crate::api::{build_default_art_url, AlbumPartialRaw, AlbumPartialRawWithGenre};
 
-use super::{middleware::jwt::OptionalAuthUser, AlbumPartial, ArtistPartial};
+use super::{
+    middleware::jwt::OptionalAuthUser, song::hides_explicit_for_user, AlbumPartial, ArtistPartial,
+};
 
 pub type Home = Vec<HomeRow>;
 
@@ -48,6 +50,10 @@ pub async fn home(
     let art_url = build_default_art_url(host);
     let mut rows: Vec<HomeRow> = Vec::new();
     let user_id = payload.and_then(|p| p.sub.parse::<i32>().ok());
+    // albums stay listed while they have at least one song the user can see
+    let hide_explicit = hides_explicit_for_user(&pool, user_id)
+        .await
+        .map_err(internal_error)?;
 
     // Recently Played — only when authenticated
     if let Some(uid) = user_id {
@@ -62,7 +68,7 @@ pub async fn home(
                 GROUP BY s.album
                 ORDER BY s.album, last_played DESC
             )
-            SELECT album.id, album.slug, album.name, album.disambiguation, album.year, COUNT(song.id),
+            SELECT album.id, album.slug, album.name, album.disambiguation, album.year, album.release_type, album.secondary_types, album.original_date, COUNT(song.id),
                    artist.id AS artist_id, artist.name AS artist_name,
                    artist.picture AS artist_picture,
                    STRING_AGG(CAST(album_art.path AS VARCHAR), ',') AS arts
@@ -71,11 +77,13 @@ pub async fn home(
             LEFT JOIN song ON song.album         = album.id
             LEFT JOIN artist ON album.artist     = artist.id
             LEFT JOIN album_art ON album.id      = album_art.album
+            WHERE NOT $2::bool OR EXISTS (SELECT 1 FROM song visible WHERE visible.album = album.id AND visible.explicit IS NOT TRUE)
             GROUP BY album.id, album.name, artist.id, recent.last_played
             ORDER BY recent.last_played DESC
             LIMIT 13
             "#,
-            uid
+            uid,
+            hide_explicit
         )
         .fetch_all(&pool)
         .await
@@ -94,6 +102,9 @@ pub async fn home(
                 .map(|p| art_url.clone() + p)
                 .collect(),
             year: i.year,
+            release_type: i.release_type.clone(),
+            secondary_types: i.secondary_types.clone(),
+            original_date: i.original_date.clone(),
             count: i.count,
             artist: Some(ArtistPartial {
                 id: i.artist_id,
@@ -117,18 +128,20 @@ pub async fn home(
     let latest_albums: Vec<AlbumPartial> = match sqlx::query_as!(
         AlbumPartialRaw,
         r#"
-        SELECT album.id, album.slug, album.name, album.disambiguation, album.year, count(song.id), artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture,
+        SELECT album.id, album.slug, album.name, album.disambiguation, album.year, album.release_type, album.secondary_types, album.original_date, count(song.id), artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture,
         STRING_AGG(CAST(album_art.path AS VARCHAR), ',') as arts
 
         FROM album
         LEFT JOIN song ON song.album = album.id
         LEFT JOIN artist ON album.artist = artist.id
         LEFT JOIN album_art ON album.id = album_art.album
+        WHERE NOT $1::bool OR EXISTS (SELECT 1 FROM song visible WHERE visible.album = album.id AND visible.explicit IS NOT TRUE)
 
         GROUP BY album.id, album.name, artist.id
         ORDER BY album.created_at DESC
         LIMIT 13
 "#,
+        hide_explicit
     )
     .fetch_all(&pool)
     .await
@@ -140,6 +153,9 @@ pub async fn home(
             disambiguation: i.disambiguation.clone(),
             art: i.arts.clone().unwrap_or("".to_string()).split(',').map(|i| art_url.clone() + i).collect(),
             year:i.year,
+            release_type: i.release_type.clone(),
+            secondary_types: i.secondary_types.clone(),
+            original_date: i.original_date.clone(),
             count:i.count,
             artist:Some(ArtistPartial{
                 id: i.artist_id,
@@ -163,18 +179,20 @@ pub async fn home(
     let random_albums: Vec<AlbumPartial> = match sqlx::query_as!(
         AlbumPartialRaw,
         r#"
-        SELECT album.id, album.slug, album.name, album.disambiguation, album.year, count(song.id), artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture,
+        SELECT album.id, album.slug, album.name, album.disambiguation, album.year, album.release_type, album.secondary_types, album.original_date, count(song.id), artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture,
         STRING_AGG(CAST(album_art.path AS VARCHAR), ',') as arts
 
         FROM album
         LEFT JOIN song ON song.album = album.id
         LEFT JOIN artist ON album.artist = artist.id
         LEFT JOIN album_art ON album.id = album_art.album
+        WHERE NOT $1::bool OR EXISTS (SELECT 1 FROM song visible WHERE visible.album = album.id AND visible.explicit IS NOT TRUE)
 
         GROUP BY album.id, album.name, artist.id
         ORDER BY RANDOM()
         LIMIT 13
 "#,
+        hide_explicit
     )
     .fetch_all(&pool)
     .await
@@ -186,6 +204,9 @@ pub async fn home(
             disambiguation: i.disambiguation.clone(),
             art: i.arts.clone().unwrap_or("".to_string()).split(',').map(|i| art_url.clone() + i).collect(),
             year:i.year,
+            release_type: i.release_type.clone(),
+            secondary_types: i.secondary_types.clone(),
+            original_date: i.original_date.clone(),
             count:i.count,
             artist:Some(ArtistPartial{
                 id: i.artist_id,
@@ -219,7 +240,7 @@ pub async fn home(
             ORDER BY RANDOM()
             LIMIT 1
         )
-        SELECT album.id, album.slug, album.name, album.disambiguation, album.year, count(song.id), artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture, random_genre.name as genre,
+        SELECT album.id, album.slug, album.name, album.disambiguation, album.year, album.release_type, album.secondary_types, album.original_date, count(song.id), artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture, random_genre.name as genre,
         STRING_AGG(CAST(album_art.path AS VARCHAR), ',') as arts
 
         FROM album
@@ -228,11 +249,13 @@ pub async fn home(
         LEFT JOIN album_art ON album.id = album_art.album
         LEFT JOIN album_genre ON album.id = album_genre.album
         JOIN random_genre ON album_genre.genre = random_genre.id
+        WHERE NOT $1::bool OR EXISTS (SELECT 1 FROM song visible WHERE visible.album = album.id AND visible.explicit IS NOT TRUE)
 
         GROUP BY album.id, album.name, artist.id, random_genre.id, random_genre.name
         ORDER BY RANDOM()
         LIMIT 13
 "#,
+        hide_explicit
     )
     .fetch_all(&pool)
     .await
@@ -247,6 +270,9 @@ pub async fn home(
             disambiguation: i.disambiguation.clone(),
             art: i.arts.clone().unwrap_or("".to_string()).split(',').map(|i| art_url.clone() + i).collect(),
             year:i.year,
+            release_type: i.release_type.clone(),
+            secondary_types: i.secondary_types.clone(),
+            original_date: i.original_date.clone(),
             count:i.count,
             artist:Some(ArtistPartial{
                 id: i.artist_id,
@@ -6,7 +6,10 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use sqlx::{prelude::FromRow, PgPool, Postgres};
 
-use crate::api::build_default_art_url;
+use crate::api::{
+    build_default_art_url, middleware::jwt::OptionalAuthUser, song::hides_explicit_for_user,
+    ARTIST_SORT_KEY,
+};
 
 #[derive(Serialize, utoipa::ToSchema)]
 pub struct IndexSong {
@@ -31,11 +34,17 @@ pub struct SearchSong {
     tag = "search",
     responses(
         (status = 200, description = "Full song index for client-side search", body = [IndexSong]),
-    )
+    ),
+    security(("bearer_token" = []))
 )]
 pub async fn index_songs(
     Extension(pool): Extension<PgPool>,
+    OptionalAuthUser { payload }: OptionalAuthUser,
 ) -> Result<axum::Json<Vec<IndexSong>>, (StatusCode, String)> {
+    let user_id = payload.and_then(|p| p.sub.parse::<i32>().ok());
+    let hide_explicit = hides_explicit_for_user(&pool, user_id)
+        .await
+        .map_err(internal_error)?;
     match sqlx::query_as!(
         IndexSong,
         r#"
@@ -48,7 +57,9 @@ pub async fn index_songs(
     song
     LEFT JOIN album ON song.album = album.id
     LEFT JOIN artist ON song.album_artist = artist.id
+    WHERE NOT $1::bool OR song.explicit IS NOT TRUE
 "#,
+        hide_explicit
     )
     .fetch_all(&pool)
     .await
@@ -84,7 +95,7 @@ impl SortByOptions {
         match self {
             Self::Id => "song.id",
             Self::SongName => "song.name",
-            Self::ArtistName => "artist.name",
+            Self::ArtistName => ARTIST_SORT_KEY,
             Self::AlbumName => "album.name",
         }
     }
@@ -101,7 +112,8 @@ impl SortByOptions {
     ),
     responses(
         (status = 200, description = "Search results", body = [SearchSong]),
-    )
+    ),
+    security(("bearer_token" = []))
 )]
 /// search songs
 /// TODO: move to tantivy or meilisearch
@@ -111,7 +123,9 @@ pub async fn search_songs(
     Extension(pool): Extension<PgPool>,
     Query(params): Query<SearchQueryParams>,
     Host(host): Host,
+    OptionalAuthUser { payload }: OptionalAuthUser,
 ) -> Result<axum::Json<Vec<SearchSong>>, (StatusCode, String)> {
+    let user_id = payload.and_then(|p| p.sub.parse::<i32>().ok());
     let sort_by = params
         .sortby
         .as_ref()
@@ -139,17 +153,30 @@ pub async fn search_songs(
             LEFT JOIN song_artist ON song.id = song_artist.song
             LEFT JOIN album_art ON album.id = album_art.album
 
-            WHERE unaccent(song.name) ILIKE ('%' || unaccent('{0}') || '%')
-            OR unaccent(artist.name) ILIKE ('%' || unaccent('{0}') || '%')
-            OR unaccent(album.name) ILIKE ('%' || unaccent('{0}') || '%')
+            WHERE (unaccent(song.name) ILIKE ('%' || unaccent($2) || '%')
+            OR unaccent(artist.name) ILIKE ('%' || unaccent($2) || '%')
+            OR unaccent(artist.sort_name) ILIKE ('%' || unaccent($2) || '%')
+            OR unaccent(album.name) ILIKE ('%' || unaccent($2) || '%')
+            OR EXISTS (
+                SELECT 1 FROM artist_alias
+                WHERE artist_alias.artist IN (artist.id, song_artist.artist)
+                AND unaccent(artist_alias.name) ILIKE ('%' || unaccent($2) || '%')
+            ))
+            AND (NOT $1::bool OR song.explicit IS NOT TRUE)
 
             GROUP BY song.id, song.name, artist.name, album.name, artist.id, album.id
 
-            ORDER BY {1} {2}
+            ORDER BY {0} {1}
         "#,
-            slug, sort_by, dir
+            sort_by, dir
         ),
     )
+    .bind(
+        hides_explicit_for_user(&pool, user_id)
+            .await
+            .map_err(internal_error)?,
+    )
+    .bind(&slug)
     .fetch_all(&pool)
     .await
     {
@@ -178,6 +205,8 @@ pub async fn search_songs(
 pub struct GenreEntry {
     pub id: i32,
     pub name: String,
+    /// Genre this one is a sub-genre of
+    pub parent: Option<i32>,
     pub album_count: i64,
     pub song_count: i64,
 }
@@ -199,6 +228,7 @@ pub async fn get_genres(
         SELECT
             genre.id,
             genre.name,
+            genre.parent,
             COUNT(DISTINCT album_genre.album) AS album_count,
             COUNT(DISTINCT song_genre.song)   AS song_count
         FROM genre
@@ -217,6 +247,7 @@ pub async fn get_genres(
             .map(|r| GenreEntry {
                 id: r.id,
                 name: r.name.unwrap_or_default(),
+                parent: r.parent,
                 album_count: r.album_count.unwrap_or(0),
                 song_count: r.song_count.unwrap_or(0),
             })
@@ -1,8 +1,8 @@
 use axum::{extract::Extension, http::StatusCode, Json};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 
-use super::middleware::jwt::AuthUser;
+use super::{middleware::jwt::AuthUser, song::hides_explicit_for_user};
 
 #[derive(Debug, Serialize, utoipa::ToSchema)]
 pub struct MeResponse {
@@ -12,6 +12,13 @@ pub struct MeResponse {
     pub picture: Option<String>,
     pub is_admin: bool,
     pub lastfm_connected: bool,
+    /// Leave explicit songs out of browsing, search, playlists and the song index
+    pub hide_explicit: bool,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct UpdateMeRequest {
+    pub hide_explicit: Option<bool>,
 }
 
 #[utoipa::path(
@@ -54,5 +61,40 @@ pub async fn get_me(
         picture: row.image,
         is_admin: row.is_admin,
         lastfm_connected: row.lastfm_connected,
+        hide_explicit: hides_explicit_for_user(&pool, Some(user_id))
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
     }))
 }
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/me",
+    tag = "user",
+    request_body = UpdateMeRequest,
+    responses(
+        (status = 200, description = "Updated user info", body = MeResponse),
+    ),
+    security(("bearer_token" = []))
+)]
+pub async fn patch_me(
+    Extension(pool): Extension<PgPool>,
+    AuthUser { payload }: AuthUser,
+    Json(req): Json<UpdateMeRequest>,
+) -> Result<Json<MeResponse>, (StatusCode, String)> {
+    let user_id = payload
+        .sub
+        .parse::<i32>()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    if let Some(hide_explicit) = req.hide_explicit {
+        sqlx::query("UPDATE users SET hide_explicit = $2 WHERE id = $1")
+            .bind(user_id)
+            .bind(hide_explicit)
+            .execute(&pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    get_me(Extension(pool), AuthUser { payload }).await
+}
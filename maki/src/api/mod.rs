@@ -1,15 +1,18 @@
 pub mod admin;
 pub mod album;
 pub mod artist;
+pub mod genre;
 pub mod hls;
 pub mod home;
 pub mod index;
 pub mod me;
+pub mod person;
 pub mod playlist;
 pub mod remote;
 pub mod serve;
 pub mod sign;
 pub mod song;
+pub mod work;
 
 pub mod auth;
 
@@ -29,9 +32,18 @@ pub mod docs;
 
 pub fn router() -> Router {
     Router::new()
-        .route("/me", get(me::get_me))
+        .route("/me", get(me::get_me).patch(me::patch_me))
         .route("/admin/rescan", post(admin::post_rescan))
+        .route("/admin/scan", get(admin::get_scan))
+        .route("/admin/scan/events", get(admin::get_scan_events))
         .route("/admin/analyze", post(admin::post_analyze))
+        .route("/admin/genres/merge", post(admin::post_merge_genres))
+        .route(
+            "/admin/artist-splits",
+            get(admin::get_split_exceptions)
+                .post(admin::post_split_exception)
+                .delete(admin::delete_split_exception),
+        )
         .route("/lastfm/token", get(connect::lastfm::get_lastfm_token))
         .route(
             "/lastfm/session",
@@ -42,9 +54,10 @@ pub fn router() -> Router {
         // Search routes
         .route("/search/:slug", get(index::search_songs))
         // Track routes
-        .route("/track/:id", get(song::get_song))
+        .route("/track/:id", get(song::get_song).patch(admin::patch_track))
         .route("/track/:id/similar", get(song::get_similar_songs))
         .route("/track/:id/mix-profile", get(song::get_mix_profile))
+        .route("/track/:id/lyrics", get(song::get_lyrics))
         .route("/track/:id/sign", get(sign::sign_track_url))
         .route("/track/:id/stream", get(serve::serve_audio))
         .route("/track/:id/transcode", get(serve::serve_transcoded_audio))
@@ -59,18 +72,27 @@ pub fn router() -> Router {
         .route("/history", get(song::get_history))
         .route("/track/:id/play", post(song::set_playing))
         // Album routes
-        .route("/album/:id", get(album::get_album))
+        .route(
+            "/album/:id",
+            get(album::get_album).patch(admin::patch_album),
+        )
         .route("/album", get(album::get_albums))
         .route("/art/:id", get(serve::serve_image))
         // Artist Routes
         .route("/artist/:id", get(artist::get_artist))
         .route("/artist", get(artist::get_artists))
+        .route("/person/:id", get(person::get_person))
+        // Classical routes
+        .route("/composer", get(work::get_composers))
+        .route("/composer/:id", get(work::get_composer))
+        .route("/work/:id", get(work::get_work))
         // Track listing + batch sign
         .route("/tracks", get(song::get_tracks))
         .route("/tracks/sign", post(sign::batch_sign_track_urls))
         // Index routes
         .route("/index-q0b3.json", get(index::index_songs))
         .route("/genres", get(index::get_genres))
+        .route("/genre/:id", get(genre::get_genre))
         .route("/home/", get(home::home))
         // Playlist routes
         .route(
@@ -165,6 +187,46 @@ pub async fn resolve_artist_id(
         })
 }
 
+pub async fn resolve_composer_id(
+    s: &str,
+    pool: &sqlx::PgPool,
+) -> Result<i32, (axum::http::StatusCode, String)> {
+    if let Ok(id) = s.parse::<i32>() {
+        return Ok(id);
+    }
+    sqlx::query_scalar("SELECT id FROM composer WHERE slug = $1 LIMIT 1")
+        .bind(s)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| {
+            (
+                axum::http::StatusCode::NOT_FOUND,
+                format!("composer not found: {}", s),
+            )
+        })
+}
+
+pub async fn resolve_work_id(
+    s: &str,
+    pool: &sqlx::PgPool,
+) -> Result<i32, (axum::http::StatusCode, String)> {
+    if let Ok(id) = s.parse::<i32>() {
+        return Ok(id);
+    }
+    sqlx::query_scalar("SELECT id FROM work WHERE slug = $1 LIMIT 1")
+        .bind(s)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| {
+            (
+                axum::http::StatusCode::NOT_FOUND,
+                format!("work not found: {}", s),
+            )
+        })
+}
+
 pub fn build_default_art_url(host: String) -> String {
     let art_path = "api/v1/art/";
     // build default art url base
@@ -191,7 +253,11 @@ pub fn picture_url(host: String, picture: Option<String>) -> Option<String> {
     picture.map(|p| format!("{}{}", build_default_art_url(host), p))
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+/// SQL expression artists are ordered by: the folded sort name, or the lower-cased name
+/// for artists that haven't been rescanned since sort names were stored.
+pub const ARTIST_SORT_KEY: &str = "COALESCE(artist.sort_key, lower(artist.name))";
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct Track {
     id: i32,
     slug: String,
@@ -199,7 +265,9 @@ pub struct Track {
     album_artist: i32,
     artists: Vec<ArtistPartial>,
     plays: Option<i32>,
+    /// Whole seconds, kept for older clients
     duration: i32,
+    duration_ms: i32,
     liked: Option<bool>,
     #[serde(with = "time::serde::timestamp::option")]
     #[schema(value_type = Option<i64>)]
@@ -211,9 +279,32 @@ pub struct Track {
     sample_rate: Option<i32>,
     bits_per_sample: Option<i32>,
     num_channels: Option<i32>,
+    /// Average bitrate in kbps
+    bitrate: Option<i32>,
+    total_samples: Option<i64>,
+    /// Gapless playback: samples to trim from the start of the decoded stream
+    encoder_delay: Option<i32>,
+    /// Gapless playback: samples to trim from the end of the decoded stream
+    encoder_padding: Option<i32>,
+    /// ReplayGain 2.0 track gain in dB, from tags or measured with EBU R128
+    replaygain_track_gain: Option<f32>,
+    /// Linear track peak, 1.0 is full scale
+    replaygain_track_peak: Option<f32>,
+    /// ReplayGain 2.0 album gain in dB, from tags or derived from the album's tracks
+    replaygain_album_gain: Option<f32>,
+    replaygain_album_peak: Option<f32>,
     composer: Option<String>,
     isrc: Option<String>,
     bpm: Option<i32>,
+    /// Classical work this track is a movement of
+    work: Option<i32>,
+    movement: Option<String>,
+    movement_number: Option<i32>,
+    movement_total: Option<i32>,
+    conductor: Option<String>,
+    orchestra: Option<String>,
+    /// Parental advisory: explicit, clean, or null when unrated
+    explicit: Option<bool>,
     #[serde(with = "time::serde::rfc3339")]
     #[schema(value_type = String)]
     created_at: OffsetDateTime,
@@ -237,14 +328,30 @@ struct TrackRaw {
     album_artist: i32,
     liked: Option<bool>,
     duration: i32,
+    duration_ms: i32,
     plays: Option<i32>,
     lossless: Option<bool>,
     sample_rate: Option<i32>,
     bits_per_sample: Option<i32>,
     num_channels: Option<i32>,
+    bitrate: Option<i32>,
+    total_samples: Option<i64>,
+    encoder_delay: Option<i32>,
+    encoder_padding: Option<i32>,
+    replaygain_track_gain: Option<f32>,
+    replaygain_track_peak: Option<f32>,
+    replaygain_album_gain: Option<f32>,
+    replaygain_album_peak: Option<f32>,
     composer: Option<String>,
     isrc: Option<String>,
     bpm: Option<i32>,
+    work: Option<i32>,
+    movement: Option<String>,
+    movement_number: Option<i32>,
+    movement_total: Option<i32>,
+    conductor: Option<String>,
+    orchestra: Option<String>,
+    explicit: Option<bool>,
     #[serde(with = "time::serde::rfc3339")]
     created_at: OffsetDateTime,
     #[serde(with = "time::serde::timestamp::option")]
@@ -268,6 +375,12 @@ pub struct Album {
     genres: Vec<String>,
     copyright: Option<String>,
     label: Option<String>,
+    compilation: bool,
+    /// Whether any track is explicit, including tracks hidden from this user
+    explicit: bool,
+    release_type: Option<String>,
+    secondary_types: Vec<String>,
+    original_date: Option<String>,
     #[serde(with = "time::serde::rfc3339")]
     #[schema(value_type = String)]
     created_at: OffsetDateTime,
@@ -275,7 +388,27 @@ pub struct Album {
     #[schema(value_type = Option<i64>)]
     updated_at: Option<OffsetDateTime>,
     artist: ArtistPartial,
+    /// Every track in disc and track order
     tracks: Option<Vec<Track>>,
+    /// Discs in the release when tagged, which may be more than `discs` holds
+    disc_total: Option<i32>,
+    discs: Vec<Disc>,
+    /// Whether track numbering has gaps on any disc, or whole discs are missing
+    missing_tracks: bool,
+}
+
+/// One disc of an album, with its tracks.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Disc {
+    /// Disc number, 1 for tracks without one
+    disc: i32,
+    subtitle: Option<String>,
+    /// Art from the disc's own folder, when it has one
+    art_url: Option<String>,
+    track_total: Option<i32>,
+    /// Track numbers missing from this disc, up to its track total or its last track
+    missing: Vec<i32>,
+    tracks: Vec<Track>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -287,6 +420,9 @@ pub struct AlbumPartialRaw {
     count: Option<i64>,
     arts: Option<String>,
     year: Option<i32>,
+    release_type: Option<String>,
+    secondary_types: Vec<String>,
+    original_date: Option<String>,
     artist_id: i32,
     artist_name: String,
     artist_picture: Option<String>,
@@ -300,6 +436,9 @@ pub struct AlbumPartialRawWithGenre {
     count: Option<i64>,
     arts: Option<String>,
     year: Option<i32>,
+    release_type: Option<String>,
+    secondary_types: Vec<String>,
+    original_date: Option<String>,
     artist_id: i32,
     artist_name: String,
     artist_picture: Option<String>,
@@ -314,6 +453,12 @@ pub struct AlbumPartial {
     disambiguation: Option<String>,
     art: Vec<String>,
     year: Option<i32>,
+    /// MusicBrainz primary type: album, single, ep, broadcast or other
+    release_type: Option<String>,
+    /// MusicBrainz secondary types, e.g. compilation, live, remix
+    secondary_types: Vec<String>,
+    /// First release date, YYYY[-MM[-DD]]
+    original_date: Option<String>,
     count: Option<i64>,
     artist: Option<ArtistPartial>,
 }
@@ -335,6 +480,10 @@ pub struct AlbumRaw {
     arts: Option<String>,
     copyright: Option<String>,
     label: Option<String>,
+    compilation: bool,
+    release_type: Option<String>,
+    secondary_types: Vec<String>,
+    original_date: Option<String>,
     #[serde(with = "time::serde::rfc3339")]
     created_at: OffsetDateTime,
     #[serde(with = "time::serde::timestamp::option")]
@@ -364,6 +513,16 @@ pub struct Artist {
     #[schema(value_type = Option<i64>)]
     updated_at: Option<OffsetDateTime>,
     albums: Vec<AlbumPartial>,
+    /// The same albums grouped by release type
+    discography: Vec<DiscographySection>,
+}
+
+/// An artist's albums of one release type, newest first
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DiscographySection {
+    /// album, ep, single, live, compilation, remix, soundtrack, other or unknown
+    release_type: String,
+    albums: Vec<AlbumPartial>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -0,0 +1,212 @@
+use axum::{
+    extract::{Host, Path},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use utoipa::ToSchema;
+
+use super::{
+    build_default_art_url, middleware::jwt::OptionalAuthUser, picture_url, resolve_artist_id,
+    song::hides_explicit_for_user, work::WorkPartial, AlbumPartial, AlbumPartialRaw, ArtistPartial,
+};
+
+/// Everything one person contributed to: albums as the album artist, credited and
+/// featured tracks, and works they composed.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Person {
+    id: i32,
+    slug: String,
+    name: String,
+    picture: Option<String>,
+    bio: Option<String>,
+    /// Distinct roles across albums, credits and works, e.g. artist, producer, composer
+    roles: Vec<String>,
+    albums: Vec<AlbumPartial>,
+    credits: Vec<PersonCredit>,
+    works: Vec<WorkPartial>,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct PersonCredit {
+    /// Credited role, or `featured` for tracks they perform on under another artist
+    role: String,
+    /// Instrument or vocal part, for performers
+    detail: Option<String>,
+    track: i32,
+    track_slug: String,
+    track_name: String,
+    album: i32,
+    album_name: String,
+    artist_name: String,
+    #[serde(skip)]
+    art_path: Option<String>,
+    art_url: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/person/{id}",
+    tag = "artists",
+    params(("id" = String, Path, description = "Artist ID or slug")),
+    responses(
+        (status = 200, description = "Person with everything they contributed to", body = Person),
+        (status = 404, description = "Person not found"),
+    ),
+    security(("bearer_token" = []))
+)]
+pub async fn get_person(
+    Path(id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Host(host): Host,
+    OptionalAuthUser { payload }: OptionalAuthUser,
+) -> Result<Json<Person>, (StatusCode, String)> {
+    let id = resolve_artist_id(&id, &pool).await?;
+    let user_id = payload.and_then(|p| p.sub.parse::<i32>().ok());
+    let hide_explicit = hides_explicit_for_user(&pool, user_id)
+        .await
+        .map_err(internal_error)?;
+    let (slug, name, picture, bio): (String, String, Option<String>, Option<String>) =
+        sqlx::query_as("SELECT slug, name, picture, bio FROM artist WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&pool)
+            .await
+            .map_err(internal_error)?
+            .ok_or_else(|| (StatusCode::NOT_FOUND, format!("person not found: {}", id)))?;
+
+    let art_url = build_default_art_url(host.clone());
+
+    let albums: Vec<AlbumPartial> = sqlx::query_as::<_, AlbumPartialRaw>(
+        r#"
+        SELECT album.id, album.slug, album.name, album.disambiguation, album.year,
+               album.release_type, album.secondary_types, album.original_date, count(song.id),
+               artist.id as artist_id, artist.name as artist_name, artist.picture as artist_picture,
+               STRING_AGG(CAST(album_art.path AS VARCHAR), ',') as arts
+        FROM album
+        LEFT JOIN song ON song.album = album.id
+        LEFT JOIN artist ON album.artist = artist.id
+        LEFT JOIN album_art ON album.id = album_art.album
+        WHERE album.artist = $1
+          AND (NOT $2::bool OR EXISTS (SELECT 1 FROM song visible WHERE visible.album = album.id AND visible.explicit IS NOT TRUE))
+        GROUP BY album.id, artist.id
+        ORDER BY album.year DESC NULLS LAST, album.name
+        "#,
+    )
+    .bind(id)
+    .bind(hide_explicit)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?
+    .into_iter()
+    .map(|a| AlbumPartial {
+        id: a.id,
+        slug: a.slug,
+        name: a.name,
+        disambiguation: a.disambiguation,
+        art: a
+            .arts
+            .map(|arts| arts.split(',').map(|p| art_url.clone() + p).collect())
+            .unwrap_or_default(),
+        year: a.year,
+        release_type: a.release_type,
+        secondary_types: a.secondary_types,
+        original_date: a.original_date,
+        count: a.count,
+        artist: Some(ArtistPartial {
+            id: a.artist_id,
+            slug: None,
+            name: a.artist_name,
+            picture: picture_url(host.clone(), a.artist_picture),
+            num_albums: None,
+        }),
+    })
+    .collect();
+
+    let mut credits = sqlx::query_as::<_, PersonCredit>(
+        r#"
+        SELECT credit.role, NULLIF(credit.detail, '') AS detail, credit.track,
+               credit.track_slug, credit.track_name, credit.album,
+               album.name AS album_name, artist.name AS artist_name,
+               (SELECT album_art.path FROM album_art WHERE album_art.album = album.id LIMIT 1) AS art_path,
+               NULL::varchar AS art_url
+        FROM (
+            SELECT song_credit.role, song_credit.detail, song.id AS track, song.slug AS track_slug,
+                   song.name AS track_name, song.album
+            FROM song_credit
+            JOIN song ON song.id = song_credit.song
+            WHERE song_credit.artist = $1 AND (NOT $2::bool OR song.explicit IS NOT TRUE)
+            UNION ALL
+            SELECT 'featured', '', song.id, song.slug, song.name, song.album
+            FROM song_artist
+            JOIN song ON song.id = song_artist.song
+            WHERE song_artist.artist = $1 AND song.album_artist <> $1
+              AND (NOT $2::bool OR song.explicit IS NOT TRUE)
+        ) credit
+        JOIN album ON album.id = credit.album
+        JOIN artist ON artist.id = album.artist
+        ORDER BY credit.role, credit.detail, album.year NULLS LAST, album.name, credit.track_name
+        "#,
+    )
+    .bind(id)
+    .bind(hide_explicit)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?;
+    for credit in &mut credits {
+        credit.art_url = credit
+            .art_path
+            .as_ref()
+            .map(|p| format!("{}{}", art_url, p));
+    }
+
+    // composers are matched to artists by name, which is what both slugs are made from
+    let works = sqlx::query_as::<_, WorkPartial>(
+        r#"
+        SELECT work.id, work.slug, work.name, COUNT(DISTINCT song.album) AS num_recordings
+        FROM work
+        JOIN composer ON composer.id = work.composer
+        LEFT JOIN song ON song.work = work.id AND (NOT $2::bool OR song.explicit IS NOT TRUE)
+        WHERE composer.slug = $1
+        GROUP BY work.id
+        ORDER BY lower(work.name), work.id
+        "#,
+    )
+    .bind(&slug)
+    .bind(hide_explicit)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?;
+
+    let mut roles: Vec<String> = Vec::new();
+    if !albums.is_empty() {
+        roles.push("artist".to_string());
+    }
+    for credit in &credits {
+        if !roles.contains(&credit.role) {
+            roles.push(credit.role.clone());
+        }
+    }
+    if !works.is_empty() {
+        roles.push("composer".to_string());
+    }
+
+    Ok(Json(Person {
+        id,
+        slug,
+        name,
+        picture: picture_url(host, picture),
+        bio: bio.filter(|b| !b.is_empty()),
+        roles,
+        albums,
+        credits,
+        works,
+    }))
+}
+
+fn internal_error<E: std::fmt::Debug>(err: E) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Internal error: {:?}", err),
+    )
+}
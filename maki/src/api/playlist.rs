@@ -11,7 +11,7 @@ use time::OffsetDateTime;
 
 use super::middleware::jwt::AuthUser;
 use crate::api::build_default_art_url;
-use crate::api::song::liked_ids_for_user;
+use crate::api::song::{hides_explicit_for_user, liked_ids_for_user};
 
 // ── Response types ────────────────────────────────────────────────────────────
 
@@ -34,10 +34,12 @@ pub struct PlaylistTrack {
     pub song_id: i32,
     pub name: String,
     pub duration: i32,
+    pub duration_ms: i32,
     pub number: Option<i32>,
     pub disc: Option<i32>,
     pub liked: Option<bool>,
     pub lossless: Option<bool>,
+    pub explicit: Option<bool>,
     pub album_id: i32,
     pub album_name: String,
     pub artist_name: String,
@@ -228,7 +230,7 @@ pub async fn get_playlist(
     let rows = sqlx::query!(
         r#"
         SELECT pi.id AS item_id, pi.song_id, pi.prev_song_id, pi.next_song_id,
-               s.name, s.duration, s.number, s.disc, s.lossless,
+               s.name, s.duration, s.duration_ms AS "duration_ms!", s.number, s.disc, s.lossless, s.explicit,
                s.album AS album_id,
                album.name AS album_name,
                artist.name AS artist_name,
@@ -247,6 +249,9 @@ pub async fn get_playlist(
 
     let song_ids: Vec<i32> = rows.iter().map(|r| r.song_id).collect();
     let liked_ids = liked_ids_for_user(&pool, Some(user_id)).await;
+    let hide_explicit = hides_explicit_for_user(&pool, Some(user_id))
+        .await
+        .map_err(internal_error)?;
 
     // Build an index so we can traverse the doubly-linked list in O(n)
     let id_to_idx: HashMap<i32, usize> = rows
@@ -263,28 +268,40 @@ pub async fn get_playlist(
     if let Some(mut idx) = head_idx {
         loop {
             let r = &rows[idx];
-            ordered.push(PlaylistTrack {
-                item_id: r.item_id,
-                song_id: r.song_id,
-                name: r.name.clone(),
-                duration: r.duration,
-                number: r.number,
-                disc: r.disc,
-                liked: Some(liked_ids.contains(&r.song_id)),
-                lossless: r.lossless,
-                album_id: r.album_id,
-                album_name: r.album_name.clone(),
-                artist_name: r.artist_name.clone(),
-                art_url: r.art_path.as_ref().map(|p| format!("{}{}", art_base, p)),
-                prev_item_id: r.prev_song_id,
-                next_item_id: r.next_song_id,
-            });
+            if !(hide_explicit && r.explicit == Some(true)) {
+                ordered.push(PlaylistTrack {
+                    item_id: r.item_id,
+                    song_id: r.song_id,
+                    name: r.name.clone(),
+                    duration: r.duration,
+                    duration_ms: r.duration_ms,
+                    number: r.number,
+                    disc: r.disc,
+                    liked: Some(liked_ids.contains(&r.song_id)),
+                    lossless: r.lossless,
+                    explicit: r.explicit,
+                    album_id: r.album_id,
+                    album_name: r.album_name.clone(),
+                    artist_name: r.artist_name.clone(),
+                    art_url: r.art_path.as_ref().map(|p| format!("{}{}", art_base, p)),
+                    prev_item_id: r.prev_song_id,
+                    next_item_id: r.next_song_id,
+                });
+            }
             match r.next_song_id.and_then(|nid| id_to_idx.get(&nid)) {
                 Some(&next_idx) => idx = next_idx,
                 None => break,
             }
         }
     }
+    // link visible neighbours to each other across the hidden items between them
+    if hide_explicit {
+        let item_ids: Vec<i32> = ordered.iter().map(|t| t.item_id).collect();
+        for (i, track) in ordered.iter_mut().enumerate() {
+            track.prev_item_id = i.checked_sub(1).map(|prev| item_ids[prev]);
+            track.next_item_id = item_ids.get(i + 1).copied();
+        }
+    }
 
     Ok(Json(PlaylistDetail {
         id: playlist.id,
@@ -482,7 +499,7 @@ pub async fn add_track(
     // Return the new item with song info
     let song = sqlx::query!(
         r#"
-        SELECT s.name, s.duration, s.number, s.disc, s.lossless,
+        SELECT s.name, s.duration, s.duration_ms AS "duration_ms!", s.number, s.disc, s.lossless, s.explicit,
                s.album AS album_id, album.name AS album_name, artist.name AS artist_name,
                (SELECT album_art.path FROM album_art WHERE album_art.album = s.album LIMIT 1) AS art_path
         FROM song s
@@ -503,10 +520,12 @@ pub async fn add_track(
         song_id: req.song_id,
         name: song.name,
         duration: song.duration,
+        duration_ms: song.duration_ms,
         number: song.number,
         disc: song.disc,
         liked: Some(liked_ids.contains(&req.song_id)),
         lossless: song.lossless,
+        explicit: song.explicit,
         album_id: song.album_id,
         album_name: song.album_name,
         artist_name: song.artist_name,
@@ -19,10 +19,49 @@ use tower::util::ServiceExt;
 use tower_http::services::fs::ServeFile;
 use tracing::{debug, error};
 
-use crate::{api::resolve_song_id, error::AppError};
+use crate::{
+    api::resolve_song_id,
+    error::AppError,
+    metadata::{cue::CueSpan, formats::dsd::pcm_filter},
+};
 
 use super::middleware::hmac::HmacAuth;
 
+/// Where a song's audio lives: its file and, for CUE virtual tracks, the span to cut from it.
+/// `dsd` marks 1-bit DSD sources, which are converted to PCM whenever they're re-encoded.
+pub(crate) struct SongSource {
+    pub path: String,
+    pub duration_ms: i32,
+    pub span: Option<CueSpan>,
+    pub dsd: bool,
+}
+
+pub(crate) async fn song_source(song_id: i32, pool: &PgPool) -> Result<SongSource, sqlx::Error> {
+    let song = sqlx::query!(
+        r#"
+        SELECT path, duration_ms AS "duration_ms!", cue_track, start_ms, end_ms, bits_per_sample
+        FROM song WHERE id = $1
+        "#,
+        song_id
+    )
+    .fetch_one(pool)
+    .await?;
+    let span = song
+        .cue_track
+        .zip(song.start_ms)
+        .map(|(track, start)| CueSpan {
+            track: track as u32,
+            start_ms: start as u32,
+            end_ms: song.end_ms.map(|e| e as u32),
+        });
+    Ok(SongSource {
+        path: song.path,
+        duration_ms: song.duration_ms,
+        span,
+        dsd: song.bits_per_sample == Some(1),
+    })
+}
+
 pub async fn serve_audio(
     Path(id): Path<String>,
     Extension(pool): Extension<PgPool>,
@@ -31,17 +70,34 @@ pub async fn serve_audio(
 ) -> impl IntoResponse {
     let id_parsed = resolve_song_id(&id, &pool).await.map_err(|(s, e)| (s, e))?;
 
-    match sqlx::query!(r#"SELECT path FROM song WHERE id = $1"#, id_parsed)
-        .fetch_one(&pool)
-        .await
-    {
-        Ok(f) => match ServeFile::new(f.path).oneshot(request).await {
-            Ok(res) => Ok(res),
-            Err(err) => Err((
-                StatusCode::NOT_FOUND,
-                format!("Something went wrong when serving a file: {}", err),
-            )),
-        },
+    match song_source(id_parsed, &pool).await {
+        Ok(SongSource {
+            path,
+            duration_ms,
+            span,
+            dsd,
+        }) => {
+            // virtual tracks are cut from their file and DSD converted to PCM as they stream
+            if span.is_some() || dsd {
+                let params = ServeTranscodedAudioParams {
+                    dir: path,
+                    codec: TranscodeCodec::Flac,
+                    dps: String::new(),
+                    span,
+                    dsd,
+                };
+                return Ok(stream_ffmpeg(id_parsed, &params, duration_ms, None)
+                    .await
+                    .into_response());
+            }
+            match ServeFile::new(path).oneshot(request).await {
+                Ok(res) => Ok(res),
+                Err(err) => Err((
+                    StatusCode::NOT_FOUND,
+                    format!("Something went wrong when serving a file: {}", err),
+                )),
+            }
+        }
         Err(err) => Err((
             StatusCode::NOT_FOUND,
             format!("Something went wrong when finding the file: {}", err),
@@ -71,6 +127,10 @@ struct ServeTranscodedAudioParams {
     dir: String,
     codec: TranscodeCodec,
     dps: String,
+    #[serde(skip)]
+    span: Option<CueSpan>,
+    #[serde(skip)]
+    dsd: bool,
 }
 #[derive(Debug, Deserialize)]
 enum TranscodeCodec {
@@ -148,11 +208,15 @@ async fn setup_ffmpeg(
     params: &ServeTranscodedAudioParams,
 ) -> Result<tokio::process::Child, AppError> {
     let mut command = Command::new("ffmpeg");
+    if let Some(span) = &params.span {
+        command.args(span.input_args());
+    }
+    command.arg("-i").arg(&params.dir).arg("-map").arg("0:a:0");
+    // DSD has no lossy or FLAC encoding of its own, so decimate it to PCM first
+    if params.dsd {
+        command.arg("-af").arg(pcm_filter());
+    }
     command
-        .arg("-i")
-        .arg(&params.dir)
-        .arg("-map")
-        .arg("0:a:0")
         .arg("-c:a")
         .arg(params.codec.as_encoder())
         .arg("-f")
@@ -206,17 +270,19 @@ pub async fn serve_transcoded_audio(
         .await
         .map_err(|(_, e)| anyhow::anyhow!(e))?;
 
-    let song = sqlx::query!(
-        r#"SELECT path, duration FROM song WHERE id = $1"#,
-        id_parsed
-    )
-    .fetch_one(&pool)
-    .await?;
+    let SongSource {
+        path,
+        duration_ms,
+        span,
+        dsd,
+    } = song_source(id_parsed, &pool).await?;
 
     let tparams = ServeTranscodedAudioParams {
-        dir: song.path,
+        dir: path,
         codec: TranscodeCodec::from_str(&params.codec),
         dps: params.dps.clone(),
+        span,
+        dsd,
     };
 
     // Cache key includes codec and bitrate so different quality levels don't collide
@@ -238,7 +304,18 @@ pub async fn serve_transcoded_audio(
         return Ok(res.into_response());
     }
 
-    let mut child = setup_ffmpeg(&tparams).await?;
+    stream_ffmpeg(id_parsed, &tparams, duration_ms, Some(&cache_file_path)).await
+}
+
+/// Stream ffmpeg's output to the client as it's encoded, copying it to `cache_file_path`
+/// when given.
+async fn stream_ffmpeg(
+    song_id: i32,
+    tparams: &ServeTranscodedAudioParams,
+    duration_ms: i32,
+    cache_file_path: Option<&str>,
+) -> Result<Response, AppError> {
+    let mut child = setup_ffmpeg(tparams).await?;
 
     let stdout = child
         .stdout
@@ -247,8 +324,13 @@ pub async fn serve_transcoded_audio(
 
     let mut stream = ReaderStream::new(stdout).boxed();
 
-    std::fs::create_dir_all(std::path::Path::new(&cache_file_path).parent().unwrap())?;
-    let mut cache_file = File::create(&cache_file_path).await?;
+    let mut cache_file = match cache_file_path {
+        Some(cache_file_path) => {
+            std::fs::create_dir_all(std::path::Path::new(cache_file_path).parent().unwrap())?;
+            Some(File::create(cache_file_path).await?)
+        }
+        None => None,
+    };
 
     // Duplex: stream to client and write to cache simultaneously
     let (mut writer, reader) = tokio::io::duplex(1024 * 1024 * 128);
@@ -261,9 +343,11 @@ pub async fn serve_transcoded_audio(
                         error!("Error writing to writer.");
                         break;
                     }
-                    if cache_file.write_all(&data).await.is_err() {
-                        error!("Error writing to cache file.");
-                        break;
+                    if let Some(cache_file) = &mut cache_file {
+                        if cache_file.write_all(&data).await.is_err() {
+                            error!("Error writing to cache file.");
+                            break;
+                        }
                     }
                 }
                 Err(e) => {
@@ -284,12 +368,15 @@ pub async fn serve_transcoded_audio(
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, content_type)
         .header(header::ACCEPT_RANGES, "none")
-        .header("X-Content-Duration", song.duration.to_string())
+        .header(
+            "X-Content-Duration",
+            format!("{:.3}", duration_ms as f64 / 1000.0),
+        )
         .header(
             header::CONTENT_DISPOSITION,
             format!(
                 "inline; filename=\"{}.{}\"",
-                id_parsed,
+                song_id,
                 tparams.codec.as_container_format().as_str()
             ),
         )
@@ -20,6 +20,7 @@ pub struct TrackListItem {
     pub slug: String,
     pub name: String,
     pub duration: i32,
+    pub duration_ms: i32,
     pub number: Option<i32>,
     pub disc: Option<i32>,
     pub lossless: Option<bool>,
@@ -53,6 +54,7 @@ use crate::{
     analysis::{decode_vector, FEATURE_VERSION, MIX_PROFILE_VERSION},
     api::{build_default_art_url, resolve_song_id, ArtistPartial, Track, TrackRaw},
     clients,
+    metadata::lyrics::LyricLine,
 };
 
 use super::middleware::jwt::{AuthUser, OptionalAuthUser};
@@ -280,6 +282,53 @@ pub async fn get_mix_profile(
     .into_response())
 }
 
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct LyricsResponse {
+    /// Unsynchronised lyrics text
+    pub plain: Option<String>,
+    /// Time-synced lines, empty when only plain lyrics are known
+    pub synced: Vec<LyricLine>,
+    /// "embedded" or "sidecar"
+    pub source: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/track/{id}/lyrics",
+    tag = "tracks",
+    params(("id" = String, Path, description = "Track ID or slug")),
+    responses(
+        (status = 200, description = "Lyrics for the track", body = LyricsResponse),
+        (status = 404, description = "Track not found or has no lyrics"),
+    )
+)]
+pub async fn get_lyrics(
+    Path(id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+) -> Result<Json<LyricsResponse>, (StatusCode, String)> {
+    let song_id = resolve_song_id(&id, &pool).await?;
+    let row = sqlx::query("SELECT plain, synced, source FROM song_lyrics WHERE song = $1")
+        .bind(song_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(internal_error)?
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            "no lyrics for this track".to_string(),
+        ))?;
+
+    let synced = row
+        .try_get::<Option<sqlx::types::Json<Vec<LyricLine>>>, _>("synced")
+        .map_err(internal_error)?
+        .map(|j| j.0)
+        .unwrap_or_default();
+    Ok(Json(LyricsResponse {
+        plain: row.try_get("plain").map_err(internal_error)?,
+        synced,
+        source: row.try_get("source").map_err(internal_error)?,
+    }))
+}
+
 fn z_scored_distance(
     seed: &[f32],
     candidate: &[f32],
@@ -319,6 +368,21 @@ pub async fn liked_ids_for_user(pool: &PgPool, user_id: Option<i32>) -> HashSet<
     .collect()
 }
 
+/// Whether a user asked to have explicit songs left out. Anonymous users see everything.
+pub async fn hides_explicit_for_user(
+    pool: &PgPool,
+    user_id: Option<i32>,
+) -> Result<bool, sqlx::Error> {
+    let Some(uid) = user_id else {
+        return Ok(false);
+    };
+    let hide: Option<bool> = sqlx::query_scalar("SELECT hide_explicit FROM users WHERE id = $1")
+        .bind(uid)
+        .fetch_optional(pool)
+        .await?;
+    Ok(hide.unwrap_or(false))
+}
+
 // ── Handlers ──────────────────────────────────────────────────────────────────
 
 #[utoipa::path(
@@ -343,26 +407,30 @@ pub async fn get_song(
     let user_id = payload.and_then(|p| p.sub.parse::<i32>().ok());
     let art_base = build_default_art_url(host);
 
-    let tracks = match sqlx::query_as!(TrackRaw,
+    let tracks = match sqlx::query_as!(
+        TrackRaw,
         r#"
-        SELECT song.id, song.slug, disc, number, song.name, album, song.album_artist, liked, duration, plays, lossless,
-               sample_rate, bits_per_sample, num_channels, composer, song.isrc, bpm,
+        SELECT song.id, song.slug, disc, number, song.name, album, song.album_artist, liked, duration, song.duration_ms as "duration_ms!", plays, lossless,
+               sample_rate, bits_per_sample, num_channels, song.bitrate, song.total_samples,
+               song.encoder_delay, song.encoder_padding,
+               song.replaygain_track_gain, song.replaygain_track_peak,
+               song.replaygain_album_gain, song.replaygain_album_peak, composer, song.isrc, bpm,
+               song.work, song.movement, song.movement_number, song.movement_total,
+               song.conductor, song.orchestra, song.explicit,
                song.created_at, song.updated_at, last_play, year,
-               album.name as album_name,
-               artist.name as artist_name,
+               album.name as "album_name!",
+               artist.name as "artist_name!",
                (SELECT album_art.path FROM album_art WHERE album_art.album = song.album LIMIT 1) AS art_path
         FROM song
         LEFT JOIN album ON song.album = album.id
         LEFT JOIN artist ON song.album_artist = artist.id
         WHERE song.id = $1
-        GROUP BY song.id, disc, number, song.name, album, song.album_artist, liked, duration, plays, lossless,
-                 sample_rate, bits_per_sample, num_channels, composer, song.isrc, bpm,
-                 song.created_at, song.updated_at, last_play, year,
-                 album.name, artist.name
-        "#, id_parsed
+        "#,
+        id_parsed
     )
     .fetch_all(&pool)
-    .await {
+    .await
+    {
         Ok(e) => e,
         Err(e) => return Err(internal_error(e)),
     };
@@ -427,14 +495,30 @@ pub async fn get_song(
                 album_artist: track.album_artist,
                 liked: user_id.map(|_| liked_ids.contains(&track_id)),
                 duration: track.duration,
+                duration_ms: track.duration_ms,
                 plays: track.plays,
                 lossless: track.lossless,
                 sample_rate: track.sample_rate,
                 bits_per_sample: track.bits_per_sample,
                 num_channels: track.num_channels,
+                bitrate: track.bitrate,
+                total_samples: track.total_samples,
+                encoder_delay: track.encoder_delay,
+                encoder_padding: track.encoder_padding,
+                replaygain_track_gain: track.replaygain_track_gain,
+                replaygain_track_peak: track.replaygain_track_peak,
+                replaygain_album_gain: track.replaygain_album_gain,
+                replaygain_album_peak: track.replaygain_album_peak,
                 composer: track.composer,
                 isrc: track.isrc,
                 bpm: track.bpm,
+                work: track.work,
+                movement: track.movement,
+                movement_number: track.movement_number,
+                movement_total: track.movement_total,
+                conductor: track.conductor,
+                orchestra: track.orchestra,
+                explicit: track.explicit,
                 created_at: track.created_at,
                 updated_at: track.updated_at,
                 last_play: track.last_play,
@@ -788,7 +872,7 @@ pub async fn get_tracks(
 
     let rows = sqlx::query!(
         r#"
-        SELECT song.id, song.slug, song.name, song.duration, song.number, song.disc,
+        SELECT song.id, song.slug, song.name, song.duration, song.duration_ms as "duration_ms!", song.number, song.disc,
                song.lossless, song.sample_rate, song.bits_per_sample, song.num_channels,
                song.album as album_id, album.name as album_name,
                song.album_artist as artist_id, artist.name as artist_name,
@@ -820,6 +904,7 @@ pub async fn get_tracks(
                 slug: r.slug,
                 name: r.name,
                 duration: r.duration,
+                duration_ms: r.duration_ms,
                 number: r.number,
                 disc: r.disc,
                 lossless: r.lossless,
@@ -0,0 +1,332 @@
+use axum::{
+    extract::{Host, Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use utoipa::ToSchema;
+
+use super::{
+    build_default_art_url, middleware::jwt::OptionalAuthUser, resolve_composer_id, resolve_work_id,
+    song::hides_explicit_for_user,
+};
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct ComposerPartial {
+    id: i32,
+    slug: String,
+    name: String,
+    num_works: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AllComposers {
+    composers: Vec<ComposerPartial>,
+    limit: i64,
+    offset: i64,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct WorkPartial {
+    id: i32,
+    slug: String,
+    name: String,
+    /// Albums with at least one movement of the work
+    num_recordings: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Composer {
+    id: i32,
+    slug: String,
+    name: String,
+    works: Vec<WorkPartial>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Work {
+    id: i32,
+    slug: String,
+    name: String,
+    composer: Option<ComposerPartial>,
+    recordings: Vec<Recording>,
+}
+
+/// One performance of a work: its movements as they appear on one album.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Recording {
+    album: i32,
+    album_slug: String,
+    album_name: String,
+    artist_name: String,
+    year: Option<i32>,
+    art_url: Option<String>,
+    conductor: Option<String>,
+    orchestra: Option<String>,
+    /// Combined length of the movements on this album
+    duration_ms: i64,
+    movements: Vec<Movement>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Movement {
+    id: i32,
+    slug: String,
+    name: String,
+    movement: Option<String>,
+    movement_number: Option<i32>,
+    disc: Option<i32>,
+    number: Option<i32>,
+    duration_ms: i32,
+}
+
+#[derive(FromRow)]
+struct RecordingRow {
+    id: i32,
+    slug: String,
+    name: String,
+    movement: Option<String>,
+    movement_number: Option<i32>,
+    disc: Option<i32>,
+    number: Option<i32>,
+    duration_ms: i32,
+    conductor: Option<String>,
+    orchestra: Option<String>,
+    album_id: i32,
+    album_slug: String,
+    album_name: String,
+    year: Option<i32>,
+    artist_name: String,
+    art_path: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct GetComposersParams {
+    #[serde(default)]
+    limit: Option<i64>,
+    #[serde(default)]
+    offset: Option<i64>,
+    #[serde(default)]
+    filter: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/composer",
+    tag = "works",
+    params(
+        ("limit" = Option<i64>, Query, description = "Max results (default 50)"),
+        ("offset" = Option<i64>, Query, description = "Results to skip"),
+        ("filter" = Option<String>, Query, description = "Filter by composer name"),
+    ),
+    responses(
+        (status = 200, description = "Composers with works, by name", body = AllComposers),
+    ),
+    security(("bearer_token" = []))
+)]
+pub async fn get_composers(
+    Extension(pool): Extension<PgPool>,
+    Query(GetComposersParams {
+        limit,
+        offset,
+        filter,
+    }): Query<GetComposersParams>,
+) -> Result<Json<AllComposers>, (StatusCode, String)> {
+    let limit = limit.unwrap_or(50).clamp(1, 500);
+    let offset = offset.unwrap_or(0).max(0);
+
+    let composers = sqlx::query_as::<_, ComposerPartial>(
+        r#"
+        SELECT composer.id, composer.slug, composer.name, COUNT(work.id) AS num_works
+        FROM composer
+        JOIN work ON work.composer = composer.id
+        WHERE $1::varchar IS NULL OR composer.name ILIKE $1
+        GROUP BY composer.id
+        ORDER BY lower(composer.name), composer.id
+        LIMIT $2 OFFSET $3
+        "#,
+    )
+    .bind(filter.map(|f| format!("%{}%", f)))
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(AllComposers {
+        composers,
+        limit,
+        offset,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/composer/{id}",
+    tag = "works",
+    params(("id" = String, Path, description = "Composer ID or slug")),
+    responses(
+        (status = 200, description = "Composer with their works", body = Composer),
+        (status = 404, description = "Composer not found"),
+    ),
+    security(("bearer_token" = []))
+)]
+pub async fn get_composer(
+    Path(id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    OptionalAuthUser { payload }: OptionalAuthUser,
+) -> Result<Json<Composer>, (StatusCode, String)> {
+    let id = resolve_composer_id(&id, &pool).await?;
+    let user_id = payload.and_then(|p| p.sub.parse::<i32>().ok());
+    let hide_explicit = hides_explicit_for_user(&pool, user_id)
+        .await
+        .map_err(internal_error)?;
+    let (slug, name): (String, String) =
+        sqlx::query_as("SELECT slug, name FROM composer WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&pool)
+            .await
+            .map_err(internal_error)?
+            .ok_or_else(|| (StatusCode::NOT_FOUND, format!("composer not found: {}", id)))?;
+
+    let works = sqlx::query_as::<_, WorkPartial>(
+        r#"
+        SELECT work.id, work.slug, work.name, COUNT(DISTINCT song.album) AS num_recordings
+        FROM work
+        LEFT JOIN song ON song.work = work.id AND (NOT $2::bool OR song.explicit IS NOT TRUE)
+        WHERE work.composer = $1
+        GROUP BY work.id
+        ORDER BY lower(work.name), work.id
+        "#,
+    )
+    .bind(id)
+    .bind(hide_explicit)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(Composer {
+        id,
+        slug,
+        name,
+        works,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/work/{id}",
+    tag = "works",
+    params(("id" = String, Path, description = "Work ID or slug")),
+    responses(
+        (status = 200, description = "Work with every recording of it, oldest first", body = Work),
+        (status = 404, description = "Work not found"),
+    ),
+    security(("bearer_token" = []))
+)]
+pub async fn get_work(
+    Path(id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Host(host): Host,
+    OptionalAuthUser { payload }: OptionalAuthUser,
+) -> Result<Json<Work>, (StatusCode, String)> {
+    let id = resolve_work_id(&id, &pool).await?;
+    let user_id = payload.and_then(|p| p.sub.parse::<i32>().ok());
+    let hide_explicit = hides_explicit_for_user(&pool, user_id)
+        .await
+        .map_err(internal_error)?;
+    let (slug, name, composer_id): (String, String, Option<i32>) =
+        sqlx::query_as("SELECT slug, name, composer FROM work WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&pool)
+            .await
+            .map_err(internal_error)?
+            .ok_or_else(|| (StatusCode::NOT_FOUND, format!("work not found: {}", id)))?;
+
+    let composer = match composer_id {
+        Some(composer_id) => sqlx::query_as::<_, ComposerPartial>(
+            r#"
+            SELECT composer.id, composer.slug, composer.name,
+                   (SELECT COUNT(*) FROM work WHERE work.composer = composer.id) AS num_works
+            FROM composer WHERE composer.id = $1
+            "#,
+        )
+        .bind(composer_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(internal_error)?,
+        None => None,
+    };
+
+    let rows = sqlx::query_as::<_, RecordingRow>(
+        r#"
+        SELECT song.id, song.slug, song.name, song.movement, song.movement_number,
+               song.disc, song.number, song.duration_ms, song.conductor, song.orchestra,
+               album.id AS album_id, album.slug AS album_slug, album.name AS album_name, album.year,
+               artist.name AS artist_name,
+               (SELECT album_art.path FROM album_art WHERE album_art.album = album.id LIMIT 1) AS art_path
+        FROM song
+        JOIN album ON song.album = album.id
+        JOIN artist ON album.artist = artist.id
+        WHERE song.work = $1 AND (NOT $2::bool OR song.explicit IS NOT TRUE)
+        ORDER BY album.year NULLS LAST, album.id, song.movement_number NULLS LAST, song.disc, song.number
+        "#,
+    )
+    .bind(id)
+    .bind(hide_explicit)
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?;
+
+    let art_url = build_default_art_url(host);
+    let mut recordings: Vec<Recording> = Vec::new();
+    for row in rows {
+        let movement = Movement {
+            id: row.id,
+            slug: row.slug,
+            name: row.name,
+            movement: row.movement,
+            movement_number: row.movement_number,
+            disc: row.disc,
+            number: row.number,
+            duration_ms: row.duration_ms,
+        };
+        // rows arrive grouped by album
+        match recordings.last_mut() {
+            Some(recording) if recording.album == row.album_id => {
+                recording.duration_ms += movement.duration_ms as i64;
+                recording.conductor = recording.conductor.take().or(row.conductor);
+                recording.orchestra = recording.orchestra.take().or(row.orchestra);
+                recording.movements.push(movement);
+            }
+            _ => recordings.push(Recording {
+                album: row.album_id,
+                album_slug: row.album_slug,
+                album_name: row.album_name,
+                artist_name: row.artist_name,
+                year: row.year,
+                art_url: row.art_path.map(|p| format!("{}{}", art_url, p)),
+                conductor: row.conductor,
+                orchestra: row.orchestra,
+                duration_ms: movement.duration_ms as i64,
+                movements: vec![movement],
+            }),
+        }
+    }
+
+    Ok(Json(Work {
+        id,
+        slug,
+        name,
+        composer,
+        recordings,
+    }))
+}
+
+fn internal_error<E: std::fmt::Debug>(err: E) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Internal error: {:?}", err),
+    )
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::{fs, path::Path};
+use std::{collections::HashMap, fs, path::Path};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct HlsProfile {
@@ -44,6 +44,27 @@ fn default_mix_analysis_max_pcm_bytes() -> u64 {
     256 * 1024 * 1024
 }
 
+fn default_replaygain_compute() -> bool {
+    true
+}
+
+fn default_various_artists_name() -> String {
+    "Various Artists".to_string()
+}
+
+fn default_scan_workers() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+fn default_cover_art_filenames() -> Vec<String> {
+    ["cover", "folder", "front", "album", "albumart"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
     pub artist_split_exceptions: Vec<String>,
@@ -70,6 +91,30 @@ pub struct Config {
     pub mix_analysis_timeout_seconds: u64,
     #[serde(default = "default_mix_analysis_max_pcm_bytes")]
     pub mix_analysis_max_pcm_bytes: u64,
+    /// Measure EBU R128 loudness with ffmpeg for songs without ReplayGain tags, in the
+    /// background after a scan.
+    #[serde(default = "default_replaygain_compute")]
+    pub replaygain_compute: bool,
+    /// File stems checked, in order, for cover art next to songs (e.g. "cover" matches cover.jpg).
+    #[serde(default = "default_cover_art_filenames")]
+    pub cover_art_filenames: Vec<String>,
+    /// Artist that compilation albums are grouped under.
+    #[serde(default = "default_various_artists_name")]
+    pub various_artists_name: String,
+    /// Genre spellings mapped to the name they are stored under, e.g. "rap": "Hip-Hop".
+    /// Case, spaces and punctuation are ignored when matching.
+    #[serde(default)]
+    pub genre_aliases: HashMap<String, String>,
+    /// Sub-genres mapped to their parent genre, e.g. "Shoegaze": "Rock".
+    #[serde(default)]
+    pub genre_parents: HashMap<String, String>,
+    /// Files read and indexed at once during a scan. Defaults to the number of CPUs.
+    #[serde(default = "default_scan_workers")]
+    pub scan_workers: usize,
+    /// Also compare a hash of the start and end of files whose size and modification time
+    /// are unchanged before skipping them on a rescan, for tools that preserve mtimes.
+    #[serde(default)]
+    pub scan_partial_hash: bool,
 }
 
 fn create_default_config(path: &str) -> Config {
@@ -86,6 +131,13 @@ fn create_default_config(path: &str) -> Config {
         mix_analysis_url: None,
         mix_analysis_timeout_seconds: default_mix_analysis_timeout_seconds(),
         mix_analysis_max_pcm_bytes: default_mix_analysis_max_pcm_bytes(),
+        replaygain_compute: default_replaygain_compute(),
+        cover_art_filenames: default_cover_art_filenames(),
+        various_artists_name: default_various_artists_name(),
+        genre_aliases: HashMap::new(),
+        genre_parents: HashMap::new(),
+        scan_workers: default_scan_workers(),
+        scan_partial_hash: false,
     };
 
     let config_json =
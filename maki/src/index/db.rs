@@ -1,10 +1,13 @@
 use std::{
+    collections::HashMap,
     convert::TryInto,
-    io::{Cursor, Read},
+    io::{Cursor, Read, Seek, SeekFrom},
+    sync::{Arc, OnceLock},
     time::UNIX_EPOCH,
 };
 
 use base64::Engine;
+use dashmap::DashMap;
 use md5::{
     digest::{ExtendableOutput, Update},
     Digest, Md5,
@@ -14,7 +17,15 @@ use sqlx::{postgres::Postgres, Row};
 use time::OffsetDateTime;
 use tracing::{debug, error, info, warn};
 
-use crate::metadata::{deezer, fm, spotify, theaudiodb, AudioMetadata};
+use crate::{
+    config::Config,
+    helpers::sort_string,
+    metadata::{
+        art, cue, deezer, fm, genre,
+        lyrics::{self, LyricLine, Lyrics},
+        musicbrainz, replaygain, spotify, theaudiodb, AudioMetadata,
+    },
+};
 
 /// Stable public slug — hex-encoded MD5 of the given key string.
 /// Matches the SQL backfill in the migration: `md5(key)`.
@@ -30,14 +41,62 @@ fn sanitize_str(s: &str) -> String {
     s.replace('\0', "")
 }
 
-pub async fn add_song(mut metadata: AudioMetadata, pool: sqlx::Pool<Postgres>) {
+/// Stored with every song a scan reads. Bump it when scanning starts storing something
+/// new, so the next scan reads files that are otherwise unchanged again.
+const SCANNER_VERSION: i32 = 1;
+
+/// One lock per album being indexed, so files of the same album scanned in parallel don't
+/// race to create it and its artists.
+static ALBUM_LOCKS: OnceLock<DashMap<String, Arc<tokio::sync::Mutex<()>>>> = OnceLock::new();
+
+/// Which lock a file takes. Keyed by what every file of an album shares whether or not it
+/// is tagged with a release MBID: the album name and its artist.
+fn album_lock_key(metadata: &AudioMetadata, cfg: &Config) -> String {
+    let album_artist = if metadata.compilation {
+        &cfg.various_artists_name
+    } else {
+        &metadata.album_artist
+    };
+    format!(
+        "{}\0{}",
+        metadata.album.to_lowercase(),
+        album_artist.to_lowercase()
+    )
+}
+
+/// Held shared while a song is indexed and exclusively by [`cleanup_orphans`], so cleanup
+/// never deletes an album or artist a song is about to be inserted under.
+static INDEXING: tokio::sync::RwLock<()> = tokio::sync::RwLock::const_new(());
+
+pub async fn add_song(metadata: AudioMetadata, pool: sqlx::Pool<Postgres>, cfg: &Config) {
+    let key = album_lock_key(&metadata, cfg);
+    let locks = ALBUM_LOCKS.get_or_init(DashMap::new);
+    let lock = locks
+        .entry(key.clone())
+        .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(())))
+        .clone();
+    {
+        let _guard = lock.lock().await;
+        let _indexing = INDEXING.read().await;
+        index_song(metadata, pool, cfg).await;
+    }
+    drop(lock);
+    locks.remove_if(&key, |_, lock| Arc::strong_count(lock) == 1);
+}
+
+async fn index_song(mut metadata: AudioMetadata, pool: sqlx::Pool<Postgres>, cfg: &Config) {
     // Sanitize all text fields — null bytes (0x00) are invalid in PostgreSQL text
     // columns and typically come from corrupted or poorly-encoded metadata tags.
     metadata.name = sanitize_str(&metadata.name);
     metadata.album = sanitize_str(&metadata.album);
     metadata.album_artist = sanitize_str(&metadata.album_artist);
-    if let Some(s) = &mut metadata.album_sort {
-        *s = sanitize_str(s);
+    for s in [
+        &mut metadata.album_sort,
+        &mut metadata.artist_sort,
+        &mut metadata.album_artist_sort,
+        &mut metadata.artist_tag,
+    ] {
+        *s = s.as_deref().map(sanitize_str);
     }
     metadata.artists = metadata.artists.iter().map(|s| sanitize_str(s)).collect();
     if let Some(g) = &mut metadata.genre {
@@ -47,8 +106,30 @@ pub async fn add_song(mut metadata: AudioMetadata, pool: sqlx::Pool<Postgres>) {
     metadata.isrc = metadata.isrc.as_deref().map(sanitize_str);
     metadata.copyright = metadata.copyright.as_deref().map(sanitize_str);
     metadata.label = metadata.label.as_deref().map(sanitize_str);
+    let classical = &mut metadata.classical;
+    for s in [
+        &mut classical.work,
+        &mut classical.movement,
+        &mut classical.conductor,
+        &mut classical.orchestra,
+    ] {
+        *s = s.as_deref().map(sanitize_str);
+    }
+    for credit in &mut metadata.credits {
+        credit.name = sanitize_str(&credit.name);
+        credit.detail = credit.detail.as_deref().map(sanitize_str);
+    }
+
+    // albums credited to various artists are compilations even without the tag
+    metadata.compilation |= (!cfg.various_artists_name.is_empty()
+        && metadata
+            .album_artist
+            .eq_ignore_ascii_case(&cfg.various_artists_name))
+        || metadata.mbid_artist.as_deref() == Some(musicbrainz::VARIOUS_ARTISTS_MBID);
+
+    let credit_mbids = resolve_artists(&mut metadata, &pool).await;
 
-    let artist = match artist_foc(metadata.clone(), pool.clone()).await {
+    let artist = match artist_foc(metadata.clone(), &credit_mbids, pool.clone()).await {
         Ok(ids) if !ids.is_empty() => ids,
         Ok(_) => {
             error!("no artists resolved for {}", metadata.name);
@@ -66,7 +147,7 @@ pub async fn add_song(mut metadata: AudioMetadata, pool: sqlx::Pool<Postgres>) {
     // check for genre data, and if so, find/create.
     // we need genres for albums and songs!
     let genres = if let Some(genre) = &metadata.genre {
-        match genre_foc(genre, pool.clone()).await {
+        match genre_foc(genre, &cfg.genre_aliases, pool.clone()).await {
             Ok(ids) => Some(ids),
             Err(e) => {
                 error!("failed to find or create genres: {e}");
@@ -77,11 +158,32 @@ pub async fn add_song(mut metadata: AudioMetadata, pool: sqlx::Pool<Postgres>) {
         None
     };
 
+    // compilation tracks keep their own artists, but the album belongs to various artists
+    // so it isn't split into one album per track artist
+    let album_artist = if metadata.compilation {
+        match various_artists_foc(cfg, &pool).await {
+            Ok(id) => {
+                metadata.album_artist = cfg.various_artists_name.clone();
+                id
+            }
+            Err(e) => {
+                error!(
+                    "failed to find or create {} for {}: {}",
+                    cfg.various_artists_name, metadata.album, e
+                );
+                return;
+            }
+        }
+    } else {
+        artist[0]
+    };
+
     let album = match album_foc(
         metadata.clone(),
-        artist.clone(),
+        album_artist,
         pool.clone(),
         genres.clone(),
+        cfg,
     )
     .await
     {
@@ -95,28 +197,560 @@ pub async fn add_song(mut metadata: AudioMetadata, pool: sqlx::Pool<Postgres>) {
         }
     };
 
+    if metadata.compilation {
+        if let Err(e) = mark_compilation(album, album_artist, &pool).await {
+            error!("failed to mark {} as a compilation: {}", metadata.album, e);
+        }
+    }
+
     // finally, add our track
-    if let Err(e) = song_foc(metadata.clone(), artist.clone(), album, genres, pool).await {
-        error!(
-            "failed to add song {} at path {}: {}",
-            metadata.name,
-            metadata.path.display(),
-            e
-        );
+    let song_id = match song_foc(
+        metadata.clone(),
+        artist.clone(),
+        album,
+        genres,
+        pool.clone(),
+    )
+    .await
+    {
+        Ok(id) => id,
+        Err(e) => {
+            error!(
+                "failed to add song {} at path {}: {}",
+                metadata.name,
+                metadata.path.display(),
+                e
+            );
+            return;
+        }
+    };
+
+    if let Err(e) = work_foc(song_id, &metadata, &pool).await {
+        error!("failed to store work for {}: {}", metadata.name, e);
+    }
+
+    if let Err(e) = sqlx::query(
+        "UPDATE song SET artist_tag = $2 WHERE id = $1 AND artist_tag IS DISTINCT FROM $2",
+    )
+    .bind(song_id)
+    .bind(&metadata.artist_tag)
+    .execute(&pool)
+    .await
+    {
+        error!("failed to store artist tag for {}: {}", metadata.name, e);
+    }
+
+    if cfg.scan_partial_hash {
+        let hash = partial_file_hash(&metadata.path);
+        if let Err(e) = sqlx::query("UPDATE song SET partial_hash = $2 WHERE id = $1")
+            .bind(song_id)
+            .bind(hash.as_ref().ok())
+            .execute(&pool)
+            .await
+        {
+            error!("failed to store partial hash for {}: {}", metadata.name, e);
+        }
+    }
+
+    if let Err(e) = credits_foc(song_id, &metadata, &pool).await {
+        error!("failed to store credits for {}: {}", metadata.name, e);
+    }
+
+    if let Err(e) = disc_foc(song_id, album, &metadata, cfg, &pool).await {
+        error!("failed to store disc info for {}: {}", metadata.name, e);
+    }
+
+    if let Err(e) = lyrics_foc(song_id, metadata.lyrics.as_ref(), &pool).await {
+        error!("failed to store lyrics for {}: {}", metadata.name, e);
+    }
+
+    // songs without ReplayGain tags are measured by the enrichment pass
+    if let Err(e) = derive_album_gain(album, &pool).await {
+        warn!("failed to derive album gain for {}: {}", metadata.name, e);
+    }
+}
+
+/// Measure loudness for a song without ReplayGain tags, then refresh its album's derived
+/// gain. A file ffmpeg can't measure is marked so it isn't decoded again until it changes.
+pub async fn measure_replaygain(id: i32, pool: &sqlx::Pool<Postgres>) -> anyhow::Result<()> {
+    let (path, album, cue_track, start_ms, end_ms): (
+        String,
+        i32,
+        Option<i32>,
+        Option<i32>,
+        Option<i32>,
+    ) = sqlx::query_as("SELECT path, album, cue_track, start_ms, end_ms FROM song WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+    let span = cue_track.zip(start_ms).map(|(track, start)| cue::CueSpan {
+        track: track as u32,
+        start_ms: start as u32,
+        end_ms: end_ms.map(|e| e as u32),
+    });
+
+    let measured = match replaygain::measure(std::path::Path::new(&path), span.as_ref()).await {
+        Ok(measured) => measured,
+        Err(e) => {
+            // ffmpeg failing to start isn't the file's fault, try again next pass
+            if e.downcast_ref::<std::io::Error>().is_none() {
+                sqlx::query("UPDATE song SET replaygain_failed = true WHERE id = $1")
+                    .bind(id)
+                    .execute(pool)
+                    .await?;
+            }
+            return Err(e);
+        }
+    };
+    debug!("measured replaygain for {}: {:?}", path, measured);
+    sqlx::query(
+        "UPDATE song SET replaygain_track_gain = $2, replaygain_track_peak = $3, replaygain_computed = true WHERE id = $1",
+    )
+    .bind(id)
+    .bind(measured.track_gain)
+    .bind(measured.track_peak)
+    .execute(pool)
+    .await?;
+
+    derive_album_gain(album, pool).await
+}
+
+/// Derive album gain from the album's track gains, unless its songs carry album gain tags.
+async fn derive_album_gain(album: i32, pool: &sqlx::Pool<Postgres>) -> anyhow::Result<()> {
+    let rows = sqlx::query(
+        r#"
+        SELECT replaygain_track_gain, replaygain_track_peak, replaygain_album_gain,
+               replaygain_album_derived, duration_ms
+        FROM song WHERE album = $1
+        "#,
+    )
+    .bind(album)
+    .fetch_all(pool)
+    .await?;
+
+    let mut tracks: Vec<(f32, Option<f32>, u32)> = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let album_gain: Option<f32> = row.try_get("replaygain_album_gain")?;
+        let derived: bool = row.try_get("replaygain_album_derived")?;
+        if album_gain.is_some() && !derived {
+            // tagged album gain was measured over the whole album, trust it
+            return Ok(());
+        }
+        if let Some(gain) = row.try_get::<Option<f32>, _>("replaygain_track_gain")? {
+            let duration_ms: i32 = row.try_get("duration_ms")?;
+            let peak: Option<f32> = row.try_get("replaygain_track_peak")?;
+            tracks.push((gain, peak, duration_ms.max(0) as u32));
+        }
+    }
+
+    let Some((gain, peak)) = replaygain::album_from_tracks(&tracks) else {
+        return Ok(());
+    };
+    sqlx::query(
+        r#"
+        UPDATE song SET replaygain_album_gain = $2, replaygain_album_peak = $3, replaygain_album_derived = true
+        WHERE album = $1 AND (replaygain_album_gain IS NULL OR replaygain_album_derived)
+        "#,
+    )
+    .bind(album)
+    .bind(gain)
+    .bind(peak)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Link a song to its classical work, creating the work and its composer, and store
+/// the movement and performer tags.
+async fn work_foc(
+    song_id: i32,
+    metadata: &AudioMetadata,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let classical = &metadata.classical;
+    let work = match classical.work.as_deref() {
+        Some(name) => {
+            let (composer, composer_slug) = match metadata.composer.as_deref().map(str::trim) {
+                Some(composer) if !composer.is_empty() => {
+                    let (id, slug) = composer_foc(composer, pool).await?;
+                    (Some(id), slug)
+                }
+                _ => (None, String::new()),
+            };
+            // the same title by different composers is a different work
+            let slug_key = format!("{}|{}", composer_slug, name.to_lowercase());
+            let id: i32 = sqlx::query_scalar(
+                r#"
+                INSERT INTO work (slug, name, composer, created_at)
+                VALUES ($1, $2, $3, now())
+                ON CONFLICT (slug) DO UPDATE SET name = work.name
+                RETURNING id
+                "#,
+            )
+            .bind(make_slug(&slug_key))
+            .bind(name)
+            .bind(composer)
+            .fetch_one(pool)
+            .await?;
+            Some(id)
+        }
+        None => None,
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE song SET work = $2, movement = $3, movement_number = $4, movement_total = $5,
+          conductor = $6, orchestra = $7
+        WHERE id = $1
+        "#,
+    )
+    .bind(song_id)
+    .bind(work)
+    .bind(classical.movement.as_deref())
+    .bind(classical.movement_number.map(|n| n as i32))
+    .bind(classical.movement_total.map(|n| n as i32))
+    .bind(classical.conductor.as_deref())
+    .bind(classical.orchestra.as_deref())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Find or create a composer, returning its id and slug.
+async fn composer_foc(name: &str, pool: &sqlx::Pool<Postgres>) -> anyhow::Result<(i32, String)> {
+    let slug = make_slug(&name.to_lowercase());
+    let id: i32 = sqlx::query_scalar(
+        r#"
+        INSERT INTO composer (slug, name, created_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT (slug) DO UPDATE SET name = composer.name
+        RETURNING id
+        "#,
+    )
+    .bind(&slug)
+    .bind(name)
+    .fetch_one(pool)
+    .await?;
+    Ok((id, slug))
+}
+
+/// Replace a song's credits, linking each credited name to an artist.
+async fn credits_foc(
+    song_id: i32,
+    metadata: &AudioMetadata,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM song_credit WHERE song = $1")
+        .bind(song_id)
+        .execute(pool)
+        .await?;
+
+    for credit in &metadata.credits {
+        let person = person_foc(&credit.name, metadata, pool).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO song_credit (song, artist, role, detail, created_at)
+            VALUES ($1, $2, $3, $4, now())
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(song_id)
+        .bind(person)
+        .bind(&credit.role)
+        .bind(credit.detail.as_deref().unwrap_or_default())
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Store where a song sits in a multi-disc release: its disc's subtitle, the track and
+/// disc totals, and art for its disc.
+async fn disc_foc(
+    song_id: i32,
+    album: i32,
+    metadata: &AudioMetadata,
+    cfg: &Config,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let subtitle = metadata
+        .disc_subtitle
+        .as_deref()
+        .map(|s| sanitize_str(s.trim()))
+        .filter(|s| !s.is_empty());
+    sqlx::query(
+        "UPDATE song SET disc_subtitle = $2, track_total = $3, disc_total = $4 WHERE id = $1",
+    )
+    .bind(song_id)
+    .bind(subtitle)
+    .bind(metadata.track_total.map(|t| t as i32))
+    .bind(metadata.disc_total.map(|t| t as i32))
+    .execute(pool)
+    .await?;
+
+    let disc = metadata.disc.unwrap_or(1) as i32;
+    sync_disc_art(album, disc, &metadata.path, &cfg.cover_art_filenames, pool).await
+}
+
+/// Bring a disc's art in line with the sidecar image inside its disc folder, removing it
+/// when the image is gone.
+async fn sync_disc_art(
+    album: i32,
+    disc: i32,
+    song: &std::path::Path,
+    names: &[String],
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some(path) = art::find_disc_sidecar(song, names) else {
+        sqlx::query("DELETE FROM disc_art WHERE album = $1 AND disc = $2")
+            .bind(album)
+            .bind(disc)
+            .execute(pool)
+            .await?;
+        return Ok(());
     };
+
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("path is not valid UTF-8: {}", path.display()))?;
+    let signature = source_file_signature(&path)?;
+    let stored: Option<(String, i64, i64)> = sqlx::query_as(
+        "SELECT source_path, source_size, source_mtime_ns FROM disc_art WHERE album = $1 AND disc = $2",
+    )
+    .bind(album)
+    .bind(disc)
+    .fetch_optional(pool)
+    .await?;
+    if stored == Some((path_str.to_string(), signature.0 as i64, signature.1)) {
+        return Ok(());
+    }
+
+    let (hash, signature) = save_sidecar_image(&path).await?;
+    sqlx::query(
+        r#"
+        INSERT INTO disc_art (album, disc, path, source_path, source_size, source_mtime_ns, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, now())
+        ON CONFLICT (album, disc) DO UPDATE SET
+          path = EXCLUDED.path, source_path = EXCLUDED.source_path,
+          source_size = EXCLUDED.source_size, source_mtime_ns = EXCLUDED.source_mtime_ns
+        "#,
+    )
+    .bind(album)
+    .bind(disc)
+    .bind(hash)
+    .bind(path_str)
+    .bind(signature.0 as i64)
+    .bind(signature.1)
+    .execute(pool)
+    .await?;
+    info!(
+        "updated album {} disc {} art from {}",
+        album,
+        disc,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Find the artist a credited person already is, by MBID when they are the album artist
+/// and otherwise by name. Anyone else gets a bare artist row, without the network lookups
+/// `artist_foc` makes, since most credited people never appear as a performing artist.
+async fn person_foc(
+    name: &str,
+    metadata: &AudioMetadata,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<i32> {
+    if let Some(mbid) = metadata
+        .mbid_artist
+        .as_deref()
+        .filter(|_| name.eq_ignore_ascii_case(&metadata.album_artist))
+    {
+        let found: Option<i32> = sqlx::query_scalar("SELECT id FROM artist WHERE mbid = $1")
+            .bind(mbid)
+            .fetch_optional(pool)
+            .await?;
+        if let Some(id) = found {
+            return Ok(id);
+        }
+    }
+
+    // slugs are lowercased names, so an existing artist of the same name wins the conflict
+    let id = sqlx::query_scalar(
+        r#"
+        INSERT INTO artist (name, bio, tags, slug, created_at)
+        VALUES ($1, '', '', $2, now())
+        ON CONFLICT (slug) DO UPDATE SET name = artist.name
+        RETURNING id
+        "#,
+    )
+    .bind(name)
+    .bind(make_slug(&name.to_lowercase()))
+    .fetch_one(pool)
+    .await?;
+    Ok(id)
 }
 
-/// find or create artist
+/// Replace the stored lyrics for a song, removing them when the file no longer has any.
+async fn lyrics_foc(
+    song_id: i32,
+    lyrics: Option<&Lyrics>,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some(lyrics) = lyrics else {
+        sqlx::query("DELETE FROM song_lyrics WHERE song = $1")
+            .bind(song_id)
+            .execute(pool)
+            .await?;
+        return Ok(());
+    };
+
+    // jsonb rejects \u0000 just like text columns do
+    let synced = if lyrics.synced.is_empty() {
+        None
+    } else {
+        let lines: Vec<LyricLine> = lyrics
+            .synced
+            .iter()
+            .map(|l| LyricLine {
+                time_ms: l.time_ms,
+                text: sanitize_str(&l.text),
+            })
+            .collect();
+        Some(serde_json::to_value(lines)?)
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO song_lyrics (song, plain, synced, source, created_at)
+        VALUES ($1, $2, $3, $4, now())
+        ON CONFLICT (song) DO UPDATE SET
+          plain = EXCLUDED.plain, synced = EXCLUDED.synced, source = EXCLUDED.source,
+          updated_at = now()
+        "#,
+    )
+    .bind(song_id)
+    .bind(lyrics.text.as_deref().map(sanitize_str))
+    .bind(synced)
+    .bind(lyrics.source)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Settle how the track artist splits into artists. A recording's cached MusicBrainz artist
+/// credit is authoritative; without one, an admin's split exception keeps the tag whole.
+/// Uncached credits are fetched by enrich_song, which rescans the file if they differ.
+/// Returns the MBIDs of credited artists by name.
+async fn resolve_artists(
+    metadata: &mut AudioMetadata,
+    pool: &sqlx::Pool<Postgres>,
+) -> HashMap<String, String> {
+    if let Some(recording) = metadata.mbid_track.clone() {
+        match cached_recording_artists(&recording, pool).await {
+            Ok(Some(credited)) if !credited.is_empty() => {
+                let artists: Vec<String> = credited.iter().map(|(name, _)| name.clone()).collect();
+                if artists != metadata.artists {
+                    debug!(
+                        "artist credit for {} splits {:?} into {:?}",
+                        metadata.name, metadata.artist_tag, artists
+                    );
+                }
+                metadata.artists = artists;
+                return credited.into_iter().collect();
+            }
+            Ok(_) => {}
+            Err(e) => warn!("failed to read artist credit for {}: {}", recording, e),
+        }
+    }
+
+    if metadata.artists.len() < 2 {
+        return HashMap::new();
+    }
+    if let Some(tag) = &metadata.artist_tag {
+        match sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM artist_split_exception WHERE name = $1)",
+        )
+        .bind(tag)
+        .fetch_one(pool)
+        .await
+        {
+            Ok(true) => metadata.artists = vec![tag.clone()],
+            Ok(false) => {}
+            Err(e) => warn!("failed to check split exceptions for {}: {}", tag, e),
+        }
+    }
+    HashMap::new()
+}
+
+/// Names and MBIDs of the artists credited on a recording MBID, if they have been fetched.
+async fn cached_recording_artists(
+    recording: &str,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<Option<Vec<(String, String)>>> {
+    let cached: Option<(Vec<String>, Vec<String>)> =
+        sqlx::query_as("SELECT artists, mbids FROM mb_artist_credit WHERE recording = $1")
+            .bind(recording)
+            .fetch_optional(pool)
+            .await?;
+    Ok(cached.map(|(artists, mbids)| artists.into_iter().zip(mbids).collect()))
+}
+
+/// Fetch and cache the names and MBIDs of the artists credited on a recording MBID. Artists
+/// are named as they are in MusicBrainz, not as credited, so an alias credit doesn't make
+/// a second artist. Empty when MusicBrainz doesn't know the recording.
+async fn fetch_recording_artists(
+    recording: &str,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let mut credited: Vec<(String, String)> = Vec::new();
+    for credit in musicbrainz::get_recording_artist_credit(recording)
+        .await?
+        .unwrap_or_default()
+    {
+        let name = sanitize_str(credit.artist.name.trim());
+        if !name.is_empty() && !credited.iter().any(|(_, mbid)| *mbid == credit.artist.id) {
+            credited.push((name, credit.artist.id));
+        }
+    }
+    let (artists, mbids): (Vec<String>, Vec<String>) = credited.iter().cloned().unzip();
+    sqlx::query(
+        r#"
+        INSERT INTO mb_artist_credit (recording, artists, mbids) VALUES ($1, $2, $3)
+        ON CONFLICT (recording) DO UPDATE
+        SET artists = EXCLUDED.artists, mbids = EXCLUDED.mbids, fetched_at = now()
+        "#,
+    )
+    .bind(recording)
+    .bind(&artists)
+    .bind(&mbids)
+    .execute(pool)
+    .await?;
+    Ok(credited)
+}
+
+/// find or create artist. `credit_mbids` are artist MBIDs by name from the recording's
+/// MusicBrainz artist credit.
 async fn artist_foc(
     metadata: AudioMetadata,
+    credit_mbids: &HashMap<String, String>,
     pool: sqlx::Pool<Postgres>,
 ) -> anyhow::Result<Vec<i32>> {
     let mut artist_ids = Vec::new();
+    let single = metadata.artists.len() == 1;
 
     for arti in metadata.artists {
         // If this artist name matches the album artist, we can potentially use the MBID
-        let this_mbid = if arti == metadata.album_artist {
-            metadata.mbid_artist.clone()
+        let this_mbid = match credit_mbids.get(&arti) {
+            Some(mbid) => Some(mbid.clone()),
+            None if arti == metadata.album_artist => metadata.mbid_artist.clone(),
+            None => None,
+        };
+        // ARTISTSORT covers the whole artist credit, so it only fits a lone artist
+        let sort_tag = if arti == metadata.album_artist && metadata.album_artist_sort.is_some() {
+            metadata.album_artist_sort.as_deref()
+        } else if single {
+            metadata.artist_sort.as_deref()
         } else {
             None
         };
@@ -140,158 +774,310 @@ async fn artist_foc(
                 .await
             {
                 artist_id = Some(id);
-                // TODO: Update MBID if we have one but the DB doesn't?
+                if let Some(mbid) = &this_mbid {
+                    sqlx::query("UPDATE artist SET mbid = $2 WHERE id = $1 AND mbid IS NULL")
+                        .bind(id)
+                        .bind(mbid)
+                        .execute(&pool)
+                        .await?;
+                }
             }
         }
 
-        // 3. Fall back to MusicBrainz name search if we still have no MBID
-        let this_mbid = if this_mbid.is_none() && artist_id.is_none() {
-            match crate::metadata::musicbrainz::get_artist_mbid(&arti).await {
-                Ok(mbid) => mbid,
-                Err(e) => {
-                    tracing::warn!("MusicBrainz artist lookup failed for {}: {}", arti, e);
-                    None
-                }
+        // bio, picture, similar artists and a searched MBID are filled in by enrich_artist
+        let id = match artist_id {
+            Some(id) => id,
+            None => {
+                sqlx::query_scalar(
+                    r#"
+                    INSERT INTO artist (name, bio, tags, mbid, slug, created_at)
+                    VALUES ($1, '', '', $2, $3, now())
+                    ON CONFLICT (slug) DO UPDATE SET name = EXCLUDED.name
+                    RETURNING id;
+                    "#,
+                )
+                .bind(&arti)
+                .bind(&this_mbid)
+                .bind(make_slug(&arti.to_lowercase()))
+                .fetch_one(&pool)
+                .await?
             }
-        } else {
-            this_mbid
         };
+        if let Err(e) = sync_artist_names(id, sort_tag, &pool).await {
+            warn!("failed to update sort name for {}: {}", arti, e);
+        }
+        artist_ids.push(id);
+    }
 
-        if let Some(id) = artist_id {
-            // Lazy migration: if this artist still has a hotlinked external picture,
-            // download it and swap the column to a local cache key.
-            if let Ok(Some(picture)) =
-                sqlx::query_scalar!("SELECT picture FROM artist WHERE id = $1", id)
-                    .fetch_one(&pool)
-                    .await
-            {
-                if picture.starts_with("http://") || picture.starts_with("https://") {
-                    match cache_external_image(&picture).await {
-                        Ok(hash) => {
-                            if let Err(e) = sqlx::query!(
-                                "UPDATE artist SET picture = $1 WHERE id = $2",
-                                hash,
-                                id
-                            )
-                            .execute(&pool)
-                            .await
-                            {
-                                warn!("failed to update cached artist picture for {}: {}", arti, e);
-                            }
-                        }
-                        Err(e) => warn!("failed to cache artist picture for {}: {}", arti, e),
+    Ok(artist_ids)
+}
+
+/// Keep an artist's sort name current. A tagged sort name wins over the stored one.
+async fn sync_artist_names(
+    id: i32,
+    sort_tag: Option<&str>,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let (name, sort_name, sort_key): (String, Option<String>, Option<String>) =
+        sqlx::query_as("SELECT name, sort_name, sort_key FROM artist WHERE id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await?;
+
+    let new_sort = sort_tag
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .or_else(|| sort_name.clone());
+    let new_key = sort_string(Some(new_sort.as_deref().unwrap_or(&name)));
+    if new_sort != sort_name || new_key != sort_key {
+        sqlx::query(
+            "UPDATE artist SET sort_name = $2, sort_key = $3, updated_at = now() WHERE id = $1",
+        )
+        .bind(id)
+        .bind(&new_sort)
+        .bind(&new_key)
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Look up what a scan leaves out for an artist: MusicBrainz ID, bio, tags, picture and
+/// similar artists on first sight, then their MusicBrainz aliases.
+pub async fn enrich_artist(id: i32, pool: &sqlx::Pool<Postgres>) -> anyhow::Result<()> {
+    let (name, mbid, picture, enriched): (String, Option<String>, Option<String>, bool) =
+        sqlx::query_as(
+            "SELECT name, mbid, picture, enriched_at IS NOT NULL FROM artist WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+    if enriched {
+        // Lazy migration: if this artist still has a hotlinked external picture,
+        // download it and swap the column to a local cache key.
+        if let Some(picture) = picture {
+            if picture.starts_with("http://") || picture.starts_with("https://") {
+                match cache_external_image(&picture).await {
+                    Ok(hash) => {
+                        sqlx::query("UPDATE artist SET picture = $1 WHERE id = $2")
+                            .bind(hash)
+                            .bind(id)
+                            .execute(pool)
+                            .await?;
                     }
+                    Err(e) => warn!("failed to cache artist picture for {}: {}", name, e),
                 }
             }
-            artist_ids.push(id);
-        } else {
-            debug!("artist {} not found, searching...", arti);
-
-            let fm_info = match fm::get_artist_info(&arti).await {
-                Ok(e) => e,
-                Err(_) => fm::FmArtist {
-                    bio: "What a mysterious artist. No bio found.".to_string(),
-                    tags: Vec::new(),
-                    similar: Vec::new(),
-                },
-            };
+        }
+    } else {
+        debug!("artist {} not enriched, searching...", name);
 
-            let deezer_artist = deezer::get_artist(&arti).await.unwrap_or(None);
-            let deezer_id = deezer_artist.as_ref().map(|a| a.id as i64);
+        // Fall back to MusicBrainz name search if we have no MBID. A failed search leaves
+        // the artist unenriched, so the next pass tries again.
+        let mbid = match mbid {
+            Some(mbid) => Some(mbid),
+            None => musicbrainz::get_artist_mbid(&name).await?,
+        };
 
-            // Image priority: TheAudioDB (MBID) → Deezer → Spotify
-            let artist_image_url = if let Some(mbid) = &this_mbid {
-                theaudiodb::get_artist_image(mbid).await.unwrap_or(None)
-            } else {
-                None
+        let fm_info = match fm::get_artist_info(&name).await {
+            Ok(e) => e,
+            Err(_) => fm::FmArtist {
+                bio: "What a mysterious artist. No bio found.".to_string(),
+                tags: Vec::new(),
+                similar: Vec::new(),
+            },
+        };
+
+        let deezer_artist = deezer::get_artist(&name).await.unwrap_or(None);
+        let deezer_id = deezer_artist.as_ref().map(|a| a.id as i64);
+
+        // Image priority: TheAudioDB (MBID) → Deezer → Spotify
+        let artist_image_url = if let Some(mbid) = &mbid {
+            theaudiodb::get_artist_image(mbid).await.unwrap_or(None)
+        } else {
+            None
+        };
+        let artist_image_url =
+            match artist_image_url.or_else(|| deezer_artist.and_then(|a| a.picture)) {
+                Some(img) => Some(img),
+                None => spotify::get_artist_image(&name).await.unwrap_or(None),
             };
-            let artist_image_url =
-                match artist_image_url.or_else(|| deezer_artist.and_then(|a| a.picture)) {
-                    Some(img) => Some(img),
-                    None => spotify::get_artist_image(&arti).await.unwrap_or(None),
-                };
-
-            let artist_image = if let Some(url) = artist_image_url {
-                match cache_external_image(&url).await {
-                    Ok(hash) => Some(hash),
-                    Err(e) => {
-                        warn!("failed to cache artist image for {}: {}", arti, e);
-                        None
-                    }
+
+        let artist_image = if let Some(url) = artist_image_url {
+            match cache_external_image(&url).await {
+                Ok(hash) => Some(hash),
+                Err(e) => {
+                    warn!("failed to cache artist image for {}: {}", name, e);
+                    None
                 }
-            } else {
-                None
-            };
+            }
+        } else {
+            None
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE artist SET bio = $2, tags = $3, picture = COALESCE($4, picture),
+              mbid = COALESCE(mbid, $5), deezer_id = COALESCE($6, deezer_id),
+              enriched_at = now(), updated_at = now()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(&fm_info.bio)
+        .bind(fm_info.tags.join(","))
+        .bind(artist_image)
+        .bind(&mbid)
+        .bind(deezer_id)
+        .execute(pool)
+        .await?;
 
-            let tags = fm_info.tags.join(",");
-            let artist_slug = make_slug(&arti.to_lowercase());
+        // Store similar artists from all sources
+        let mut similar: Vec<(String, String)> = fm_info
+            .similar
+            .into_iter()
+            .map(|n| (n, "lastfm".to_string()))
+            .collect();
+
+        if let Some(deezer_id_val) = deezer_id {
+            if let Ok(deezer_similar) = deezer::get_related_artists(deezer_id_val as u64).await {
+                similar.extend(
+                    deezer_similar
+                        .into_iter()
+                        .map(|n| (n, "deezer".to_string())),
+                );
+            }
+        }
 
-            let id = sqlx::query!(
+        for (similar_name, source) in similar {
+            if let Err(e) = sqlx::query(
                 r#"
-                INSERT INTO artist (name, bio, picture, tags, mbid, deezer_id, slug, created_at)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, now())
-                ON CONFLICT (slug) DO UPDATE SET name = EXCLUDED.name
-                RETURNING id;
+                INSERT INTO artist_similar (artist, name, source, created_at)
+                VALUES ($1, $2, $3, now())
                 "#,
-                arti,
-                fm_info.bio,
-                artist_image,
-                tags,
-                this_mbid,
-                deezer_id,
-                artist_slug,
             )
-            .fetch_one(&pool)
-            .await?
-            .id;
-
-            // Store similar artists from all sources
-            let mut similar: Vec<(String, String)> = fm_info
-                .similar
-                .into_iter()
-                .map(|n| (n, "lastfm".to_string()))
-                .collect();
-
-            if let Some(deezer_id_val) = deezer_id {
-                if let Ok(deezer_similar) = deezer::get_related_artists(deezer_id_val as u64).await
-                {
-                    similar.extend(
-                        deezer_similar
-                            .into_iter()
-                            .map(|n| (n, "deezer".to_string())),
-                    );
-                }
+            .bind(id)
+            .bind(&similar_name)
+            .bind(source)
+            .execute(pool)
+            .await
+            {
+                warn!("failed to insert similar artist {}: {}", similar_name, e);
             }
+        }
+    }
 
-            for (name, source) in similar {
-                if let Err(e) = sqlx::query!(
-                    r#"
-                    INSERT INTO artist_similar (artist, name, source, created_at)
-                    VALUES ($1, $2, $3, now())
-                    "#,
-                    id,
-                    name,
-                    source,
-                )
-                .execute(&pool)
-                .await
-                {
-                    tracing::warn!("failed to insert similar artist {}: {}", name, e);
-                }
-            }
+    sync_artist_aliases(id, pool).await
+}
+
+/// Fetch an artist's MusicBrainz aliases once, and their sort name when nothing was tagged.
+async fn sync_artist_aliases(id: i32, pool: &sqlx::Pool<Postgres>) -> anyhow::Result<()> {
+    let (name, sort_name, mbid, checked): (String, Option<String>, Option<String>, bool) =
+        sqlx::query_as(
+            "SELECT name, sort_name, mbid, aliases_checked_at IS NOT NULL FROM artist WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+    let mbid = match (checked, mbid) {
+        (false, Some(mbid)) => mbid,
+        _ => return Ok(()),
+    };
 
-            artist_ids.push(id);
+    // an error leaves the artist unchecked so the next pass tries again
+    let names = match musicbrainz::get_artist_names(&mbid).await {
+        Ok(names) => names,
+        Err(e) => {
+            warn!("MusicBrainz alias lookup failed for {}: {}", name, e);
+            return Ok(());
         }
+    };
+    let mut mb_sort = None;
+    if let Some(names) = names {
+        for alias in names.aliases.iter().filter(|a| a.name != name) {
+            sqlx::query(
+                r#"
+                INSERT INTO artist_alias (artist, name, sort_name, locale)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (artist, name) DO NOTHING
+                "#,
+            )
+            .bind(id)
+            .bind(sanitize_str(&alias.name))
+            .bind(alias.sort_name.as_deref().map(sanitize_str))
+            .bind(&alias.locale)
+            .execute(pool)
+            .await?;
+        }
+        mb_sort = names.sort_name.map(|s| sanitize_str(&s));
     }
 
-    Ok(artist_ids)
+    let new_sort = sort_name.or(mb_sort);
+    let new_key = sort_string(Some(new_sort.as_deref().unwrap_or(&name)));
+    sqlx::query(
+        r#"
+        UPDATE artist SET sort_name = $2, sort_key = $3, aliases_checked_at = now(),
+          updated_at = now()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(&new_sort)
+    .bind(&new_key)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The artist compilation albums are grouped under. Created without any network lookups,
+/// there is nothing useful to fetch for it.
+async fn various_artists_foc(cfg: &Config, pool: &sqlx::Pool<Postgres>) -> anyhow::Result<i32> {
+    let name = &cfg.various_artists_name;
+    let id = sqlx::query_scalar(
+        r#"
+        INSERT INTO artist (name, bio, tags, mbid, slug, enriched_at, created_at)
+        VALUES ($1, '', '', $2, $3, now(), now())
+        ON CONFLICT (slug) DO UPDATE SET name = artist.name
+        RETURNING id
+        "#,
+    )
+    .bind(name)
+    .bind(musicbrainz::VARIOUS_ARTISTS_MBID)
+    .bind(make_slug(&name.to_lowercase()))
+    .fetch_one(pool)
+    .await?;
+    Ok(id)
+}
+
+/// Flag an album as a compilation and move it under the various artists entity, for albums
+/// found by MBID that were first created from an untagged track.
+async fn mark_compilation(
+    album: i32,
+    album_artist: i32,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE album SET compilation = true, artist = $2, updated_at = now()
+        WHERE id = $1 AND (NOT compilation OR artist <> $2)
+        "#,
+    )
+    .bind(album)
+    .bind(album_artist)
+    .execute(pool)
+    .await?;
+    Ok(())
 }
+
 /// find or create album
 async fn album_foc(
     metadata: AudioMetadata,
-    artist: Vec<i32>,
+    album_artist: i32,
     pool: sqlx::Pool<Postgres>,
     genres: Option<Vec<i32>>,
+    cfg: &Config,
 ) -> anyhow::Result<i32> {
     let mut album_id: Option<i32> = None;
 
@@ -311,7 +1097,7 @@ async fn album_foc(
         if let Ok(Some(id)) = sqlx::query_scalar!(
             "SELECT id FROM album WHERE name = $1 AND artist = $2",
             metadata.album,
-            artist[0]
+            album_artist
         )
         .fetch_optional(&pool)
         .await
@@ -320,46 +1106,8 @@ async fn album_foc(
         }
     }
 
-    // 3. Resolve MBID + disambiguation from MusicBrainz
-    //    - If tags carry an MBID, do a direct release-group lookup to get disambiguation.
-    //    - If no MBID, search by name+artist to get both.
-    let (album_mbid, album_disambiguation) = if let Some(mbid) = &metadata.mbid_album {
-        let disambiguation = if album_id.is_none() {
-            // Only bother fetching if we're about to insert a new album row
-            match crate::metadata::musicbrainz::get_release_group_info(mbid).await {
-                Ok(Some(rg)) => rg.disambiguation,
-                Ok(None) => None,
-                Err(e) => {
-                    tracing::warn!(
-                        "MusicBrainz release-group lookup failed for {}: {}",
-                        mbid,
-                        e
-                    );
-                    None
-                }
-            }
-        } else {
-            None
-        };
-        (Some(mbid.clone()), disambiguation)
-    } else if album_id.is_none() {
-        match crate::metadata::musicbrainz::get_album_info(&metadata.album, &metadata.album_artist)
-            .await
-        {
-            Ok(Some(rg)) => (Some(rg.id), rg.disambiguation),
-            Ok(None) => (None, None),
-            Err(e) => {
-                tracing::warn!(
-                    "MusicBrainz album lookup failed for {}: {}",
-                    metadata.album,
-                    e
-                );
-                (None, None)
-            }
-        }
-    } else {
-        (None, None)
-    };
+    // release info and network cover art are looked up later by enrich_album
+    let album_mbid = metadata.mbid_album.clone();
 
     if let Some(id) = album_id {
         if let Some(year) = metadata.year {
@@ -371,6 +1119,12 @@ async fn album_foc(
             .execute(&pool)
             .await?;
         }
+        let embedded = !metadata.picture.is_empty();
+        if let Err(e) =
+            sync_sidecar_art(id, &metadata.path, embedded, &cfg.cover_art_filenames, &pool).await
+        {
+            warn!("failed to refresh sidecar art for album {}: {}", metadata.album, e);
+        }
         Ok(id)
     } else {
         // else we insert the allbum
@@ -390,53 +1144,27 @@ async fn album_foc(
         }
         // dedupe images
         images.dedup();
+        let mut art_source = "embedded";
+        let mut sidecar = None;
 
-        // if no embedded art, try Cover Art Archive then Deezer
+        // if no embedded art, try a cover image next to the song
         if images.is_empty() {
-            if let Some(mbid) = &album_mbid {
-                match crate::metadata::musicbrainz::get_cover_art_bytes(mbid).await {
-                    Ok(Some(bytes)) => match save_image(bytes).await {
-                        Ok(hash) => images.push(hash),
-                        Err(e) => {
-                            error!("failed to save CAA art for album {}: {}", metadata.album, e)
-                        }
-                    },
-                    Ok(None) => debug!("no cover art on CAA for album MBID: {}", mbid),
+            if let Some(path) = art::find_sidecar(&metadata.path, &cfg.cover_art_filenames) {
+                match save_sidecar_image(&path).await {
+                    Ok((hash, signature)) => {
+                        images.push(hash);
+                        art_source = "sidecar";
+                        sidecar = Some((path, signature));
+                    }
                     Err(e) => error!(
-                        "failed to fetch CAA art for album {}: {}",
-                        metadata.album, e
+                        "failed to save sidecar art {} for album {}: {}",
+                        path.display(),
+                        metadata.album,
+                        e
                     ),
                 }
             }
         }
-        if images.is_empty() {
-            match deezer::get_album_cover(&metadata.album, &metadata.album_artist).await {
-                Ok(Some(url)) => match reqwest::get(&url).await {
-                    Ok(resp) => match resp.bytes().await {
-                        Ok(bytes) => match save_image(bytes.to_vec()).await {
-                            Ok(hash) => images.push(hash),
-                            Err(e) => error!(
-                                "failed to save Deezer art for album {}: {}",
-                                metadata.album, e
-                            ),
-                        },
-                        Err(e) => error!(
-                            "failed to read Deezer art bytes for album {}: {}",
-                            metadata.album, e
-                        ),
-                    },
-                    Err(e) => error!(
-                        "failed to fetch Deezer art for album {}: {}",
-                        metadata.album, e
-                    ),
-                },
-                Ok(None) => debug!("no Deezer cover for album: {}", metadata.album),
-                Err(e) => error!(
-                    "Deezer album cover search failed for {}: {}",
-                    metadata.album, e
-                ),
-            }
-        }
 
         // Include the MBID in the slug key when available so that identically-named
         // albums (e.g. self-titled LPs) each get a stable, unique slug.  When there
@@ -480,75 +1208,408 @@ async fn album_foc(
 
         // insert into database — use ON CONFLICT to handle the race where two
         // concurrent files from the same album both try to insert at once.
-        let row = sqlx::query!(
+        let row: Option<i32> = sqlx::query_scalar(
             r#"
-            INSERT INTO album (name, artist, year, mbid, slug, disambiguation, copyright, label, created_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            INSERT INTO album (name, artist, year, mbid, slug, copyright, label, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, now())
             ON CONFLICT (slug) DO NOTHING
             RETURNING id;
             "#,
-            metadata.album,
-            artist[0],
-            metadata.year,
-            album_mbid,
-            album_slug,
-            album_disambiguation,
-            metadata.copyright,
-            metadata.label,
-            time::OffsetDateTime::now_utc()
         )
+        .bind(&metadata.album)
+        .bind(album_artist)
+        .bind(metadata.year)
+        .bind(&album_mbid)
+        .bind(&album_slug)
+        .bind(&metadata.copyright)
+        .bind(&metadata.label)
         .fetch_optional(&pool)
         .await?;
 
-        // If None, another concurrent insert won the race — look up the existing id.
-        let album_id = match row {
-            Some(r) => r.id,
-            None => {
-                sqlx::query_scalar!("SELECT id FROM album WHERE slug = $1", album_slug)
-                    .fetch_one(&pool)
-                    .await?
-            }
-        };
+        // If None, another concurrent insert won the race — look up the existing id.
+        let album_id = match row {
+            Some(id) => id,
+            None => {
+                sqlx::query_scalar!("SELECT id FROM album WHERE slug = $1", album_slug)
+                    .fetch_one(&pool)
+                    .await?
+            }
+        };
+
+        // insert the art path into album-art
+        if !images.is_empty() {
+            for image in images {
+                sqlx::query(
+                    r#"
+                    INSERT INTO album_art (album, path, source, source_path, source_size, source_mtime_ns, created_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, now())
+                    ON CONFLICT DO NOTHING
+                    "#,
+                )
+                .bind(album_id)
+                .bind(image)
+                .bind(art_source)
+                .bind(sidecar.as_ref().and_then(|(p, _)| p.to_str()))
+                .bind(sidecar.as_ref().map(|(_, s)| s.0 as i64))
+                .bind(sidecar.as_ref().map(|(_, s)| s.1))
+                .execute(&pool)
+                .await?;
+            }
+        }
+        // insert into genre-album
+        // Note: albums themselves don't have 'genres' so this is based on all the genres in all the songs
+        // SO we should do an upsert here
+        if let Some(genres) = genres {
+            for genre in genres {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO album_genre (album, genre, created_at)
+                    VALUES ($1, $2, now())
+                    ON CONFLICT DO NOTHING
+                    "#,
+                    album_id,
+                    genre
+                )
+                .execute(&pool)
+                .await?;
+            }
+        }
+        Ok(album_id)
+    }
+}
+
+/// Store release-group types and original date on an album, and mark it as looked up.
+async fn store_release_info(
+    album: i32,
+    release_group: Option<&musicbrainz::MbReleaseGroup>,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE album SET
+          release_type = $2, secondary_types = $3, original_date = $4,
+          disambiguation = COALESCE(disambiguation, $5),
+          release_checked = true
+        WHERE id = $1
+        "#,
+    )
+    .bind(album)
+    .bind(release_group.and_then(|rg| rg.release_type()))
+    .bind(release_group.map(|rg| rg.secondary_types()).unwrap_or_default())
+    .bind(release_group.and_then(|rg| rg.original_date()))
+    .bind(release_group.and_then(|rg| rg.disambiguation.clone()))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Look up what a scan leaves out for an album: its MusicBrainz release group, and cover
+/// art from the Cover Art Archive or Deezer when it has none. Failed lookups are left
+/// unchecked so the next pass tries again.
+pub async fn enrich_album(id: i32, pool: &sqlx::Pool<Postgres>) -> anyhow::Result<()> {
+    let (name, artist, mbid, release_checked, art_checked, has_art): (
+        String,
+        String,
+        Option<String>,
+        bool,
+        bool,
+        bool,
+    ) = sqlx::query_as(
+        r#"
+        SELECT album.name, artist.name, album.mbid, album.release_checked, album.remote_art_checked,
+               EXISTS (SELECT 1 FROM album_art WHERE album_art.album = album.id)
+        FROM album JOIN artist ON artist.id = album.artist
+        WHERE album.id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_one(pool)
+    .await?;
+
+    // - If tags carry an MBID, do a direct release lookup to get its release group.
+    // - If no MBID, search by name+artist.
+    let mut mbid = mbid;
+    if !release_checked {
+        let lookup = match &mbid {
+            Some(mbid) => musicbrainz::get_release_group_info(mbid).await,
+            None => musicbrainz::get_album_info(&name, &artist).await,
+        };
+        match lookup {
+            Ok(release_group) => {
+                if let (None, Some(rg)) = (&mbid, &release_group) {
+                    sqlx::query("UPDATE album SET mbid = $2 WHERE id = $1 AND mbid IS NULL")
+                        .bind(id)
+                        .bind(&rg.id)
+                        .execute(pool)
+                        .await?;
+                    mbid = Some(rg.id.clone());
+                }
+                store_release_info(id, release_group.as_ref(), pool).await?;
+            }
+            Err(e) => warn!("MusicBrainz album lookup failed for {}: {}", name, e),
+        }
+    }
+
+    if art_checked || has_art {
+        return Ok(());
+    }
+    // Cover Art Archive, then Deezer
+    let mut failed = false;
+    let mut image = None;
+    if let Some(mbid) = &mbid {
+        match musicbrainz::get_cover_art_bytes(mbid).await {
+            Ok(Some(bytes)) => image = Some((save_image(bytes).await?, "coverartarchive")),
+            Ok(None) => debug!("no cover art on CAA for album MBID: {}", mbid),
+            Err(e) => {
+                failed = true;
+                error!("failed to fetch CAA art for album {}: {}", name, e)
+            }
+        }
+    }
+    if image.is_none() {
+        match deezer::get_album_cover(&name, &artist).await {
+            Ok(Some(url)) => match reqwest::get(&url).await {
+                Ok(resp) => match resp.bytes().await {
+                    Ok(bytes) => image = Some((save_image(bytes.to_vec()).await?, "deezer")),
+                    Err(e) => {
+                        failed = true;
+                        error!("failed to read Deezer art bytes for album {}: {}", name, e)
+                    }
+                },
+                Err(e) => {
+                    failed = true;
+                    error!("failed to fetch Deezer art for album {}: {}", name, e)
+                }
+            },
+            Ok(None) => debug!("no Deezer cover for album: {}", name),
+            Err(e) => {
+                failed = true;
+                error!("Deezer album cover search failed for {}: {}", name, e)
+            }
+        }
+    }
+
+    if let Some((hash, source)) = &image {
+        sqlx::query(
+            r#"
+            INSERT INTO album_art (album, path, source, created_at)
+            VALUES ($1, $2, $3, now())
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(id)
+        .bind(hash)
+        .bind(*source)
+        .execute(pool)
+        .await?;
+    }
+    if image.is_some() || !failed {
+        sqlx::query("UPDATE album SET remote_art_checked = true WHERE id = $1")
+            .bind(id)
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Look up what a scan leaves out for a song: its MBID when untagged, searched once under
+/// its first artist, or else the MusicBrainz artist credit of its tagged MBID. Returns
+/// whether the credit splits its artists differently, in which case the file should be
+/// rescanned.
+pub async fn enrich_song(id: i32, pool: &sqlx::Pool<Postgres>) -> anyhow::Result<bool> {
+    let (name, artist, mbid, searched): (String, String, Option<String>, bool) = sqlx::query_as(
+        r#"
+        SELECT song.name, artist.name, song.mbid, song.mbid_searched
+        FROM song
+        JOIN song_artist ON song_artist.song = song.id
+        JOIN artist ON artist.id = song_artist.artist
+        WHERE song.id = $1
+        ORDER BY song_artist.id
+        LIMIT 1
+        "#,
+    )
+    .bind(id)
+    .fetch_one(pool)
+    .await?;
+
+    let mbid = match (mbid, searched) {
+        (Some(mbid), false) => mbid,
+        (_, true) => return Ok(false),
+        (None, false) => {
+            // an error leaves the song unsearched so the next pass tries again
+            match musicbrainz::get_track_mbid(&name, &artist).await {
+                Ok(mbid) => {
+                    sqlx::query("UPDATE song SET mbid = $2, mbid_searched = true WHERE id = $1")
+                        .bind(id)
+                        .bind(mbid)
+                        .execute(pool)
+                        .await?;
+                }
+                Err(e) => warn!("MusicBrainz track lookup failed for {}: {}", name, e),
+            }
+            return Ok(false);
+        }
+    };
+
+    if cached_recording_artists(&mbid, pool).await?.is_some() {
+        return Ok(false);
+    }
+    let mut credited: Vec<String> = fetch_recording_artists(&mbid, pool)
+        .await?
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    if credited.is_empty() {
+        return Ok(false);
+    }
+    let mut linked: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT artist.name FROM song_artist JOIN artist ON artist.id = song_artist.artist
+        WHERE song_artist.song = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_all(pool)
+    .await?;
+    credited.sort();
+    linked.sort();
+    Ok(credited != linked)
+}
+
+#[derive(sqlx::FromRow)]
+struct AlbumArtRow {
+    id: i32,
+    source: Option<String>,
+    source_path: Option<String>,
+    source_size: Option<i64>,
+    source_mtime_ns: Option<i64>,
+}
+
+/// Read a sidecar image and save it, returning the cache key and the file's size/mtime.
+async fn save_sidecar_image(path: &std::path::Path) -> anyhow::Result<(String, (u64, i64))> {
+    let signature = source_file_signature(path)?;
+    let bytes = tokio::fs::read(path).await?;
+    Ok((save_image(bytes).await?, signature))
+}
+
+/// Bring an existing album's art in line with the sidecar images next to one of its songs.
+/// Sidecar art replaces art fetched over the network, but never art embedded in the files.
+async fn sync_sidecar_art(
+    album_id: i32,
+    song: &std::path::Path,
+    embedded: bool,
+    names: &[String],
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    if embedded {
+        return Ok(());
+    }
+    let rows = sqlx::query_as::<_, AlbumArtRow>(
+        "SELECT id, source, source_path, source_size, source_mtime_ns FROM album_art WHERE album = $1",
+    )
+    .bind(album_id)
+    .fetch_all(pool)
+    .await?;
+
+    let Some(path) = art::find_sidecar(song, names) else {
+        // the sidecar we read earlier may have been deleted
+        for row in rows.iter().filter(|r| r.source.as_deref() == Some("sidecar")) {
+            let exists = row
+                .source_path
+                .as_deref()
+                .map(|p| std::path::Path::new(p).is_file())
+                .unwrap_or(false);
+            if !exists {
+                sqlx::query("DELETE FROM album_art WHERE id = $1")
+                    .bind(row.id)
+                    .execute(pool)
+                    .await?;
+            }
+        }
+        return Ok(());
+    };
+
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("path is not valid UTF-8: {}", path.display()))?;
+    let signature = source_file_signature(&path)?;
+    let unchanged = rows.iter().any(|r| {
+        r.source.as_deref() == Some("sidecar")
+            && r.source_path.as_deref() == Some(path_str)
+            && r.source_size == Some(signature.0 as i64)
+            && r.source_mtime_ns == Some(signature.1)
+    });
+    // another song on the album may carry embedded art
+    if unchanged || rows.iter().any(|r| r.source.as_deref() == Some("embedded")) {
+        return Ok(());
+    }
+
+    let (hash, signature) = save_sidecar_image(&path).await?;
+    sqlx::query("DELETE FROM album_art WHERE album = $1 AND source IS DISTINCT FROM 'embedded'")
+        .bind(album_id)
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO album_art (album, path, source, source_path, source_size, source_mtime_ns, created_at)
+        VALUES ($1, $2, 'sidecar', $3, $4, $5, now())
+        "#,
+    )
+    .bind(album_id)
+    .bind(hash)
+    .bind(path_str)
+    .bind(signature.0 as i64)
+    .bind(signature.1)
+    .execute(pool)
+    .await?;
+    info!("updated album {} art from {}", album_id, path.display());
+    Ok(())
+}
+
+/// Re-check sidecar art for the albums whose songs sit next to `image`, or in disc folders
+/// below it, and for the disc whose folder holds it. Called by the watcher when a cover
+/// image changes.
+pub async fn refresh_sidecar_art(
+    image: &std::path::Path,
+    names: &[String],
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some(dir) = image.parent() else {
+        return Ok(());
+    };
+    let prefix = dir
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("path is not valid UTF-8: {}", dir.display()))?;
+    let songs: Vec<(i32, String, i32)> = sqlx::query_as(
+        "SELECT album, path, COALESCE(disc, 1) FROM song WHERE left(path, length($1)) = $1 ORDER BY album, path",
+    )
+    .bind(prefix)
+    .fetch_all(pool)
+    .await?;
 
-        // insert the art path into album-art
-        if !images.is_empty() {
-            for image in images {
-                sqlx::query!(
-                    r#"
-                    INSERT INTO album_art (album, path, created_at)
-                    VALUES ($1, $2, now())
-                    ON CONFLICT DO NOTHING
-                    "#,
-                    album_id,
-                    image
-                )
-                .execute(&pool)
-                .await?;
-            }
+    let mut seen = std::collections::HashSet::new();
+    let mut seen_discs = std::collections::HashSet::new();
+    for (album, path, disc) in songs {
+        let path = std::path::PathBuf::from(path);
+        let parent = path.parent();
+        let nearby = parent == Some(dir) || parent.and_then(|p| p.parent()) == Some(dir);
+        if nearby && seen.insert(album) {
+            sync_sidecar_art(album, &path, false, names, pool).await?;
         }
-        // insert into genre-album
-        // Note: albums themselves don't have 'genres' so this is based on all the genres in all the songs
-        // SO we should do an upsert here
-        if let Some(genres) = genres {
-            for genre in genres {
-                sqlx::query!(
-                    r#"
-                    INSERT INTO album_genre (album, genre, created_at)
-                    VALUES ($1, $2, now())
-                    ON CONFLICT DO NOTHING
-                    "#,
-                    album_id,
-                    genre
-                )
-                .execute(&pool)
-                .await?;
-            }
+        if parent == Some(dir) && seen_discs.insert((album, disc)) {
+            sync_disc_art(album, disc, &path, names, pool).await?;
         }
-        Ok(album_id)
     }
+    Ok(())
 }
 
-async fn genre_foc(genres_orig: &[String], pool: sqlx::Pool<Postgres>) -> anyhow::Result<Vec<i32>> {
+/// Find or create the genres a song is tagged with. Spellings are folded to a key and
+/// mapped through the configured aliases, then any spelling an admin merged away.
+async fn genre_foc(
+    genres_orig: &[String],
+    aliases: &HashMap<String, String>,
+    pool: sqlx::Pool<Postgres>,
+) -> anyhow::Result<Vec<i32>> {
     let mut genre_ids = Vec::new();
     let genres = if genres_orig.len() == 1 {
         genres_orig[0]
@@ -564,45 +1625,171 @@ async fn genre_foc(genres_orig: &[String], pool: sqlx::Pool<Postgres>) -> anyhow
             .collect()
     };
     for genre in genres {
+        let name = genre::canonical_name(&genre, aliases);
+        let key = genre::genre_key(&name);
         // SELECT-first to avoid the check-then-insert race.
-        if let Ok(Some(id)) = sqlx::query_scalar!(
-            "SELECT id FROM genre WHERE name = $1 ORDER BY id LIMIT 1",
-            genre
+        if let Ok(Some(id)) = sqlx::query_scalar::<_, i32>(
+            r#"
+            SELECT id FROM (
+              SELECT genre AS id, 0 AS rank FROM genre_alias WHERE key = $1
+              UNION ALL
+              SELECT id, 1 FROM genre WHERE key = $1
+            ) found
+            ORDER BY rank, id LIMIT 1
+            "#,
         )
+        .bind(&key)
         .fetch_optional(&pool)
         .await
         {
-            genre_ids.push(id);
+            if !genre_ids.contains(&id) {
+                genre_ids.push(id);
+            }
             continue;
         }
         // Not found — insert. If a concurrent insert races us, fall back to SELECT.
-        let id = match sqlx::query!(
-            r#"
-            INSERT INTO genre (name, created_at)
-            VALUES ($1, now())
-            RETURNING id;
-            "#,
-            genre
+        let id = match sqlx::query_scalar(
+            "INSERT INTO genre (name, key, created_at) VALUES ($1, $2, now()) RETURNING id",
         )
+        .bind(&name)
+        .bind(&key)
         .fetch_one(&pool)
         .await
         {
-            Ok(row) => row.id,
+            Ok(id) => id,
             Err(e) => {
-                debug!("genre insert failed for '{}' (likely race): {}", genre, e);
-                sqlx::query_scalar!(
-                    "SELECT id FROM genre WHERE name = $1 ORDER BY id LIMIT 1",
-                    genre
-                )
-                .fetch_one(&pool)
-                .await?
+                debug!("genre insert failed for '{}' (likely race): {}", name, e);
+                sqlx::query_scalar("SELECT id FROM genre WHERE key = $1 ORDER BY id LIMIT 1")
+                    .bind(&key)
+                    .fetch_one(&pool)
+                    .await?
             }
         };
-        genre_ids.push(id);
+        if !genre_ids.contains(&id) {
+            genre_ids.push(id);
+        }
     }
     Ok(genre_ids)
 }
 
+/// Bring stored genres in line with the alias map and key folding: rename genres to their
+/// canonical spelling and merge genres that now share a key into the oldest of them. Then
+/// link sub-genres to the parents configured in `parents`. Returns how many genres were
+/// merged away.
+pub async fn canonicalize_genres(
+    aliases: &HashMap<String, String>,
+    parents: &HashMap<String, String>,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<usize> {
+    let genres: Vec<(i32, Option<String>, Option<String>)> =
+        sqlx::query_as("SELECT id, name, key FROM genre ORDER BY id")
+            .fetch_all(pool)
+            .await?;
+
+    let mut by_key: HashMap<String, i32> = HashMap::new();
+    let mut merged = 0;
+    for (id, name, key) in genres {
+        let stored = name.unwrap_or_default();
+        let name = genre::canonical_name(&stored, aliases);
+        let new_key = genre::genre_key(&name);
+        if let Some(&into) = by_key.get(&new_key) {
+            merge_genres(&[id], into, pool).await?;
+            merged += 1;
+            continue;
+        }
+        if name != stored || key.as_deref() != Some(new_key.as_str()) {
+            sqlx::query("UPDATE genre SET name = $2, key = $3, updated_at = now() WHERE id = $1")
+                .bind(id)
+                .bind(&name)
+                .bind(&new_key)
+                .execute(pool)
+                .await?;
+        }
+        by_key.insert(new_key, id);
+    }
+
+    let mut children = Vec::with_capacity(parents.len());
+    for (child, parent) in parents {
+        let child = genre::genre_key(&genre::canonical_name(child, aliases));
+        let parent = genre_foc(&[parent.clone()], aliases, pool.clone()).await?;
+        let parent = match parent.first() {
+            Some(&parent) => parent,
+            None => continue,
+        };
+        sqlx::query(
+            "UPDATE genre SET parent = $2, updated_at = now() WHERE key = $1 AND id <> $2 AND parent IS DISTINCT FROM $2",
+        )
+        .bind(&child)
+        .bind(parent)
+        .execute(pool)
+        .await?;
+        children.push(child);
+    }
+    // genres taken out of the map go back to the top level
+    sqlx::query("UPDATE genre SET parent = NULL WHERE parent IS NOT NULL AND key <> ALL($1)")
+        .bind(&children)
+        .execute(pool)
+        .await?;
+    Ok(merged)
+}
+
+/// Move every song and album in the `from` genres to `into`, then delete them. Their keys
+/// are kept as aliases so later scans file those spellings under `into` as well.
+pub async fn merge_genres(
+    from: &[i32],
+    into: i32,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let from: Vec<i32> = from.iter().copied().filter(|&id| id != into).collect();
+    if from.is_empty() {
+        return Ok(());
+    }
+    let mut tx = pool.begin().await?;
+    // neither junction table is unique, so drop rows that would end up duplicated
+    for (table, column) in [("song_genre", "song"), ("album_genre", "album")] {
+        sqlx::query(&format!(
+            "UPDATE {0} SET genre = $2, updated_at = now() WHERE genre = ANY($1)",
+            table
+        ))
+        .bind(&from)
+        .bind(into)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(&format!(
+            "DELETE FROM {0} a USING {0} b WHERE a.genre = $1 AND b.genre = $1 AND a.{1} = b.{1} AND a.id > b.id",
+            table, column
+        ))
+        .bind(into)
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query("UPDATE genre_alias SET genre = $2 WHERE genre = ANY($1)")
+        .bind(&from)
+        .bind(into)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO genre_alias (key, genre, created_at)
+        SELECT key, $2, now() FROM genre
+        WHERE id = ANY($1) AND key IS NOT NULL
+          AND key IS DISTINCT FROM (SELECT key FROM genre WHERE id = $2)
+        ON CONFLICT (key) DO UPDATE SET genre = EXCLUDED.genre
+        "#,
+    )
+    .bind(&from)
+    .bind(into)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM genre WHERE id = ANY($1)")
+        .bind(&from)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    info!("merged genres {:?} into {}", from, into);
+    Ok(())
+}
+
 async fn song_foc(
     metadata: AudioMetadata,
     artist: Vec<i32>,
@@ -610,35 +1797,25 @@ async fn song_foc(
     genres: Option<Vec<i32>>,
     pool: sqlx::Pool<Postgres>,
 ) -> anyhow::Result<i32> {
-    // resolve track MBID: use tag value, or fall back to MusicBrainz lookup
-    let mbid_track = if metadata.mbid_track.is_none() {
-        let artist_name = metadata.artists.first().map(|s| s.as_str()).unwrap_or("");
-        match crate::metadata::musicbrainz::get_track_mbid(&metadata.name, artist_name).await {
-            Ok(mbid) => mbid,
-            Err(e) => {
-                tracing::warn!(
-                    "MusicBrainz track lookup failed for {}: {}",
-                    metadata.name,
-                    e
-                );
-                None
-            }
-        }
-    } else {
-        metadata.mbid_track.clone()
-    };
-
     let source_signature = source_file_signature(&metadata.path)?;
 
     let path_str = metadata.path.to_str().ok_or_else(|| {
         anyhow::anyhow!("path is not valid UTF-8: {}", metadata.path.display())
     })?;
 
+    // CUE virtual tracks share their source file's path
+    let cue_track = metadata.cue.map(|c| c.track as i32);
+    let cue_start = metadata.cue.map(|c| c.start_ms as i32);
+    let cue_end = metadata.cue.and_then(|c| c.end_ms).map(|e| e as i32);
+
     // check if song exists
-    match sqlx::query("SELECT id, audio_hash_size, audio_hash_mtime_ns FROM song WHERE path = $1")
-        .bind(path_str)
-        .fetch_optional(&pool)
-        .await
+    match sqlx::query(
+        "SELECT id, audio_hash_size, audio_hash_mtime_ns FROM song WHERE path = $1 AND cue_track IS NOT DISTINCT FROM $2",
+    )
+    .bind(path_str)
+    .bind(cue_track)
+    .fetch_optional(&pool)
+    .await
     {
         Ok(Some(row)) => {
             let song_id: i32 = row.try_get("id")?;
@@ -646,21 +1823,34 @@ async fn song_foc(
                 == Some(source_signature.0 as i64)
                 && row.try_get::<Option<i64>, _>("audio_hash_mtime_ns")?
                     == Some(source_signature.1);
-            // Update metadata and stamp last_scanned_at; leave plays/liked/last_play/created_at untouched
+            // Update metadata and stamp last_scanned_at; leave plays/liked/last_play/created_at untouched.
+            // An MBID found by enrich_song's search is kept until the file is tagged with one.
             sqlx::query(
                 r#"
                 UPDATE song SET
                   number = $2, disc = $3, name = $4, album = $5, album_artist = $6,
                   duration = $7, lossless = $8, sample_rate = $9, bits_per_sample = $10,
-                  num_channels = $11, mbid = $12, composer = $13, isrc = $14, bpm = $15,
+                  num_channels = $11, composer = $13, isrc = $14, bpm = $15,
                   audio_hash = CASE WHEN $16 THEN audio_hash ELSE NULL END,
                   audio_hash_size = $17, audio_hash_mtime_ns = $18,
-                  updated_at = now(), last_scanned_at = now()
+                  bitrate = $19, total_samples = $20, encoder_delay = $21, encoder_padding = $22,
+                  duration_ms = $23,
+                  replaygain_track_gain = CASE WHEN $24::real IS NULL AND $16 AND replaygain_computed THEN replaygain_track_gain ELSE $24 END,
+                  replaygain_track_peak = CASE WHEN $24::real IS NULL AND $16 AND replaygain_computed THEN replaygain_track_peak ELSE $25 END,
+                  replaygain_computed = ($24::real IS NULL AND $16 AND replaygain_computed),
+                  replaygain_failed = (replaygain_failed AND $16),
+                  replaygain_album_gain = CASE WHEN $26::real IS NULL AND replaygain_album_derived THEN replaygain_album_gain ELSE $26 END,
+                  replaygain_album_peak = CASE WHEN $26::real IS NULL AND replaygain_album_derived THEN replaygain_album_peak ELSE $27 END,
+                  replaygain_album_derived = ($26::real IS NULL AND replaygain_album_derived),
+                  start_ms = $28, end_ms = $29, explicit = $30,
+                  mbid = CASE WHEN $12::varchar IS NULL AND mbid_searched THEN mbid ELSE $12 END,
+                  mbid_searched = ($12::varchar IS NULL AND mbid_searched),
+                  scanner_version = $31, updated_at = now(), last_scanned_at = now()
                 WHERE id = $1
                 "#,
             )
             .bind(song_id)
-            .bind(metadata.number as i32)
+            .bind(Some(metadata.number as i32).filter(|&n| n > 0))
             .bind(metadata.disc.map(|e| e as i32))
             .bind(metadata.name)
             .bind(album as i32)
@@ -670,13 +1860,26 @@ async fn song_foc(
             .bind(metadata.sample_rate.map(|e| e as i32))
             .bind(metadata.bits_per_sample.map(|e| e as i32))
             .bind(metadata.num_channels.map(|e| e as i32))
-            .bind(mbid_track)
+            .bind(metadata.mbid_track)
             .bind(metadata.composer)
             .bind(metadata.isrc)
             .bind(metadata.bpm.map(|b| b as i32))
             .bind(unchanged_source)
             .bind(source_signature.0 as i64)
             .bind(source_signature.1)
+            .bind(metadata.bitrate.map(|b| b as i32))
+            .bind(metadata.total_samples.map(|t| t as i64))
+            .bind(metadata.encoder_delay.map(|d| d as i32))
+            .bind(metadata.encoder_padding.map(|p| p as i32))
+            .bind(metadata.duration_ms as i32)
+            .bind(metadata.replay_gain.track_gain)
+            .bind(metadata.replay_gain.track_peak)
+            .bind(metadata.replay_gain.album_gain)
+            .bind(metadata.replay_gain.album_peak)
+            .bind(cue_start)
+            .bind(cue_end)
+            .bind(metadata.explicit)
+            .bind(SCANNER_VERSION)
             .execute(&pool)
             .await?;
 
@@ -725,35 +1928,54 @@ async fn song_foc(
         }
         Ok(None) => {
             // put in database
-            let song_slug = make_slug(path_str);
-            let song_id = sqlx::query!(
+            let song_slug = match cue_track {
+                Some(track) => make_slug(&format!("{}#{}", path_str, track)),
+                None => make_slug(path_str),
+            };
+            let song_id: i32 = sqlx::query_scalar(
                 r#"
-                INSERT INTO song (number, disc, name, path, album, album_artist, liked, duration, plays, lossless, sample_rate, bits_per_sample, num_channels, mbid, slug, composer, isrc, bpm, last_scanned_at, created_at)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, now(), now())
+                INSERT INTO song (number, disc, name, path, album, album_artist, liked, duration, plays, lossless, sample_rate, bits_per_sample, num_channels, mbid, slug, composer, isrc, bpm,
+                                  bitrate, total_samples, encoder_delay, encoder_padding, duration_ms,
+                                  replaygain_track_gain, replaygain_track_peak, replaygain_album_gain, replaygain_album_peak,
+                                  cue_track, start_ms, end_ms, explicit, scanner_version, last_scanned_at, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, now(), now())
                 RETURNING id;
                 "#,
-                metadata.number as i32,
-                metadata.disc.map(|e| e as i32),
-                metadata.name,
-                path_str,
-                album as i32,
-                artist[0],
-                false,
-                metadata.duration as i32,
-                0 as i32,
-                metadata.lossless,
-                metadata.sample_rate.map(|e| e as i32),
-                metadata.bits_per_sample.map(|e| e as i32),
-                metadata.num_channels.map(|e| e as i32),
-                mbid_track,
-                song_slug,
-                metadata.composer,
-                metadata.isrc,
-                metadata.bpm.map(|b| b as i32),
             )
+            .bind(Some(metadata.number as i32).filter(|&n| n > 0))
+            .bind(metadata.disc.map(|e| e as i32))
+            .bind(metadata.name)
+            .bind(path_str)
+            .bind(album as i32)
+            .bind(artist[0])
+            .bind(false)
+            .bind(metadata.duration as i32)
+            .bind(0_i32)
+            .bind(metadata.lossless)
+            .bind(metadata.sample_rate.map(|e| e as i32))
+            .bind(metadata.bits_per_sample.map(|e| e as i32))
+            .bind(metadata.num_channels.map(|e| e as i32))
+            .bind(metadata.mbid_track)
+            .bind(song_slug)
+            .bind(metadata.composer)
+            .bind(metadata.isrc)
+            .bind(metadata.bpm.map(|b| b as i32))
+            .bind(metadata.bitrate.map(|b| b as i32))
+            .bind(metadata.total_samples.map(|t| t as i64))
+            .bind(metadata.encoder_delay.map(|d| d as i32))
+            .bind(metadata.encoder_padding.map(|p| p as i32))
+            .bind(metadata.duration_ms as i32)
+            .bind(metadata.replay_gain.track_gain)
+            .bind(metadata.replay_gain.track_peak)
+            .bind(metadata.replay_gain.album_gain)
+            .bind(metadata.replay_gain.album_peak)
+            .bind(cue_track)
+            .bind(cue_start)
+            .bind(cue_end)
+            .bind(metadata.explicit)
+            .bind(SCANNER_VERSION)
             .fetch_one(&pool)
-            .await?
-            .id;
+            .await?;
 
             // insert into song-genres
             if let Some(genres) = genres {
@@ -785,44 +2007,83 @@ async fn song_foc(
     }
 }
 
-/// Delete a song by its file path and clean up orphaned albums/artists.
+/// Delete the songs read from a file (several for a CUE-split rip) and clean up orphaned albums/artists.
 pub async fn delete_song_by_path(path: &str, pool: &sqlx::Pool<Postgres>) -> anyhow::Result<()> {
-    let song_id = match sqlx::query_scalar!("SELECT id FROM song WHERE path = $1 LIMIT 1", path)
-        .fetch_optional(pool)
+    let song_ids: Vec<i32> = sqlx::query_scalar("SELECT id FROM song WHERE path = $1")
+        .bind(path)
+        .fetch_all(pool)
+        .await?;
+    if song_ids.is_empty() {
+        return Ok(());
+    }
+
+    delete_songs(&song_ids, pool).await?;
+    cleanup_orphans(pool).await
+}
+
+/// Remove songs for a file that its latest scan no longer produced: virtual tracks missing
+/// from the CUE sheet, or the whole-file song once a sheet splits it (and vice versa).
+pub async fn prune_file_songs(
+    path: &std::path::Path,
+    cue_tracks: &[i32],
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some(path) = path.to_str() else {
+        return Ok(());
+    };
+    let song_ids: Vec<i32> = if cue_tracks.is_empty() {
+        sqlx::query_scalar("SELECT id FROM song WHERE path = $1 AND cue_track IS NOT NULL")
+            .bind(path)
+            .fetch_all(pool)
+            .await?
+    } else {
+        sqlx::query_scalar(
+            "SELECT id FROM song WHERE path = $1 AND (cue_track IS NULL OR cue_track <> ALL($2))",
+        )
+        .bind(path)
+        .bind(cue_tracks)
+        .fetch_all(pool)
         .await?
-    {
-        Some(id) => id,
-        None => return Ok(()),
     };
+    if song_ids.is_empty() {
+        return Ok(());
+    }
+
+    info!("pruning {} replaced song(s) for {}", song_ids.len(), path);
+    delete_songs(&song_ids, pool).await?;
+    cleanup_orphans(pool).await
+}
 
+/// Delete songs by id, detaching them from playlists and favorites first.
+async fn delete_songs(song_ids: &[i32], pool: &sqlx::Pool<Postgres>) -> anyhow::Result<()> {
     // Nullify LL pointers in sibling playlist_item rows before DELETE fires
     sqlx::query!(
-        "UPDATE playlist_item SET prev_song_id = NULL WHERE prev_song_id IN (SELECT id FROM playlist_item WHERE song_id = $1)",
-        song_id
+        "UPDATE playlist_item SET prev_song_id = NULL WHERE prev_song_id IN (SELECT id FROM playlist_item WHERE song_id = ANY($1))",
+        song_ids
     )
     .execute(pool)
     .await?;
 
     sqlx::query!(
-        "UPDATE playlist_item SET next_song_id = NULL WHERE next_song_id IN (SELECT id FROM playlist_item WHERE song_id = $1)",
-        song_id
+        "UPDATE playlist_item SET next_song_id = NULL WHERE next_song_id IN (SELECT id FROM playlist_item WHERE song_id = ANY($1))",
+        song_ids
     )
     .execute(pool)
     .await?;
 
     // favorites has no FK so must be cleaned manually
     sqlx::query!(
-        "DELETE FROM favorites WHERE favoritable_id = $1 AND favoritable_type = 'song'",
-        song_id
+        "DELETE FROM favorites WHERE favoritable_type = 'song' AND favoritable_id = ANY($1)",
+        song_ids
     )
     .execute(pool)
     .await?;
 
-    sqlx::query!("DELETE FROM song WHERE id = $1", song_id)
+    sqlx::query!("DELETE FROM song WHERE id = ANY($1)", song_ids)
         .execute(pool)
         .await?;
 
-    cleanup_orphans(pool).await
+    Ok(())
 }
 
 /// Invalidate cached audio features when a watcher reports a file replacement.
@@ -831,20 +2092,20 @@ pub async fn invalidate_features_by_path(
     path: &str,
     pool: &sqlx::Pool<Postgres>,
 ) -> anyhow::Result<()> {
-    let song_id = sqlx::query("SELECT id FROM song WHERE path = $1 LIMIT 1")
+    // every virtual track of a CUE-split file shares the file's hash
+    let song_ids: Vec<i32> = sqlx::query_scalar("SELECT id FROM song WHERE path = $1")
         .bind(path)
-        .fetch_optional(pool)
-        .await?
-        .and_then(|row| row.try_get::<i32, _>("id").ok());
-    if let Some(song_id) = song_id {
+        .fetch_all(pool)
+        .await?;
+    if !song_ids.is_empty() {
         sqlx::query(
-            "UPDATE song SET audio_hash = NULL, audio_hash_size = NULL, audio_hash_mtime_ns = NULL WHERE id = $1",
+            "UPDATE song SET audio_hash = NULL, audio_hash_size = NULL, audio_hash_mtime_ns = NULL WHERE id = ANY($1)",
         )
-        .bind(song_id)
+        .bind(&song_ids)
         .execute(pool)
         .await?;
-        sqlx::query("DELETE FROM song_hash_failures WHERE song = $1")
-            .bind(song_id)
+        sqlx::query("DELETE FROM song_hash_failures WHERE song = ANY($1)")
+            .bind(&song_ids)
             .execute(pool)
             .await?;
     }
@@ -862,19 +2123,144 @@ fn source_file_signature(path: &std::path::Path) -> anyhow::Result<(u64, i64)> {
     Ok((metadata.len(), nanos.try_into()?))
 }
 
-/// Remove albums with no songs and artists with no albums or songs.
+/// Hash of the first and last 64 KiB of a file, where tags are kept, to catch edits that
+/// leave the size and modification time alone.
+fn partial_file_hash(path: &std::path::Path) -> anyhow::Result<String> {
+    const CHUNK: u64 = 64 * 1024;
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut buf = Vec::new();
+    (&mut file).take(CHUNK).read_to_end(&mut buf)?;
+    if len > CHUNK {
+        file.seek(SeekFrom::Start((len - CHUNK).max(CHUNK)))?;
+        file.take(CHUNK).read_to_end(&mut buf)?;
+    }
+    let mut h = Md5::new();
+    Digest::update(&mut h, &buf);
+    Ok(format!("{:x}", h.finalize()))
+}
+
+/// Newest modification time of the lyrics, CUE sheets and cover art in `dir`, and of `dir`
+/// itself, which changes when any of them is added or removed.
+fn sidecar_mtime_ns(dir: &std::path::Path, cover_art_filenames: &[String]) -> Option<i64> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            let lrc = p
+                .extension()
+                .and_then(|e| e.to_str())
+                .map_or(false, |e| e.eq_ignore_ascii_case("lrc"));
+            lrc || cue::is_cue(p) || art::is_sidecar_name(p, cover_art_filenames)
+        })
+        .chain(std::iter::once(dir.to_path_buf()))
+        .filter_map(|p| source_file_signature(&p).ok())
+        .map(|(_, mtime)| mtime)
+        .max()
+}
+
+/// Sidecar modification times of the directories one scan has looked at, so a directory
+/// is listed once rather than once for every file in it.
+#[derive(Default)]
+pub struct SidecarTimes(DashMap<std::path::PathBuf, Option<i64>>);
+
+impl SidecarTimes {
+    /// Newest modification time of what a scan reads besides `song` itself: its lyrics
+    /// sidecar, the CUE sheets next to it and its cover art, in every directory art for it
+    /// is looked up in.
+    fn newest(&self, song: &std::path::Path, cover_art_filenames: &[String]) -> Option<i64> {
+        art::sidecar_dirs(song)
+            .into_iter()
+            .filter_map(|dir| {
+                if let Some(mtime) = self.0.get(dir) {
+                    return *mtime;
+                }
+                let mtime = sidecar_mtime_ns(dir, cover_art_filenames);
+                self.0.insert(dir.to_path_buf(), mtime);
+                mtime
+            })
+            .max()
+    }
+}
+
+/// Touch `last_scanned_at` on the songs read from a file if it is indexed as it is on disk:
+/// same size and modification time, and partial hash when `partial_hash` is set, with no
+/// lyrics, CUE sheet or cover art changed since, by this version of the scanner. Returns
+/// false when the file needs reading.
+pub async fn touch_if_unchanged(
+    path: &std::path::Path,
+    partial_hash: bool,
+    cover_art_filenames: &[String],
+    sidecar_times: &SidecarTimes,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let Some(path_str) = path.to_str() else {
+        return Ok(false);
+    };
+    let rows: Vec<(
+        Option<i64>,
+        Option<i64>,
+        Option<String>,
+        Option<OffsetDateTime>,
+        i32,
+    )> = sqlx::query_as(
+        r#"
+            SELECT audio_hash_size, audio_hash_mtime_ns, partial_hash, last_scanned_at, scanner_version
+            FROM song WHERE path = $1
+            "#,
+    )
+    .bind(path_str)
+    .fetch_all(pool)
+    .await?;
+    if rows.is_empty() {
+        return Ok(false);
+    }
+
+    let (size, mtime) = source_file_signature(path)?;
+    let sidecars = sidecar_times.newest(path, cover_art_filenames).unwrap_or(0) as i128;
+    let unchanged = rows.iter().all(|(s, m, _, scanned, version)| {
+        *s == Some(size as i64)
+            && *m == Some(mtime)
+            && scanned.map_or(false, |t| sidecars <= t.unix_timestamp_nanos())
+            && *version == SCANNER_VERSION
+    });
+    if !unchanged {
+        return Ok(false);
+    }
+    if partial_hash {
+        let hash = partial_file_hash(path)?;
+        if rows
+            .iter()
+            .any(|(_, _, h, _, _)| h.as_deref() != Some(hash.as_str()))
+        {
+            return Ok(false);
+        }
+    }
+
+    sqlx::query("UPDATE song SET last_scanned_at = now() WHERE path = $1")
+        .bind(path_str)
+        .execute(pool)
+        .await?;
+    Ok(true)
+}
+
+/// Remove albums with no songs and artists with no albums or songs. Waits for songs being
+/// indexed, whose album and artists exist before the song does.
 pub async fn cleanup_orphans(pool: &sqlx::Pool<Postgres>) -> anyhow::Result<()> {
+    let _indexing = INDEXING.write().await;
     sqlx::query!("DELETE FROM album WHERE id NOT IN (SELECT DISTINCT album FROM song)")
         .execute(pool)
         .await?;
 
-    sqlx::query!(
+    sqlx::query(
         r#"
         DELETE FROM artist
         WHERE id NOT IN (SELECT DISTINCT artist FROM album)
           AND id NOT IN (SELECT DISTINCT album_artist FROM song)
           AND id NOT IN (SELECT DISTINCT artist FROM song_artist)
-        "#
+          AND id NOT IN (SELECT DISTINCT artist FROM song_credit)
+        "#,
     )
     .execute(pool)
     .await?;
@@ -883,6 +2269,26 @@ pub async fn cleanup_orphans(pool: &sqlx::Pool<Postgres>) -> anyhow::Result<()>
         .execute(pool)
         .await?;
 
+    sqlx::query("DELETE FROM work WHERE id NOT IN (SELECT work FROM song WHERE work IS NOT NULL)")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "DELETE FROM composer WHERE id NOT IN (SELECT composer FROM work WHERE composer IS NOT NULL)",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM disc_art
+        WHERE NOT EXISTS (
+          SELECT 1 FROM song WHERE song.album = disc_art.album AND COALESCE(song.disc, 1) = disc_art.disc
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
     Ok(())
 }
 
@@ -904,34 +2310,9 @@ pub async fn delete_stale_songs(
     }
 
     info!("pruning {} stale song(s)", stale_ids.len());
-
-    sqlx::query!(
-        "UPDATE playlist_item SET prev_song_id = NULL WHERE prev_song_id IN (SELECT id FROM playlist_item WHERE song_id = ANY($1))",
-        &stale_ids
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query!(
-        "UPDATE playlist_item SET next_song_id = NULL WHERE next_song_id IN (SELECT id FROM playlist_item WHERE song_id = ANY($1))",
-        &stale_ids
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query!(
-        "DELETE FROM favorites WHERE favoritable_type = 'song' AND favoritable_id = ANY($1)",
-        &stale_ids
-    )
-    .execute(pool)
-    .await?;
-
+    delete_songs(&stale_ids, pool).await?;
     let count = stale_ids.len() as u64;
 
-    sqlx::query!("DELETE FROM song WHERE id = ANY($1)", &stale_ids)
-        .execute(pool)
-        .await?;
-
     if let Err(e) = cleanup_orphans(pool).await {
         warn!("orphan cleanup after stale prune failed: {}", e);
     }
@@ -939,6 +2320,49 @@ pub async fn delete_stale_songs(
     Ok(count)
 }
 
+/// When the songs read from a file were last indexed, if there are any.
+pub async fn last_scanned_at(
+    path: &std::path::Path,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<Option<OffsetDateTime>> {
+    let scanned = sqlx::query_scalar("SELECT MAX(last_scanned_at) FROM song WHERE path = $1")
+        .bind(path.to_str())
+        .fetch_one(pool)
+        .await?;
+    Ok(scanned)
+}
+
+/// Add a row to the `server` table for a scan that just started, returning its id.
+pub async fn record_scan_start(pool: &sqlx::Pool<Postgres>) -> anyhow::Result<i32> {
+    let id = sqlx::query_scalar("INSERT INTO server (scan_start) VALUES (now()) RETURNING id")
+        .fetch_one(pool)
+        .await?;
+    Ok(id)
+}
+
+/// Finish a scan's `server` row with its duration and the library's size, in songs.
+pub async fn record_scan_end(
+    id: i32,
+    seconds: i32,
+    pool: &sqlx::Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE server SET
+          scan_end = now(), last_scan = now(), seconds = $2,
+          albums = (SELECT COUNT(*) FROM album),
+          artists = (SELECT COUNT(*) FROM artist),
+          size = (SELECT COUNT(*) FROM song)
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(seconds)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
 /// Converts to webp and saves an image to disk under its SHAKE128 hash
 async fn save_image(bytes: Vec<u8>) -> anyhow::Result<String> {
     // convert to webp via image crate
@@ -983,3 +2407,111 @@ async fn cache_external_image(url: &str) -> anyhow::Result<String> {
     let bytes = res.bytes().await?.to_vec();
     save_image(bytes).await
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use super::*;
+
+    /// An empty directory of its own for a test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("maki-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn set_mtime(path: &std::path::Path, time: SystemTime) {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test]
+    fn partial_hash_covers_head_and_tail_only() {
+        let dir = test_dir("partial-hash");
+        let path = dir.join("song.flac");
+        let mut data = vec![0u8; 256 * 1024];
+        std::fs::write(&path, &data).unwrap();
+        let original = partial_file_hash(&path).unwrap();
+
+        data[128 * 1024] = 1;
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(partial_file_hash(&path).unwrap(), original);
+
+        data[10] = 1;
+        std::fs::write(&path, &data).unwrap();
+        let head = partial_file_hash(&path).unwrap();
+        assert_ne!(head, original);
+
+        let last = data.len() - 1;
+        data[last] = 1;
+        std::fs::write(&path, &data).unwrap();
+        assert_ne!(partial_file_hash(&path).unwrap(), head);
+
+        // shorter than two chunks, the tail is whatever follows the head
+        std::fs::write(&path, &data[..100 * 1024]).unwrap();
+        let short = partial_file_hash(&path).unwrap();
+        data[99 * 1024] = 2;
+        std::fs::write(&path, &data[..100 * 1024]).unwrap();
+        assert_ne!(partial_file_hash(&path).unwrap(), short);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn sidecar_mtime_follows_lyrics_cue_and_art() {
+        let dir = test_dir("sidecar-mtime");
+        let names = vec!["cover".to_string()];
+        let song = dir.join("01 Song.flac");
+        std::fs::write(&song, b"").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(3600);
+        let mtime = |t: SystemTime| t.duration_since(UNIX_EPOCH).unwrap().as_nanos() as i64;
+
+        let notes = dir.join("notes.txt");
+        std::fs::write(&notes, b"").unwrap();
+        set_mtime(&notes, later + Duration::from_secs(1000));
+        let scan = SidecarTimes::default();
+        let base = scan.newest(&song, &names).unwrap();
+        assert!(base < mtime(later));
+
+        for (i, name) in ["01 Song.lrc", "album.cue", "cover.jpg"].iter().enumerate() {
+            let sidecar = dir.join(name);
+            std::fs::write(&sidecar, b"").unwrap();
+            let t = later + Duration::from_secs(i as u64);
+            set_mtime(&sidecar, t);
+            let times = SidecarTimes::default();
+            assert_eq!(times.newest(&song, &names), Some(mtime(t)), "{}", name);
+        }
+        // a scan lists each directory once
+        assert_eq!(scan.newest(&song, &names), Some(base));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn sidecar_mtime_follows_album_art_outside_disc_folder() {
+        let dir = test_dir("sidecar-mtime-disc");
+        let names = vec!["cover".to_string()];
+        std::fs::create_dir(dir.join("CD1")).unwrap();
+        let song = dir.join("CD1").join("01 Song.flac");
+        std::fs::write(&song, b"").unwrap();
+        let cover = dir.join("cover.jpg");
+        std::fs::write(&cover, b"").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(3600);
+        set_mtime(&cover, later);
+        assert_eq!(
+            SidecarTimes::default().newest(&song, &names),
+            Some(later.duration_since(UNIX_EPOCH).unwrap().as_nanos() as i64)
+        );
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
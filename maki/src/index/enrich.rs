@@ -0,0 +1,193 @@
+//! Work left out of scanning: MusicBrainz IDs and artist credits for songs, bios, pictures
+//! and aliases for artists, release groups and cover art for albums, and loudness for songs
+//! without ReplayGain tags. It runs in the background after a scan, so indexing only waits
+//! on reading tags and the database.
+
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use sqlx::postgres::Postgres;
+use tracing::{error, info};
+
+use super::db;
+use crate::{config::Config, metadata};
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Rows looked up per candidate query.
+const BATCH: i64 = 100;
+
+/// Start an enrichment pass in the background. A pass already running picks up whatever
+/// was indexed meanwhile with one more pass once it finishes.
+pub fn enqueue(pool: sqlx::Pool<Postgres>, cfg: Config) {
+    PENDING.store(true, Ordering::SeqCst);
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            PENDING.store(false, Ordering::SeqCst);
+            if let Err(e) = run(&pool, &cfg).await {
+                error!(target: "enrich", "enrichment failed: {}", e);
+            }
+            RUNNING.store(false, Ordering::SeqCst);
+            if !PENDING.load(Ordering::SeqCst) || RUNNING.swap(true, Ordering::SeqCst) {
+                break;
+            }
+        }
+    });
+}
+
+async fn run(pool: &sqlx::Pool<Postgres>, cfg: &Config) -> anyhow::Result<()> {
+    // songs first, a changed artist credit rescans the file and may add artists
+    let mut rescan: Vec<PathBuf> = Vec::new();
+    let songs = for_each_candidate(
+        r#"
+        SELECT id FROM song
+        WHERE id > $1 AND NOT mbid_searched
+          AND (mbid IS NULL OR NOT EXISTS (SELECT 1 FROM mb_artist_credit WHERE recording = song.mbid))
+        ORDER BY id LIMIT $2
+        "#,
+        pool,
+        |id| async move {
+            match db::enrich_song(id, pool).await {
+                Ok(true) => Some(id),
+                Ok(false) => None,
+                Err(e) => {
+                    error!(target: "enrich", "failed to enrich song {}: {}", id, e);
+                    None
+                }
+            }
+        },
+    )
+    .await?;
+    for id in songs {
+        let path: Option<String> = sqlx::query_scalar("SELECT path FROM song WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+        if let Some(path) = path.map(PathBuf::from) {
+            if !rescan.contains(&path) {
+                rescan.push(path);
+            }
+        }
+    }
+    for path in &rescan {
+        info!(target: "enrich", "artist credit changed, rescanning {}", path.display());
+        metadata::scan_file(path, pool.clone(), false, cfg).await;
+    }
+    if !rescan.is_empty() {
+        db::cleanup_orphans(pool).await?;
+    }
+
+    let artists = for_each_candidate(
+        r#"
+        SELECT id FROM artist
+        WHERE id > $1
+          AND (enriched_at IS NULL OR (aliases_checked_at IS NULL AND mbid IS NOT NULL)
+               OR picture LIKE 'http%')
+        ORDER BY id LIMIT $2
+        "#,
+        pool,
+        |id| async move {
+            match db::enrich_artist(id, pool).await {
+                Ok(()) => Some(id),
+                Err(e) => {
+                    error!(target: "enrich", "failed to enrich artist {}: {}", id, e);
+                    None
+                }
+            }
+        },
+    )
+    .await?;
+
+    let albums = for_each_candidate(
+        r#"
+        SELECT id FROM album
+        WHERE id > $1
+          AND (NOT release_checked
+               OR (NOT remote_art_checked AND NOT EXISTS (SELECT 1 FROM album_art WHERE album_art.album = album.id)))
+        ORDER BY id LIMIT $2
+        "#,
+        pool,
+        |id| async move {
+            match db::enrich_album(id, pool).await {
+                Ok(()) => Some(id),
+                Err(e) => {
+                    error!(target: "enrich", "failed to enrich album {}: {}", id, e);
+                    None
+                }
+            }
+        },
+    )
+    .await?;
+
+    // last, a full decode per song is slower than any lookup
+    let measured = if cfg.replaygain_compute {
+        for_each_candidate(
+            r#"
+            SELECT id FROM song
+            WHERE id > $1 AND replaygain_track_gain IS NULL AND NOT replaygain_failed
+            ORDER BY id LIMIT $2
+            "#,
+            pool,
+            |id| async move {
+                match db::measure_replaygain(id, pool).await {
+                    Ok(()) => Some(id),
+                    Err(e) => {
+                        error!(target: "enrich", "failed to measure song {}: {}", id, e);
+                        None
+                    }
+                }
+            },
+        )
+        .await?
+    } else {
+        Vec::new()
+    };
+
+    if !rescan.is_empty() || !artists.is_empty() || !albums.is_empty() || !measured.is_empty() {
+        info!(
+            target: "enrich",
+            "enriched {} artist(s) and {} album(s), rescanned {} file(s), measured {} song(s)",
+            artists.len(),
+            albums.len(),
+            rescan.len(),
+            measured.len()
+        );
+    }
+    Ok(())
+}
+
+/// Page through the ids `query` selects after a cursor (`$1`, with `$2` as the limit) and
+/// call `f` on each, collecting what it returns. Each id is visited once per pass, so rows
+/// a lookup leaves as candidates wait for the next one.
+async fn for_each_candidate<F, Fut>(
+    query: &str,
+    pool: &sqlx::Pool<Postgres>,
+    f: F,
+) -> anyhow::Result<Vec<i32>>
+where
+    F: Fn(i32) -> Fut,
+    Fut: std::future::Future<Output = Option<i32>>,
+{
+    let mut done = Vec::new();
+    let mut cursor = 0;
+    loop {
+        let ids: Vec<i32> = sqlx::query_scalar(query)
+            .bind(cursor)
+            .bind(BATCH)
+            .fetch_all(pool)
+            .await?;
+        let Some(&last) = ids.last() else {
+            return Ok(done);
+        };
+        for id in ids {
+            done.extend(f(id).await);
+        }
+        cursor = last;
+    }
+}
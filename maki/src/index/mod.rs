@@ -1,7 +1,9 @@
 pub mod db;
+pub mod enrich;
+pub mod progress;
 
 use futures::{
-    channel::mpsc::{channel, Receiver},
+    channel::mpsc::{channel, Receiver, Sender},
     SinkExt, StreamExt,
 };
 
@@ -9,11 +11,16 @@ use jwalk::WalkDir;
 use notify::event::EventKind;
 use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
 use sqlx::postgres::Postgres;
-use std::{path::Path, thread, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 use time::OffsetDateTime;
 use tracing::{error, info};
 
 use crate::{analysis, config::Config, metadata};
+use progress::ScanJob;
 
 pub async fn start<P: AsRef<Path>>(
     path: P,
@@ -30,35 +37,201 @@ pub async fn start<P: AsRef<Path>>(
         );
     }
     info!(target: "index", "scanning folder {:?}", &pathstr);
-    scan(&path, pool.clone(), dry_run, cfg).await;
+    scan(&path, pool.clone(), dry_run, false, cfg).await;
     info!(target: "index", "watching folder {:?}", pathstr);
     if let Err(e) = watch(path, pool, dry_run, cfg).await {
         error!(target: "index", "error: {:?}", e)
     }
 }
 
+/// Index every file under `path`. Files indexed as they are on disk are skipped unless
+/// `force` is set. Does nothing if a scan is already running.
 pub async fn scan<P: AsRef<Path>>(
     path: P,
     pool: sqlx::Pool<Postgres>,
     dry_run: bool,
+    force: bool,
     cfg: &Config,
 ) {
-    thread::sleep(Duration::from_millis(250));
-    let scan_start = OffsetDateTime::now_utc();
-    for entry in WalkDir::new(path).sort(true) {
-        let ent = &entry.unwrap();
-        if ent.path().is_file() {
-            metadata::scan_file(&ent.path(), pool.clone(), dry_run, cfg).await;
+    match ScanJob::begin(force) {
+        Some(job) => scan_with(job, path, pool, dry_run, cfg).await,
+        None => info!(target: "index", "a scan is already running, not starting another"),
+    }
+}
+
+/// What became of one file in a scan.
+enum Outcome {
+    Added,
+    Updated,
+    Skipped,
+    Failed,
+}
+
+/// Run a scan started with [`ScanJob::begin`], reporting progress through it.
+pub async fn scan_with<P: AsRef<Path>>(
+    job: ScanJob,
+    path: P,
+    pool: sqlx::Pool<Postgres>,
+    dry_run: bool,
+    cfg: &Config,
+) {
+    let server_row = if dry_run {
+        None
+    } else {
+        // picks up edits to the genre alias map since the last scan
+        match db::canonicalize_genres(&cfg.genre_aliases, &cfg.genre_parents, &pool).await {
+            Ok(n) if n > 0 => info!(target: "index", "merged {} duplicate genre(s)", n),
+            Ok(_) => {}
+            Err(e) => error!(target: "index", "genre canonicalization failed: {}", e),
+        }
+        match db::record_scan_start(&pool).await {
+            Ok(id) => Some(id),
+            Err(e) => {
+                error!(target: "index", "failed to record scan start: {}", e);
+                None
+            }
         }
+    };
+    let scan_start = OffsetDateTime::now_utc();
+
+    // the walk reads every file to sniff its type, so it runs off the runtime and feeds
+    // files to the workers as it finds them
+    let job = Arc::new(job);
+    let (tx, files) = channel(WALK_AHEAD);
+    let walker = {
+        let root = path.as_ref().to_path_buf();
+        let job = job.clone();
+        tokio::task::spawn_blocking(move || walk(&root, tx, &job))
+    };
+
+    // files are read and indexed scan_workers at a time; add_song serializes each album
+    let shared = Arc::new(cfg.clone());
+    let sidecar_times = Arc::new(db::SidecarTimes::default());
+    files
+        .map(|file| {
+            let pool = pool.clone();
+            let cfg = shared.clone();
+            let sidecar_times = sidecar_times.clone();
+            let job = job.clone();
+            tokio::spawn(async move {
+                job.update(|progress| progress.current_path = Some(file.display().to_string()));
+                let outcome =
+                    index_file(&file, &pool, dry_run, job.force(), &cfg, &sidecar_times).await;
+                job.update(|progress| {
+                    progress.seen += 1;
+                    match outcome {
+                        Some(Outcome::Added) => progress.added += 1,
+                        Some(Outcome::Updated) => progress.updated += 1,
+                        Some(Outcome::Skipped) => progress.skipped += 1,
+                        Some(Outcome::Failed) => progress.failed += 1,
+                        None => {}
+                    }
+                });
+            })
+        })
+        .buffer_unordered(cfg.scan_workers.max(1))
+        .for_each(|res| async move {
+            if let Err(e) = res {
+                error!(target: "index", "scan task failed: {}", e);
+            }
+        })
+        .await;
+    if let Err(e) = walker.await {
+        error!(target: "index", "library walk failed: {}", e);
     }
+
     if !dry_run {
         match db::delete_stale_songs(scan_start, &pool).await {
-            Ok(n) if n > 0 => info!(target: "index", "pruned {} stale song(s)", n),
-            Ok(_) => {}
+            Ok(n) => job.update(|progress| progress.pruned = n),
             Err(e) => error!(target: "index", "stale prune failed: {}", e),
         }
+        if let Some(id) = server_row {
+            let seconds = (OffsetDateTime::now_utc() - scan_start).whole_seconds() as i32;
+            if let Err(e) = db::record_scan_end(id, seconds, &pool).await {
+                error!(target: "index", "failed to record scan end: {}", e);
+            }
+        }
         analysis::enqueue(pool.clone(), cfg.clone(), false);
+        enrich::enqueue(pool.clone(), cfg.clone());
+    }
+    let done = progress::current();
+    info!(
+        target: "index",
+        "scanned {} file(s): {} added, {} updated, {} unchanged, {} failed, {} pruned",
+        done.seen,
+        done.added,
+        done.updated,
+        done.skipped,
+        done.failed,
+        done.pruned
+    );
+}
+
+/// How many files the walk may find ahead of the workers.
+const WALK_AHEAD: usize = 1024;
+
+/// Send every audio file under `root` to the scan, in path order, counting them into its
+/// total as they're found.
+fn walk(root: &Path, mut tx: Sender<PathBuf>, job: &ScanJob) {
+    for entry in WalkDir::new(root).sort(true) {
+        let path = match entry {
+            Ok(ent) => ent.path(),
+            Err(e) => {
+                error!(target: "index", "failed to read directory entry: {}", e);
+                continue;
+            }
+        };
+        if !path.is_file() || metadata::get_filetype(&path).is_none() {
+            continue;
+        }
+        job.update(|progress| progress.total += 1);
+        if futures::executor::block_on(tx.send(path)).is_err() {
+            break;
+        }
+    }
+    job.update(|progress| progress.walking = false);
+}
+
+/// Index one audio file, skipping it if it's unchanged and `force` isn't set. None for a
+/// dry run, which only reads the file.
+async fn index_file(
+    file: &PathBuf,
+    pool: &sqlx::Pool<Postgres>,
+    dry_run: bool,
+    force: bool,
+    cfg: &Config,
+    sidecar_times: &db::SidecarTimes,
+) -> Option<Outcome> {
+    if dry_run {
+        metadata::scan_file(file, pool.clone(), dry_run, cfg).await;
+        return None;
+    }
+    if !force {
+        match db::touch_if_unchanged(
+            file,
+            cfg.scan_partial_hash,
+            &cfg.cover_art_filenames,
+            sidecar_times,
+            pool,
+        )
+        .await
+        {
+            Ok(true) => return Some(Outcome::Skipped),
+            Ok(false) => {}
+            Err(e) => error!(target: "index", "failed to check {}: {}", file.display(), e),
+        }
     }
+
+    // a file that fails to index keeps its old stamp, or has none if it's new
+    let before = db::last_scanned_at(file, pool).await.ok().flatten();
+    metadata::scan_file(file, pool.clone(), dry_run, cfg).await;
+    let after = db::last_scanned_at(file, pool).await.ok().flatten();
+    Some(match (before, after) {
+        (_, None) => Outcome::Failed,
+        (Some(before), Some(after)) if after <= before => Outcome::Failed,
+        (None, Some(_)) => Outcome::Added,
+        (Some(_), Some(_)) => Outcome::Updated,
+    })
 }
 
 fn async_watcher() -> notify::Result<(RecommendedWatcher, Receiver<notify::Result<Event>>)> {
@@ -104,19 +277,85 @@ pub async fn watch<P: AsRef<Path>>(
     Ok(())
 }
 
+/// Audio files sharing a stem with a sidecar file, e.g. `01 Song.flac` for `01 Song.lrc`.
+fn audio_for_sidecar(sidecar: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(stem)) = (sidecar.parent(), sidecar.file_stem()) else {
+        return Vec::new();
+    };
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p != sidecar && p.file_stem() == Some(stem) && metadata::get_filetype(p).is_some()
+        })
+        .collect()
+}
+
+fn is_lyrics_sidecar(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("lrc"))
+        .unwrap_or(false)
+}
+
 async fn parse_event(
     event: notify::event::Event,
     pool: sqlx::Pool<Postgres>,
     dry_run: bool,
     cfg: &Config,
 ) {
+    // cover images are not songs either, refresh the art of the albums next to them
+    if metadata::art::is_sidecar_name(&event.paths[0], &cfg.cover_art_filenames) {
+        if let EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) = event.kind {
+            let image = &event.paths[0];
+            if dry_run {
+                info!(target: "index-watcher", "dry-run: would refresh art for {}", image.display());
+            } else if let Err(e) =
+                db::refresh_sidecar_art(image, &cfg.cover_art_filenames, &pool).await
+            {
+                error!(target: "index-watcher", "failed to refresh art for {}: {}", image.display(), e);
+            }
+        }
+        return;
+    }
+
+    // lyrics sidecars are not songs themselves, rescan the audio they belong to
+    if is_lyrics_sidecar(&event.paths[0]) {
+        if let EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) = event.kind {
+            for audio in audio_for_sidecar(&event.paths[0]) {
+                info!(target: "index-watcher", "lyrics changed, rescanning {}", audio.display());
+                metadata::scan_file(&audio, pool.clone(), dry_run, cfg).await;
+            }
+        }
+        return;
+    }
+
+    // cue sheets describe audio next to them, rescan it so virtual tracks follow the sheet
+    if metadata::cue::is_cue(&event.paths[0]) {
+        if let EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) = event.kind {
+            let cue = &event.paths[0];
+            let audio = match metadata::cue::read(cue) {
+                Ok(sheet) => sheet.audio_paths(cue),
+                Err(_) => audio_for_sidecar(cue),
+            };
+            for audio in audio {
+                info!(target: "index-watcher", "cue sheet changed, rescanning {}", audio.display());
+                metadata::scan_file(&audio, pool.clone(), dry_run, cfg).await;
+            }
+        }
+        return;
+    }
+
     match event.kind {
         // we sleep here until windows stops messing around with our file smh!
         EventKind::Create(_) => {
-            thread::sleep(Duration::from_millis(75));
+            tokio::time::sleep(Duration::from_millis(75)).await;
             metadata::scan_file(&event.paths[0], pool.clone(), dry_run, cfg).await;
             if !dry_run {
-                analysis::enqueue(pool, cfg.clone(), false);
+                analysis::enqueue(pool.clone(), cfg.clone(), false);
+                enrich::enqueue(pool, cfg.clone());
             }
         }
         EventKind::Remove(_) => {
@@ -139,7 +378,8 @@ async fn parse_event(
             }
             metadata::scan_file(&event.paths[0], pool.clone(), dry_run, cfg).await;
             if !dry_run {
-                analysis::enqueue(pool, cfg.clone(), false);
+                analysis::enqueue(pool.clone(), cfg.clone(), false);
+                enrich::enqueue(pool, cfg.clone());
             }
         }
         EventKind::Access(_) => (),
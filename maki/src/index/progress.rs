@@ -0,0 +1,167 @@
+//! Progress of the library scan, for the admin API. Only one scan runs at a time.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::Instant,
+};
+
+use serde::Serialize;
+use time::OffsetDateTime;
+use tokio::sync::watch;
+use utoipa::ToSchema;
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static PROGRESS: OnceLock<watch::Sender<ScanProgress>> = OnceLock::new();
+
+/// The running or last finished scan.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ScanProgress {
+    pub running: bool,
+    /// Unchanged files are read again too
+    pub force: bool,
+    #[serde(with = "time::serde::timestamp::option")]
+    pub started_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::timestamp::option")]
+    pub finished_at: Option<OffsetDateTime>,
+    /// Still walking the library; total keeps growing until this is false
+    pub walking: bool,
+    /// Audio files found in the library
+    pub total: u64,
+    /// Audio files done so far
+    pub seen: u64,
+    pub added: u64,
+    pub updated: u64,
+    /// Unchanged since they were last indexed
+    pub skipped: u64,
+    /// Couldn't be read or indexed
+    pub failed: u64,
+    /// Songs removed because their file is gone
+    pub pruned: u64,
+    /// Most recently started file
+    pub current_path: Option<String>,
+    /// Estimated seconds left, at the pace so far. None until the walk is done
+    pub eta_seconds: Option<u64>,
+}
+
+fn sender() -> &'static watch::Sender<ScanProgress> {
+    PROGRESS.get_or_init(|| watch::channel(ScanProgress::default()).0)
+}
+
+pub fn current() -> ScanProgress {
+    sender().borrow().clone()
+}
+
+/// Receive every progress update from now on.
+pub fn subscribe() -> watch::Receiver<ScanProgress> {
+    sender().subscribe()
+}
+
+/// The running scan. Dropping it marks the scan finished.
+pub struct ScanJob {
+    force: bool,
+    started: Instant,
+}
+
+impl ScanJob {
+    /// Start a scan, or None if one is already running.
+    pub fn begin(force: bool) -> Option<ScanJob> {
+        if RUNNING.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+        sender().send_replace(ScanProgress {
+            running: true,
+            walking: true,
+            force,
+            started_at: Some(OffsetDateTime::now_utc()),
+            ..Default::default()
+        });
+        Some(ScanJob {
+            force,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn force(&self) -> bool {
+        self.force
+    }
+
+    pub fn update<F: FnOnce(&mut ScanProgress)>(&self, f: F) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        sender().send_modify(|progress| {
+            f(progress);
+            progress.eta_seconds = if progress.walking {
+                None
+            } else {
+                eta(elapsed, progress.seen, progress.total)
+            };
+        });
+    }
+}
+
+/// Seconds left for the files not seen yet, at the pace of the ones seen so far.
+fn eta(elapsed: f64, seen: u64, total: u64) -> Option<u64> {
+    if seen == 0 {
+        return None;
+    }
+    let left = total.saturating_sub(seen) as f64;
+    Some((elapsed / seen as f64 * left).round() as u64)
+}
+
+impl Drop for ScanJob {
+    fn drop(&mut self) {
+        sender().send_modify(|progress| {
+            progress.running = false;
+            progress.walking = false;
+            progress.finished_at = Some(OffsetDateTime::now_utc());
+            progress.current_path = None;
+            progress.eta_seconds = None;
+        });
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_extrapolates_pace_so_far() {
+        assert_eq!(eta(10.0, 0, 100), None);
+        assert_eq!(eta(10.0, 10, 100), Some(90));
+        assert_eq!(eta(3.0, 2, 3), Some(2));
+        assert_eq!(eta(10.0, 100, 100), Some(0));
+        // a count past the total does not wrap around
+        assert_eq!(eta(10.0, 120, 100), Some(0));
+    }
+
+    #[test]
+    fn only_one_scan_runs_at_a_time() {
+        let job = ScanJob::begin(true).expect("no scan running");
+        assert!(current().running);
+        assert!(current().force);
+        assert!(ScanJob::begin(false).is_none());
+
+        // no estimate while the walk is still finding files
+        job.update(|p| {
+            p.total = 4;
+            p.seen = 1;
+        });
+        assert_eq!(current().seen, 1);
+        assert_eq!(current().eta_seconds, None);
+        job.update(|p| p.walking = false);
+        assert!(current().eta_seconds.is_some());
+
+        drop(job);
+        let progress = current();
+        assert!(!progress.running);
+        assert!(progress.finished_at.is_some());
+        assert_eq!(progress.eta_seconds, None);
+
+        let job = ScanJob::begin(false).expect("previous scan finished");
+        assert!(!job.force());
+        assert_eq!(current().seen, 0);
+    }
+}
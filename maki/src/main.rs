@@ -209,10 +209,7 @@ async fn serve(pool: Pool<Postgres>, cfg: config::Config) -> anyhow::Result<()>
 }
 
 async fn cmd_tags(path: &std::path::Path) -> anyhow::Result<()> {
-    use metadata::{
-        formats::{aiff::scan_aiff, flac::scan_flac, mp3::scan_mp3, wav::scan_wav},
-        get_filetype, AudioFormat,
-    };
+    use metadata::{get_filetype, scan_format};
 
     let path_buf = path.to_path_buf();
     let format = get_filetype(path)
@@ -229,24 +226,47 @@ async fn cmd_tags(path: &std::path::Path) -> anyhow::Result<()> {
         mix_analysis_url: None,
         mix_analysis_timeout_seconds: 300,
         mix_analysis_max_pcm_bytes: 256 * 1024 * 1024,
+        replaygain_compute: false,
+        cover_art_filenames: vec![],
+        various_artists_name: String::new(),
+        genre_aliases: Default::default(),
+        genre_parents: Default::default(),
+        scan_workers: 1,
+        scan_partial_hash: false,
     };
 
-    let meta = match format {
-        AudioFormat::Flac => scan_flac(&path_buf, &cfg).await?,
-        AudioFormat::Mp3 => scan_mp3(&path_buf, &cfg).await?,
-        AudioFormat::Wav => scan_wav(&path_buf, &cfg).await?,
-        AudioFormat::Aiff => scan_aiff(&path_buf, &cfg).await?,
-    };
+    let meta = scan_format(format, &path_buf, &cfg).await?;
 
     println!("file:          {}", meta.path.display());
     println!("title:         {}", meta.name);
     println!("track:         {}", meta.number);
+    if let Some(total) = meta.track_total {
+        println!("track total:   {}", total);
+    }
     if let Some(disc) = meta.disc {
         println!("disc:          {}", disc);
     }
+    if let Some(total) = meta.disc_total {
+        println!("disc total:    {}", total);
+    }
+    if let Some(subtitle) = &meta.disc_subtitle {
+        println!("disc subtitle: {}", subtitle);
+    }
     println!("album:         {}", meta.album);
     println!("album artist:  {}", meta.album_artist);
     println!("artists:       {}", meta.artists.join(", "));
+    if meta.compilation {
+        println!("compilation:   yes");
+    }
+    if let Some(explicit) = meta.explicit {
+        println!("advisory:      {}", if explicit { "explicit" } else { "clean" });
+    }
+    if let Some(sort) = &meta.artist_sort {
+        println!("artist sort:   {}", sort);
+    }
+    if let Some(sort) = &meta.album_artist_sort {
+        println!("alb.art. sort: {}", sort);
+    }
     if let Some(sort) = &meta.album_sort {
         println!("album sort:    {}", sort);
     }
@@ -256,7 +276,7 @@ async fn cmd_tags(path: &std::path::Path) -> anyhow::Result<()> {
     if let Some(genres) = &meta.genre {
         println!("genre:         {}", genres.join(", "));
     }
-    println!("duration:      {}s", meta.duration);
+    println!("duration:      {}s ({} ms)", meta.duration, meta.duration_ms);
     println!("lossless:      {}", meta.lossless);
     if let Some(sr) = meta.sample_rate {
         println!("sample rate:   {} Hz", sr);
@@ -267,6 +287,31 @@ async fn cmd_tags(path: &std::path::Path) -> anyhow::Result<()> {
     if let Some(ch) = meta.num_channels {
         println!("channels:      {}", ch);
     }
+    if let Some(br) = meta.bitrate {
+        println!("bitrate:       {} kbps", br);
+    }
+    if let Some(total) = meta.total_samples {
+        println!("samples:       {}", total);
+    }
+    if let Some(delay) = meta.encoder_delay {
+        println!("enc. delay:    {}", delay);
+    }
+    if let Some(padding) = meta.encoder_padding {
+        println!("enc. padding:  {}", padding);
+    }
+    let rg = meta.replay_gain;
+    if let Some(gain) = rg.track_gain {
+        println!("track gain:    {:+.2} dB", gain);
+    }
+    if let Some(peak) = rg.track_peak {
+        println!("track peak:    {:.6}", peak);
+    }
+    if let Some(gain) = rg.album_gain {
+        println!("album gain:    {:+.2} dB", gain);
+    }
+    if let Some(peak) = rg.album_peak {
+        println!("album peak:    {:.6}", peak);
+    }
     if let Some(id) = &meta.mbid_track {
         println!("mbid track:    {}", id);
     }
@@ -279,6 +324,32 @@ async fn cmd_tags(path: &std::path::Path) -> anyhow::Result<()> {
     if let Some(composer) = &meta.composer {
         println!("composer:      {}", composer);
     }
+    let classical = &meta.classical;
+    if let Some(work) = &classical.work {
+        println!("work:          {}", work);
+    }
+    if let Some(movement) = &classical.movement {
+        println!("movement:      {}", movement);
+    }
+    if let Some(n) = classical.movement_number {
+        match classical.movement_total {
+            Some(total) => println!("movement no.:  {}/{}", n, total),
+            None => println!("movement no.:  {}", n),
+        }
+    }
+    if let Some(conductor) = &classical.conductor {
+        println!("conductor:     {}", conductor);
+    }
+    if let Some(orchestra) = &classical.orchestra {
+        println!("orchestra:     {}", orchestra);
+    }
+    for credit in &meta.credits {
+        let role = format!("{}:", credit.role);
+        match &credit.detail {
+            Some(detail) => println!("{:<15}{} ({})", role, credit.name, detail),
+            None => println!("{:<15}{}", role, credit.name),
+        }
+    }
     if let Some(isrc) = &meta.isrc {
         println!("isrc:          {}", isrc);
     }
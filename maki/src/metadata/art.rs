@@ -0,0 +1,106 @@
+use std::path::{Path, PathBuf};
+
+/// Extensions we accept for sidecar cover art.
+const IMAGE_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "webp", "gif"];
+
+/// Whether `path` is an image whose stem is one of the configured sidecar names.
+pub fn is_sidecar_name(path: &Path, names: &[String]) -> bool {
+    let (Some(stem), Some(ext)) = (
+        path.file_stem().and_then(|s| s.to_str()),
+        path.extension().and_then(|e| e.to_str()),
+    ) else {
+        return false;
+    };
+    IMAGE_EXTENSIONS.iter().any(|i| i.eq_ignore_ascii_case(ext))
+        && names.iter().any(|n| n.eq_ignore_ascii_case(stem))
+}
+
+/// Directory names like `CD1`, `Disc 2` or `disk02` that sit inside an album folder.
+fn is_disc_folder(dir: &Path) -> bool {
+    let Some(name) = dir.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let name = name.to_lowercase();
+    ["cd", "disc", "disk"].iter().any(|prefix| {
+        name.strip_prefix(prefix)
+            .map(|rest| {
+                let rest = rest.trim_start_matches(|c: char| c == ' ' || c == '_' || c == '-');
+                !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit())
+            })
+            .unwrap_or(false)
+    })
+}
+
+/// Directories sidecar cover art for a song is looked up in: the song's own, then the
+/// album directory when the song sits in a disc folder.
+pub fn sidecar_dirs(song: &Path) -> Vec<&Path> {
+    let Some(dir) = song.parent() else {
+        return Vec::new();
+    };
+    let mut dirs = vec![dir];
+    if is_disc_folder(dir) {
+        dirs.extend(dir.parent());
+    }
+    dirs
+}
+
+/// Find sidecar cover art for a song. Names are tried in configured order, first in the
+/// song's directory, then in the album directory when the song sits in a disc folder.
+pub fn find_sidecar(song: &Path, names: &[String]) -> Option<PathBuf> {
+    sidecar_dirs(song)
+        .into_iter()
+        .find_map(|dir| find_in_dir(dir, names))
+}
+
+/// Find cover art for one disc of a multi-disc album. Only images inside the song's own
+/// disc folder count; art next to the disc folders belongs to the whole album.
+pub fn find_disc_sidecar(song: &Path, names: &[String]) -> Option<PathBuf> {
+    let dir = song.parent()?;
+    if !is_disc_folder(dir) {
+        return None;
+    }
+    find_in_dir(dir, names)
+}
+
+/// The first configured sidecar name with an image in `dir`.
+fn find_in_dir(dir: &Path, names: &[String]) -> Option<PathBuf> {
+    let images: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && is_sidecar_name(p, names))
+        .collect();
+    names.iter().find_map(|name| {
+        images
+            .iter()
+            .find(|p| {
+                p.file_stem()
+                    .and_then(|s| s.to_str())
+                    .map(|s| s.eq_ignore_ascii_case(name))
+                    .unwrap_or(false)
+            })
+            .cloned()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_configured_image_names() {
+        let names = vec!["cover".to_string(), "folder".to_string()];
+        assert!(is_sidecar_name(Path::new("/music/a/Cover.JPG"), &names));
+        assert!(is_sidecar_name(Path::new("/music/a/folder.png"), &names));
+        assert!(!is_sidecar_name(Path::new("/music/a/cover.txt"), &names));
+        assert!(!is_sidecar_name(Path::new("/music/a/back.jpg"), &names));
+    }
+
+    #[test]
+    fn detects_disc_folders() {
+        assert!(is_disc_folder(Path::new("/music/a/CD1")));
+        assert!(is_disc_folder(Path::new("/music/a/Disc 02")));
+        assert!(!is_disc_folder(Path::new("/music/a/cdr")));
+        assert!(!is_disc_folder(Path::new("/music/a/Discography")));
+    }
+}
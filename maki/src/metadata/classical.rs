@@ -0,0 +1,109 @@
+use crate::metadata::formats::{parse_index, parse_total};
+
+/// Classical work, movement and performer tags for a song.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Classical {
+    pub work: Option<String>,
+    pub movement: Option<String>,
+    pub movement_number: Option<u32>,
+    pub movement_total: Option<u32>,
+    pub conductor: Option<String>,
+    pub orchestra: Option<String>,
+}
+
+impl Classical {
+    /// Build from `WORK`/`MOVEMENTNAME` style fields, as found in Vorbis comments and
+    /// APEv2 items. `get` is called with upper-case keys.
+    pub fn from_fields<F: Fn(&str) -> Option<String>>(get: F) -> Self {
+        let text = |key: &str| {
+            get(key)
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let mut movement = text("MOVEMENTNAME");
+        let (mut movement_number, mut movement_total) = (None, None);
+        // MOVEMENT holds the number, but some taggers store the name there
+        if let Some(value) = text("MOVEMENT") {
+            match parse_index(&value) {
+                Some(n) => {
+                    movement_number = Some(n);
+                    movement_total = parse_total(&value);
+                }
+                None => movement = movement.or(Some(value)),
+            }
+        }
+        Classical {
+            work: text("WORK"),
+            movement,
+            movement_number: movement_number.filter(|&n| n > 0),
+            movement_total: text("MOVEMENTTOTAL")
+                .and_then(|t| t.parse().ok())
+                .or(movement_total)
+                .filter(|&n| n > 0),
+            conductor: text("CONDUCTOR"),
+            orchestra: text("ORCHESTRA"),
+        }
+    }
+}
+
+/// Read classical tags from ID3 frames. The work is in `TXXX:WORK` as written by
+/// MusicBrainz Picard, or in `TIT1` as written by iTunes.
+pub fn from_id3(tag: &id3::Tag) -> Classical {
+    let text = |id: &str| {
+        tag.text_for_frame_id(id)
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    let extended = |key: &str| {
+        tag.extended_texts()
+            .find(|t| t.description.eq_ignore_ascii_case(key))
+            .map(|t| t.value.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    let index = text("MVIN").unwrap_or_default();
+    Classical {
+        work: extended("WORK").or_else(|| text("TIT1")),
+        movement: text("MVNM"),
+        movement_number: parse_index(&index).filter(|&n| n > 0),
+        movement_total: parse_total(&index).filter(|&n| n > 0),
+        conductor: text("TPE3"),
+        orchestra: extended("ORCHESTRA"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn fields(pairs: &[(&str, &str)]) -> Classical {
+        let map: HashMap<&str, &str> = pairs.iter().cloned().collect();
+        Classical::from_fields(|key| map.get(key).map(|v| v.to_string()))
+    }
+
+    #[test]
+    fn reads_movement_number_and_total() {
+        let c = fields(&[
+            ("WORK", "Symphony No. 5 in C minor, Op. 67"),
+            ("MOVEMENTNAME", "Allegro con brio"),
+            ("MOVEMENT", "1"),
+            ("MOVEMENTTOTAL", "4"),
+        ]);
+        assert_eq!(c.work.as_deref(), Some("Symphony No. 5 in C minor, Op. 67"));
+        assert_eq!(c.movement.as_deref(), Some("Allegro con brio"));
+        assert_eq!((c.movement_number, c.movement_total), (Some(1), Some(4)));
+    }
+
+    #[test]
+    fn treats_a_movement_name_in_movement_as_the_name() {
+        let c = fields(&[("MOVEMENT", "Andante con moto")]);
+        assert_eq!(c.movement.as_deref(), Some("Andante con moto"));
+        assert_eq!(c.movement_number, None);
+    }
+
+    #[test]
+    fn reads_movement_total_from_movement() {
+        let c = fields(&[("MOVEMENT", "2/3"), ("MOVEMENTNAME", "Adagio")]);
+        assert_eq!((c.movement_number, c.movement_total), (Some(2), Some(3)));
+    }
+}
@@ -0,0 +1,166 @@
+use id3::frame::Content;
+
+/// Someone credited on a song besides its performing artists.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Credit {
+    /// Lower case role, e.g. performer, producer, engineer, mixer, remixer or lyricist
+    pub role: String,
+    pub name: String,
+    /// Instrument or vocal part, for performers
+    pub detail: Option<String>,
+}
+
+/// Vorbis comment, APEv2 and MP4 freeform fields holding credits, with their roles.
+const FIELD_ROLES: [(&str, &str); 8] = [
+    ("PERFORMER", "performer"),
+    ("PRODUCER", "producer"),
+    ("ENGINEER", "engineer"),
+    ("MIXER", "mixer"),
+    ("REMIXER", "remixer"),
+    ("LYRICIST", "lyricist"),
+    ("ARRANGER", "arranger"),
+    ("DJMIXER", "dj-mixer"),
+];
+
+fn push(credits: &mut Vec<Credit>, role: &str, name: &str, detail: Option<&str>) {
+    let name = name.trim();
+    if name.is_empty() {
+        return;
+    }
+    let credit = Credit {
+        role: role.to_string(),
+        name: name.to_string(),
+        detail: detail
+            .map(|d| d.trim().to_lowercase())
+            .filter(|d| !d.is_empty()),
+    };
+    if !credits.contains(&credit) {
+        credits.push(credit);
+    }
+}
+
+/// Split a `Name (instrument)` performer value into the name and instrument.
+fn split_performer(value: &str) -> (&str, Option<&str>) {
+    let value = value.trim();
+    match value.strip_suffix(')').and_then(|v| v.rsplit_once(" (")) {
+        Some((name, detail)) if !name.trim().is_empty() => (name, Some(detail)),
+        _ => (value, None),
+    }
+}
+
+/// Read credits from `PERFORMER`/`PRODUCER` style fields. `get` is called with upper-case
+/// keys and returns every value of the field.
+pub fn from_fields<F: Fn(&str) -> Option<Vec<String>>>(get: F) -> Vec<Credit> {
+    let mut credits = Vec::new();
+    for (key, role) in FIELD_ROLES {
+        for value in get(key).unwrap_or_default() {
+            if role == "performer" {
+                let (name, detail) = split_performer(&value);
+                push(&mut credits, role, name, detail);
+            } else {
+                push(&mut credits, role, &value, None);
+            }
+        }
+    }
+    credits
+}
+
+/// Role for an ID3 `TIPL` involvement, spelled as MusicBrainz Picard writes them.
+fn involvement_role(involvement: &str) -> String {
+    match involvement.trim().to_lowercase().as_str() {
+        "mix" => "mixer".to_string(),
+        "dj-mix" => "dj-mixer".to_string(),
+        role => role.to_string(),
+    }
+}
+
+/// Read credits from ID3 frames: `TIPL` (`IPLS` in v2.3) for production roles, `TMCL`
+/// for musicians by instrument, `TEXT` for lyricists and `TPE4` for remixers.
+pub fn from_id3(tag: &id3::Tag) -> Vec<Credit> {
+    let mut credits = Vec::new();
+    for frame in tag.frames() {
+        match (frame.id(), frame.content()) {
+            ("TIPL" | "IPLS", Content::InvolvedPeopleList(list)) => {
+                for item in &list.items {
+                    let role = involvement_role(&item.involvement);
+                    push(&mut credits, &role, &item.involvee, None);
+                }
+            }
+            ("TMCL", Content::InvolvedPeopleList(list)) => {
+                for item in &list.items {
+                    push(
+                        &mut credits,
+                        "performer",
+                        &item.involvee,
+                        Some(&item.involvement),
+                    );
+                }
+            }
+            ("TEXT", Content::Text(text)) => {
+                for name in text.split('\0') {
+                    push(&mut credits, "lyricist", name, None);
+                }
+            }
+            ("TPE4", Content::Text(text)) => {
+                for name in text.split('\0') {
+                    push(&mut credits, "remixer", name, None);
+                }
+            }
+            _ => {}
+        }
+    }
+    credits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn reads_performers_with_instruments() {
+        let fields: HashMap<&str, Vec<String>> = vec![
+            (
+                "PERFORMER",
+                vec![
+                    "Yo-Yo Ma (Cello)".to_string(),
+                    "Emanuel Ax (piano)".to_string(),
+                ],
+            ),
+            ("PRODUCER", vec!["Steven Epstein".to_string()]),
+        ]
+        .into_iter()
+        .collect();
+        let credits = from_fields(|key| fields.get(key).cloned());
+        assert_eq!(
+            credits[0],
+            Credit {
+                role: "performer".to_string(),
+                name: "Yo-Yo Ma".to_string(),
+                detail: Some("cello".to_string()),
+            }
+        );
+        assert_eq!(credits[1].detail.as_deref(), Some("piano"));
+        assert_eq!(credits[2].role, "producer");
+        assert_eq!(credits.len(), 3);
+    }
+
+    #[test]
+    fn keeps_names_without_an_instrument() {
+        assert_eq!(split_performer("Björk"), ("Björk", None));
+        assert_eq!(split_performer("(hed) p.e."), ("(hed) p.e.", None));
+    }
+
+    #[test]
+    fn drops_empty_and_duplicate_credits() {
+        let credits = from_fields(|key| match key {
+            "ENGINEER" => Some(vec![
+                "Bob Ludwig".to_string(),
+                " ".to_string(),
+                "Bob Ludwig".to_string(),
+            ]),
+            _ => None,
+        });
+        assert_eq!(credits.len(), 1);
+    }
+}
@@ -0,0 +1,431 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    helpers::split_artists,
+    metadata::{replaygain::ReplayGain, AudioMetadata},
+};
+
+/// Where a virtual track sits inside its source file.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CueSpan {
+    pub track: u32,
+    pub start_ms: u32,
+    /// `None` for the last track, which runs to the end of the file
+    pub end_ms: Option<u32>,
+}
+
+impl CueSpan {
+    /// ffmpeg input options that select this span, to be placed before `-i`.
+    pub fn input_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "-ss".to_string(),
+            format!("{:.3}", self.start_ms as f64 / 1000.0),
+        ];
+        if let Some(end) = self.end_ms {
+            args.push("-t".to_string());
+            args.push(format!(
+                "{:.3}",
+                end.saturating_sub(self.start_ms) as f64 / 1000.0
+            ));
+        }
+        args
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    /// `REM` comments, keyed by upper-cased name (GENRE, DATE, REPLAYGAIN_ALBUM_GAIN, ...)
+    pub rem: HashMap<String, String>,
+    pub files: Vec<CueFile>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CueFile {
+    pub name: String,
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    pub isrc: Option<String>,
+    pub rem: HashMap<String, String>,
+    /// INDEX 01, or INDEX 00 when a track has no INDEX 01
+    pub start_ms: u32,
+}
+
+/// Split a command's arguments, keeping quoted strings together.
+fn arguments(rest: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = rest.trim().chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            args.push(chars.by_ref().take_while(|&c| c != '"').collect());
+        } else {
+            let mut arg = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                arg.push(c);
+                chars.next();
+            }
+            args.push(arg);
+        }
+    }
+    args
+}
+
+/// Parse an `mm:ss:ff` index (75 frames per second) into milliseconds.
+fn parse_index_time(value: &str) -> Option<u32> {
+    let mut parts = value.split(':').map(|p| p.trim().parse::<u32>().ok());
+    let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || seconds >= 60 || frames >= 75 {
+        return None;
+    }
+    Some(minutes * 60_000 + seconds * 1000 + (frames * 1000 + 37) / 75)
+}
+
+/// Parse a CUE sheet. Unknown commands and non-audio tracks are ignored.
+pub fn parse(input: &str) -> CueSheet {
+    let mut sheet = CueSheet::default();
+    let mut track: Option<CueTrack> = None;
+    // inside a non-audio track, whose fields belong to neither the sheet nor a track
+    let mut skipping = false;
+
+    fn finish(sheet: &mut CueSheet, track: Option<CueTrack>) {
+        if let (Some(track), Some(file)) = (track, sheet.files.last_mut()) {
+            file.tracks.push(track);
+        }
+    }
+
+    for line in input.lines() {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = arguments(rest);
+        let first = args.first().cloned();
+        let command = command.to_ascii_uppercase();
+
+        match command.as_str() {
+            "TITLE" | "PERFORMER" | "SONGWRITER" | "ISRC" | "REM" if skipping => {}
+            "FILE" => {
+                finish(&mut sheet, track.take());
+                skipping = false;
+                if let Some(name) = first {
+                    sheet.files.push(CueFile {
+                        name,
+                        tracks: Vec::new(),
+                    });
+                }
+            }
+            "TRACK" => {
+                finish(&mut sheet, track.take());
+                let is_audio = args
+                    .get(1)
+                    .map(|t| t.eq_ignore_ascii_case("AUDIO"))
+                    .unwrap_or(false);
+                track = first
+                    .and_then(|n| n.parse::<u32>().ok())
+                    .filter(|_| is_audio)
+                    .map(|number| CueTrack {
+                        number,
+                        ..Default::default()
+                    });
+                skipping = track.is_none();
+            }
+            "INDEX" => {
+                let time = args.get(1).and_then(|t| parse_index_time(t));
+                if let (Some(t), Some(time)) = (track.as_mut(), time) {
+                    // INDEX 00 (the pregap) only stands in for a missing INDEX 01
+                    match first.as_deref() {
+                        Some("00") | Some("0") if t.start_ms == 0 => t.start_ms = time,
+                        Some("01") | Some("1") => t.start_ms = time,
+                        _ => {}
+                    }
+                }
+            }
+            "TITLE" | "PERFORMER" | "SONGWRITER" => {
+                let (title, performer, songwriter) = match track.as_mut() {
+                    Some(t) => (&mut t.title, &mut t.performer, &mut t.songwriter),
+                    None => (
+                        &mut sheet.title,
+                        &mut sheet.performer,
+                        &mut sheet.songwriter,
+                    ),
+                };
+                let target = match command.as_str() {
+                    "TITLE" => title,
+                    "PERFORMER" => performer,
+                    _ => songwriter,
+                };
+                *target = first.filter(|v| !v.trim().is_empty());
+            }
+            "ISRC" => {
+                if let Some(t) = track.as_mut() {
+                    t.isrc = first;
+                }
+            }
+            "REM" => {
+                if let Some(key) = first {
+                    let value = args[1..].join(" ");
+                    let rem = match track.as_mut() {
+                        Some(t) => &mut t.rem,
+                        None => &mut sheet.rem,
+                    };
+                    rem.insert(key.to_ascii_uppercase(), value);
+                }
+            }
+            _ => {}
+        }
+    }
+    finish(&mut sheet, track);
+    sheet
+}
+
+/// Read a CUE sheet, tolerating a BOM and non UTF-8 encodings.
+pub fn read(path: &Path) -> anyhow::Result<CueSheet> {
+    let bytes = std::fs::read(path)?;
+    let content = String::from_utf8_lossy(&bytes);
+    Ok(parse(content.trim_start_matches('\u{feff}')))
+}
+
+fn same_stem(a: &str, b: &Path) -> bool {
+    let a = Path::new(a).file_stem().and_then(|s| s.to_str());
+    let b = b.file_stem().and_then(|s| s.to_str());
+    matches!((a, b), (Some(a), Some(b)) if a.eq_ignore_ascii_case(b))
+}
+
+impl CueSheet {
+    /// The FILE entry describing `audio`. A sheet with a single FILE always matches,
+    /// since rips are often re-encoded without updating the sheet.
+    pub fn file_for(&self, audio: &Path) -> Option<&CueFile> {
+        let name = audio.file_name()?.to_str()?;
+        match self.files.as_slice() {
+            [only] => Some(only),
+            files => files
+                .iter()
+                .find(|f| {
+                    Path::new(&f.name)
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .map(|n| n.eq_ignore_ascii_case(name))
+                        .unwrap_or(false)
+                })
+                .or_else(|| files.iter().find(|f| same_stem(&f.name, audio))),
+        }
+        .filter(|f| !f.tracks.is_empty())
+    }
+
+    /// Paths of the audio files this sheet describes, resolved next to the sheet.
+    pub fn audio_paths(&self, cue: &Path) -> Vec<PathBuf> {
+        let Some(dir) = cue.parent() else {
+            return Vec::new();
+        };
+        self.files.iter().map(|f| dir.join(&f.name)).collect()
+    }
+}
+
+/// Find a CUE sheet describing `audio`: `<stem>.cue`, `<name>.cue`, or any sheet in the
+/// same directory with a FILE entry for it.
+pub fn find_for(audio: &Path) -> Option<(PathBuf, CueSheet)> {
+    let dir = audio.parent()?;
+    let mut candidates = vec![audio.with_extension("cue")];
+    if let Some(name) = audio.file_name().and_then(|n| n.to_str()) {
+        candidates.push(dir.join(format!("{}.cue", name)));
+    }
+    let mut others: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| is_cue(p) && !candidates.contains(p))
+        .collect();
+    others.sort();
+
+    let name = audio.file_name()?.to_str()?;
+    let exact = |sheet: &CueSheet| {
+        sheet
+            .files
+            .iter()
+            .any(|f| f.name.eq_ignore_ascii_case(name) || same_stem(&f.name, audio))
+    };
+    candidates
+        .into_iter()
+        .filter(|p| p.is_file())
+        .filter_map(|p| read(&p).ok().map(|s| (p, s)))
+        .find(|(_, s)| s.file_for(audio).is_some())
+        .or_else(|| {
+            // sheets named after the album must name the file explicitly
+            others
+                .into_iter()
+                .filter_map(|p| read(&p).ok().map(|s| (p, s)))
+                .find(|(_, s)| exact(s) && s.file_for(audio).is_some())
+        })
+}
+
+pub fn is_cue(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("cue"))
+        .unwrap_or(false)
+}
+
+/// Split a whole-file scan into one virtual track per CUE track. Sheet values win over the
+/// file's tags for per-track fields, while stream properties and album art are shared.
+pub fn split(
+    meta: &AudioMetadata,
+    sheet: &CueSheet,
+    file: &CueFile,
+    split_exceptions: &[String],
+) -> Vec<AudioMetadata> {
+    let album = sheet.title.clone().unwrap_or_else(|| meta.album.clone());
+    let album_artist = sheet
+        .performer
+        .clone()
+        .unwrap_or_else(|| meta.album_artist.clone());
+    let year = meta.year.or_else(|| {
+        sheet
+            .rem
+            .get("DATE")
+            .and_then(|d| d.get(..4))
+            .and_then(|y| y.parse().ok())
+    });
+    let genre = meta
+        .genre
+        .clone()
+        .or_else(|| sheet.rem.get("GENRE").map(|g| vec![g.clone()]));
+    let album_gain = ReplayGain::from_fields(|key| sheet.rem.get(key).cloned());
+
+    let mut tracks: Vec<&CueTrack> = file.tracks.iter().collect();
+    tracks.sort_by_key(|t| t.start_ms);
+
+    tracks
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| t.start_ms < meta.duration_ms)
+        .map(|(i, track)| {
+            let end_ms = tracks
+                .get(i + 1)
+                .map(|next| next.start_ms)
+                .filter(|&end| end > track.start_ms && end < meta.duration_ms);
+            let duration_ms = end_ms.unwrap_or(meta.duration_ms) - track.start_ms;
+            let track_gain = ReplayGain::from_fields(|key| track.rem.get(key).cloned());
+            let (artists, artist_tag) = match &track.performer {
+                Some(p) => (
+                    split_artists(&vec![p.clone()], split_exceptions),
+                    Some(p.clone()),
+                ),
+                None if sheet.performer.is_some() => {
+                    (vec![album_artist.clone()], Some(album_artist.clone()))
+                }
+                None => (meta.artists.clone(), meta.artist_tag.clone()),
+            };
+
+            AudioMetadata {
+                name: track
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| format!("Track {:02}", track.number)),
+                number: track.number,
+                duration: duration_ms / 1000,
+                duration_ms,
+                album: album.clone(),
+                album_artist: album_artist.clone(),
+                artists,
+                artist_tag,
+                genre: genre.clone(),
+                year,
+                total_samples: meta
+                    .sample_rate
+                    .map(|rate| duration_ms as u64 * rate as u64 / 1000),
+                encoder_delay: None,
+                encoder_padding: None,
+                mbid_track: None,
+                composer: track.songwriter.clone().or_else(|| meta.composer.clone()),
+                isrc: track.isrc.clone(),
+                lyrics: None,
+                replay_gain: ReplayGain {
+                    track_gain: track_gain.track_gain,
+                    track_peak: track_gain.track_peak,
+                    album_gain: album_gain.album_gain.or(meta.replay_gain.album_gain),
+                    album_peak: album_gain.album_peak.or(meta.replay_gain.album_peak),
+                },
+                cue: Some(CueSpan {
+                    track: track.number,
+                    start_ms: track.start_ms,
+                    end_ms,
+                }),
+                ..meta.clone()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = r#"REM GENRE "Ambient"
+REM DATE 1999
+PERFORMER "Some Band"
+TITLE "Whole Album"
+FILE "Whole Album.wav" WAVE
+  TRACK 01 AUDIO
+    TITLE "Opening"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Second"
+    PERFORMER "Some Band feat. Guest"
+    REM REPLAYGAIN_TRACK_GAIN -7.20 dB
+    INDEX 00 03:58:50
+    INDEX 01 04:00:37
+  TRACK 03 DATA
+    TITLE "Bonus Video"
+    REM COMMENT "Enhanced CD"
+    INDEX 01 10:00:00
+"#;
+
+    #[test]
+    fn parses_sheet() {
+        let sheet = parse(SHEET);
+        assert_eq!(sheet.title.as_deref(), Some("Whole Album"));
+        assert_eq!(sheet.rem.get("GENRE").map(|s| s.as_str()), Some("Ambient"));
+        assert!(!sheet.rem.contains_key("COMMENT"));
+        assert_eq!(sheet.files.len(), 1);
+        let tracks = &sheet.files[0].tracks;
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[1].title.as_deref(), Some("Second"));
+        assert_eq!(tracks[1].start_ms, 240_493);
+        assert_eq!(
+            tracks[1]
+                .rem
+                .get("REPLAYGAIN_TRACK_GAIN")
+                .map(|s| s.as_str()),
+            Some("-7.20 dB")
+        );
+    }
+
+    #[test]
+    fn index_times() {
+        assert_eq!(parse_index_time("01:02:00"), Some(62_000));
+        assert_eq!(parse_index_time("00:00:74"), Some(987));
+        assert_eq!(parse_index_time("00:60:00"), None);
+    }
+
+    #[test]
+    fn single_file_sheet_matches_reencoded_audio() {
+        let sheet = parse(SHEET);
+        assert!(sheet.file_for(Path::new("/m/Whole Album.flac")).is_some());
+    }
+}
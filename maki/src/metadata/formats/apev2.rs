@@ -0,0 +1,449 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::{
+    config::Config,
+    helpers::split_artists,
+    metadata::{
+        classical::Classical,
+        credits,
+        formats::{
+            parse_index, parse_total_fields, samples_to_ms, DISC_TOTAL_KEYS, TRACK_TOTAL_KEYS,
+        },
+        lyrics::Lyrics,
+        replaygain::ReplayGain,
+        AudioMetadata, Picture,
+    },
+};
+
+/// Upper bound for an APE tag. Several embedded covers fit comfortably within it.
+const MAX_TAG_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Footer (and optional header) size shared by APEv1 and APEv2 tags.
+const FOOTER_LEN: u64 = 32;
+
+/// Tag flags: the tag has a header, and this frame is that header.
+const HAS_HEADER: u32 = 1 << 31;
+const IS_HEADER: u32 = 1 << 29;
+
+fn le_u32(b: &[u8], at: usize) -> Option<u32> {
+    b.get(at..at + 4)
+        .map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+}
+
+/// Seek past an ID3v2 tag at the start of the file, which some taggers prepend to
+/// WavPack and Musepack files. Leaves the reader at the start of the audio.
+pub fn skip_id3v2<R: Read + Seek>(r: &mut R) -> std::io::Result<()> {
+    let mut header = [0u8; 10];
+    r.seek(SeekFrom::Start(0))?;
+    if r.read_exact(&mut header).is_err() || &header[0..3] != b"ID3" {
+        r.seek(SeekFrom::Start(0))?;
+        return Ok(());
+    }
+    // synchsafe size excludes the 10 byte header and the optional footer
+    let size = header[6..10]
+        .iter()
+        .fold(0u64, |acc, &b| (acc << 7) | (b & 0x7F) as u64);
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    r.seek(SeekFrom::Start(10 + size + footer))?;
+    Ok(())
+}
+
+/// Stream properties read from a WavPack, Monkey's Audio or Musepack header.
+#[derive(Debug, PartialEq, Default)]
+pub struct ApeStream {
+    pub sample_rate: u32,
+    pub bits_per_sample: Option<u8>,
+    pub num_channels: u8,
+    pub total_samples: u64,
+    pub lossless: bool,
+}
+
+/// A raw APE item, kept as read so rewriting a tag preserves items we don't interpret.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApeItem {
+    pub key: String,
+    pub flags: u32,
+    pub value: Vec<u8>,
+}
+
+impl ApeItem {
+    /// A UTF-8 text item; several values are stored NUL separated.
+    pub fn text(key: &str, values: &[String]) -> Self {
+        ApeItem {
+            key: key.to_string(),
+            flags: 0,
+            value: values.join("\0").into_bytes(),
+        }
+    }
+}
+
+/// The items of a file's APE tag and the byte range the tag occupies, including its
+/// optional header. Files without a tag get an empty range where one would be appended.
+#[derive(Debug, Default)]
+pub struct ApeItems {
+    pub start: u64,
+    pub end: u64,
+    pub items: Vec<ApeItem>,
+}
+
+impl ApeItems {
+    /// Read the tag at the end of a file, ahead of any ID3v1 tag.
+    pub fn read<R: Read + Seek>(r: &mut R) -> anyhow::Result<Self> {
+        let len = r.seek(SeekFrom::End(0))?;
+        let mut end = len;
+        if len >= 128 {
+            let mut magic = [0u8; 3];
+            r.seek(SeekFrom::Start(len - 128))?;
+            r.read_exact(&mut magic)?;
+            if &magic == b"TAG" {
+                end = len - 128;
+            }
+        }
+        let untagged = ApeItems {
+            start: end,
+            end,
+            items: Vec::new(),
+        };
+        if end < FOOTER_LEN {
+            return Ok(untagged);
+        }
+
+        let mut footer = [0u8; FOOTER_LEN as usize];
+        r.seek(SeekFrom::Start(end - FOOTER_LEN))?;
+        r.read_exact(&mut footer)?;
+        if &footer[0..8] != b"APETAGEX" {
+            return Ok(untagged);
+        }
+        // the size covers the items and the footer, but not the optional header
+        let size = le_u32(&footer, 12).unwrap_or_default() as u64;
+        let count = le_u32(&footer, 16).unwrap_or_default();
+        let flags = le_u32(&footer, 20).unwrap_or_default();
+        if size < FOOTER_LEN || size > end || size > MAX_TAG_BYTES {
+            anyhow::bail!("APE tag size {} is out of range", size);
+        }
+
+        let mut data = vec![0u8; (size - FOOTER_LEN) as usize];
+        r.seek(SeekFrom::Start(end - size))?;
+        r.read_exact(&mut data)?;
+        let header = if flags & HAS_HEADER != 0 {
+            FOOTER_LEN
+        } else {
+            0
+        };
+        Ok(ApeItems {
+            start: (end - size).saturating_sub(header),
+            end,
+            items: Self::parse(&data, count)?,
+        })
+    }
+
+    /// Parse `count` items: a value size, flags, a NUL-terminated key, then the value.
+    pub fn parse(data: &[u8], count: u32) -> anyhow::Result<Vec<ApeItem>> {
+        let mut items = Vec::new();
+        let mut at = 0usize;
+        for _ in 0..count {
+            let (Some(size), Some(flags)) = (le_u32(data, at), le_u32(data, at + 4)) else {
+                anyhow::bail!("APE item header truncated at {}", at);
+            };
+            let key_start = at + 8;
+            let key_end = data
+                .get(key_start..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .map(|nul| key_start + nul)
+                .ok_or_else(|| anyhow::anyhow!("APE item key is not terminated"))?;
+            let value_start = key_end + 1;
+            let value = data
+                .get(value_start..value_start + size as usize)
+                .ok_or_else(|| anyhow::anyhow!("APE item value exceeds tag size"))?;
+            items.push(ApeItem {
+                key: String::from_utf8_lossy(&data[key_start..key_end]).to_string(),
+                flags,
+                value: value.to_vec(),
+            });
+            at = value_start + size as usize;
+        }
+        Ok(items)
+    }
+
+    /// Encode the items as an APEv2 tag with both a header and a footer.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for item in &self.items {
+            body.extend_from_slice(&(item.value.len() as u32).to_le_bytes());
+            body.extend_from_slice(&item.flags.to_le_bytes());
+            body.extend_from_slice(item.key.as_bytes());
+            body.push(0);
+            body.extend_from_slice(&item.value);
+        }
+
+        let frame = |flags: u32| {
+            let mut f = b"APETAGEX".to_vec();
+            f.extend_from_slice(&2000u32.to_le_bytes());
+            f.extend_from_slice(&(body.len() as u32 + FOOTER_LEN as u32).to_le_bytes());
+            f.extend_from_slice(&(self.items.len() as u32).to_le_bytes());
+            f.extend_from_slice(&flags.to_le_bytes());
+            f.extend_from_slice(&[0u8; 8]);
+            f
+        };
+        let mut tag = frame(HAS_HEADER | IS_HEADER);
+        tag.extend_from_slice(&body);
+        tag.extend(frame(HAS_HEADER));
+        tag
+    }
+}
+
+/// APEv2 (or APEv1) text items keyed by upper-cased name, plus any embedded covers.
+#[derive(Debug, Default)]
+pub struct ApeTag {
+    items: HashMap<String, Vec<String>>,
+    pictures: Vec<Picture>,
+}
+
+impl ApeTag {
+    /// Read the tag at the end of a file. Files without an APE tag yield an empty
+    /// one, as they still index from their headers.
+    pub fn read<R: Read + Seek>(r: &mut R) -> anyhow::Result<Self> {
+        Ok(Self::from_items(&ApeItems::read(r)?.items))
+    }
+
+    pub fn from_items(items: &[ApeItem]) -> Self {
+        let mut tag = Self::default();
+        for item in items {
+            // bits 1-2 hold the item type: UTF-8 text, binary or an external locator
+            match (item.flags >> 1) & 0x03 {
+                0 => {
+                    // text items hold several values separated by NULs
+                    let values = item
+                        .value
+                        .split(|&b| b == 0)
+                        .map(|v| String::from_utf8_lossy(v).trim().to_string())
+                        .filter(|v| !v.is_empty());
+                    tag.items
+                        .entry(item.key.to_uppercase())
+                        .or_default()
+                        .extend(values);
+                }
+                1 => {
+                    if let Some(picture) = parse_cover(&item.key, &item.value) {
+                        tag.pictures.push(picture);
+                    }
+                }
+                _ => {}
+            }
+        }
+        tag
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Vec<String>> {
+        self.items.get(key).filter(|v| !v.is_empty())
+    }
+
+    pub fn first(&self, key: &str) -> Option<String> {
+        self.get(key).and_then(|v| v.first().cloned())
+    }
+}
+
+/// Decode a `Cover Art (Front)` style binary item: a file name, a NUL, then the image.
+fn parse_cover(key: &str, value: &[u8]) -> Option<Picture> {
+    let kind = key
+        .get(..11)
+        .filter(|k| k.eq_ignore_ascii_case("cover art ("))
+        .and_then(|_| key[11..].strip_suffix(')'))?;
+    let nul = value.iter().position(|&b| b == 0)?;
+    let bytes = value[nul + 1..].to_vec();
+    if bytes.is_empty() {
+        return None;
+    }
+    Some(Picture {
+        picture_type: format!("Cover ({})", kind),
+        bytes,
+    })
+}
+
+/// Build metadata for an APE-tagged file from its tag and stream header.
+pub fn ape_metadata(
+    path: &std::path::Path,
+    tag: ApeTag,
+    stream: ApeStream,
+    file_len: u64,
+    cfg: &Config,
+) -> AudioMetadata {
+    let duration_ms = samples_to_ms(stream.total_samples, stream.sample_rate);
+    let bitrate = if stream.total_samples > 0 {
+        let bits = file_len * 8 * stream.sample_rate as u64;
+        Some((bits / stream.total_samples / 1000) as u32)
+    } else {
+        None
+    };
+
+    let year = tag
+        .first("YEAR")
+        .or_else(|| tag.first("DATE"))
+        .and_then(|y| {
+            y.split('-')
+                .next()
+                .and_then(|p| p.trim().parse::<i32>().ok())
+        })
+        .filter(|&y| y > 0);
+
+    // artists is either ARTISTS (one per value) or ARTIST (single but may be split elsewhere)
+    let unk_vec = vec!["Unknown".to_string()];
+    let artists = match tag.get("ARTISTS") {
+        Some(a) => a.to_owned(),
+        None => split_artists(
+            tag.get("ARTIST")
+                .or_else(|| tag.get("ALBUM ARTIST"))
+                .unwrap_or(&unk_vec),
+            &cfg.artist_split_exceptions,
+        ),
+    };
+
+    AudioMetadata {
+        name: tag.first("TITLE").unwrap_or_default(),
+        number: tag
+            .first("TRACK")
+            .or_else(|| tag.first("TRACKNUMBER"))
+            .and_then(|n| parse_index(&n))
+            .unwrap_or(0),
+        duration: duration_ms / 1000,
+        duration_ms,
+        album: tag.first("ALBUM").unwrap_or_default(),
+        album_artist: tag
+            .first("ALBUM ARTIST")
+            .or_else(|| tag.first("ALBUMARTIST"))
+            .or_else(|| tag.first("ARTIST"))
+            .unwrap_or_default(),
+        album_sort: tag.first("ALBUMSORT"),
+        artist_sort: tag.first("ARTISTSORT"),
+        album_artist_sort: tag.first("ALBUMARTISTSORT"),
+        artists,
+        artist_tag: tag.first("ARTIST"),
+        genre: tag.get("GENRE").map(|v| v.to_owned()),
+        picture: tag.pictures.clone(),
+        path: path.to_path_buf(),
+        year,
+        lossless: stream.lossless,
+        disc: tag
+            .first("DISC")
+            .or_else(|| tag.first("DISCNUMBER"))
+            .and_then(|n| parse_index(&n)),
+        track_total: parse_total_fields(
+            |key| tag.first(key),
+            &TRACK_TOTAL_KEYS,
+            &["TRACK", "TRACKNUMBER"],
+        ),
+        disc_total: parse_total_fields(
+            |key| tag.first(key),
+            &DISC_TOTAL_KEYS,
+            &["DISC", "DISCNUMBER"],
+        ),
+        disc_subtitle: tag.first("DISCSUBTITLE"),
+        sample_rate: Some(stream.sample_rate).filter(|&r| r > 0),
+        bits_per_sample: stream.bits_per_sample,
+        num_channels: Some(stream.num_channels).filter(|&c| c > 0),
+        bitrate,
+        total_samples: Some(stream.total_samples).filter(|&t| t > 0),
+        encoder_delay: None,
+        encoder_padding: None,
+        mbid_artist: tag
+            .first("MUSICBRAINZ_ALBUMARTISTID")
+            .or_else(|| tag.first("MUSICBRAINZ_ARTISTID")),
+        mbid_album: tag.first("MUSICBRAINZ_ALBUMID"),
+        mbid_track: tag.first("MUSICBRAINZ_TRACKID"),
+        composer: tag.first("COMPOSER"),
+        isrc: tag.first("ISRC"),
+        bpm: tag
+            .first("BPM")
+            .and_then(|s| s.trim().parse::<f32>().ok())
+            .map(|b| b.round() as u32),
+        copyright: tag.first("COPYRIGHT"),
+        label: tag.first("LABEL").or_else(|| tag.first("PUBLISHER")),
+        lyrics: tag
+            .first("LYRICS")
+            .or_else(|| tag.first("UNSYNCEDLYRICS"))
+            .and_then(|l| Lyrics::from_tag(&l)),
+        replay_gain: ReplayGain::from_fields(|key| tag.first(key)),
+        cue: None,
+        compilation: tag
+            .first("COMPILATION")
+            .map(|c| super::parse_flag(&c))
+            .unwrap_or(false),
+        classical: Classical::from_fields(|key| tag.first(key)),
+        credits: credits::from_fields(|key| tag.get(key).cloned()),
+        explicit: super::advisory_fields(|key| tag.first(key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(key: &str, flags: u32, value: &[u8]) -> Vec<u8> {
+        let mut out = (value.len() as u32).to_le_bytes().to_vec();
+        out.extend_from_slice(&flags.to_le_bytes());
+        out.extend_from_slice(key.as_bytes());
+        out.push(0);
+        out.extend_from_slice(value);
+        out
+    }
+
+    #[test]
+    fn reads_tag_before_id3v1() {
+        let mut items = item("Title", 0, b"Song");
+        items.extend(item("Artist", 0, b"One\0Two"));
+        items.extend(item("Cover Art (Front)", 2, b"front.jpg\0\xFF\xD8\xFF"));
+
+        let mut file = b"wvpk".to_vec();
+        file.extend_from_slice(&items);
+        file.extend_from_slice(b"APETAGEX");
+        for v in [2000u32, items.len() as u32 + 32, 3, 0] {
+            file.extend_from_slice(&v.to_le_bytes());
+        }
+        file.extend_from_slice(&[0u8; 8]);
+        let mut id3v1 = b"TAG".to_vec();
+        id3v1.resize(128, 0);
+        file.extend(id3v1);
+
+        let tag = ApeTag::read(&mut std::io::Cursor::new(file)).unwrap();
+        assert_eq!(tag.first("TITLE").as_deref(), Some("Song"));
+        assert_eq!(
+            tag.get("ARTIST"),
+            Some(&vec!["One".to_string(), "Two".to_string()])
+        );
+        assert_eq!(tag.pictures.len(), 1);
+        assert_eq!(tag.pictures[0].picture_type, "Cover (Front)");
+        assert_eq!(tag.pictures[0].bytes, b"\xFF\xD8\xFF");
+    }
+
+    #[test]
+    fn encodes_items_round_trip() {
+        let tagged = ApeItems {
+            items: vec![
+                ApeItem::text("Title", &["Song".to_string()]),
+                ApeItem::text("Genre", &["Rock".to_string(), "Pop".to_string()]),
+            ],
+            ..Default::default()
+        };
+        let mut file = b"MAC ".to_vec();
+        file.extend(tagged.encode());
+
+        let read = ApeItems::read(&mut std::io::Cursor::new(&file)).unwrap();
+        assert_eq!(read.start, 4);
+        assert_eq!(read.end, file.len() as u64);
+        assert_eq!(read.items, tagged.items);
+        let tag = ApeTag::from_items(&read.items);
+        assert_eq!(
+            tag.get("GENRE"),
+            Some(&vec!["Rock".to_string(), "Pop".to_string()])
+        );
+    }
+
+    #[test]
+    fn missing_tag_is_empty() {
+        let tag = ApeTag::read(&mut std::io::Cursor::new(vec![0u8; 64])).unwrap();
+        assert_eq!(tag.first("TITLE"), None);
+    }
+}
@@ -0,0 +1,415 @@
+use std::{
+    convert::TryInto,
+    io::{Cursor, Read, Seek, SeekFrom},
+};
+
+use crate::{
+    config::Config,
+    helpers::{sort_string, split_artists},
+    metadata::{formats::samples_to_ms, AudioMetadata, Picture},
+};
+use id3::{partial_tag_ok, TagLike};
+
+/// PCM rate DSD is decimated to for transcoding. Every DSD rate (64 × 44.1 kHz and up)
+/// divides into it evenly, and it keeps well clear of DSD's shaped noise floor.
+pub const DSD_PCM_SAMPLE_RATE: u32 = 88_200;
+
+/// ffmpeg audio filter converting a decoded DSD stream to PCM at [`DSD_PCM_SAMPLE_RATE`].
+pub fn pcm_filter() -> String {
+    format!("aresample={}", DSD_PCM_SAMPLE_RATE)
+}
+
+/// Stream properties shared by DSF and DFF files. Samples are 1-bit, per channel.
+#[derive(Debug, PartialEq, Default)]
+struct DsdStream {
+    sample_rate: u32,
+    num_channels: u8,
+    total_samples: u64,
+}
+
+/// What a DSD container holds besides the audio: an ID3v2 tag and, for DFF,
+/// the edited master title/artist from its DIIN chunk.
+#[derive(Debug, PartialEq, Default)]
+struct DsdInfo {
+    stream: DsdStream,
+    id3: Option<Vec<u8>>,
+    title: Option<String>,
+    artist: Option<String>,
+}
+
+fn le_u32(b: &[u8], at: usize) -> Option<u32> {
+    b.get(at..at + 4)
+        .map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+}
+
+fn le_u64(b: &[u8], at: usize) -> Option<u64> {
+    let s = b.get(at..at + 8)?;
+    Some(u64::from_le_bytes([
+        s[0], s[1], s[2], s[3], s[4], s[5], s[6], s[7],
+    ]))
+}
+
+fn be_u16(b: &[u8], at: usize) -> Option<u16> {
+    b.get(at..at + 2).map(|s| u16::from_be_bytes([s[0], s[1]]))
+}
+
+fn be_u32(b: &[u8], at: usize) -> Option<u32> {
+    b.get(at..at + 4)
+        .map(|s| u32::from_be_bytes([s[0], s[1], s[2], s[3]]))
+}
+
+/// Read a chunk body, refusing sizes that can't be metadata so a corrupt header
+/// doesn't allocate gigabytes.
+fn read_body<R: Read>(r: &mut R, size: u64) -> anyhow::Result<Vec<u8>> {
+    if size > 64 * 1024 * 1024 {
+        anyhow::bail!("chunk of {} bytes is too large", size);
+    }
+    let mut body = vec![0u8; size as usize];
+    r.read_exact(&mut body)?;
+    Ok(body)
+}
+
+/// Parse a DSF file: a `DSD ` header pointing at an optional trailing ID3v2 tag,
+/// then a little-endian `fmt ` chunk describing the stream.
+fn read_dsf<R: Read + Seek>(r: &mut R) -> anyhow::Result<DsdInfo> {
+    let mut header = [0u8; 28];
+    r.read_exact(&mut header)?;
+    if &header[0..4] != b"DSD " {
+        anyhow::bail!("not a DSF file");
+    }
+    let metadata_offset = le_u64(&header, 20).unwrap_or(0);
+
+    let mut fmt_header = [0u8; 12];
+    r.read_exact(&mut fmt_header)?;
+    if &fmt_header[0..4] != b"fmt " {
+        anyhow::bail!("DSF file has no fmt chunk");
+    }
+    let fmt_size = le_u64(&fmt_header, 4).unwrap_or(0);
+    let fmt = read_body(r, fmt_size.saturating_sub(12))?;
+
+    // version (4), format id (4), channel type (4), channels (4), sample rate (4),
+    // bits per sample (4), sample count (8), block size per channel (4)
+    let stream = DsdStream {
+        num_channels: le_u32(&fmt, 12).unwrap_or(0) as u8,
+        sample_rate: le_u32(&fmt, 16).unwrap_or(0),
+        total_samples: le_u64(&fmt, 24).unwrap_or(0),
+    };
+
+    let id3 = if metadata_offset > 0 {
+        r.seek(SeekFrom::Start(metadata_offset))?;
+        let mut tag = Vec::new();
+        r.take(64 * 1024 * 1024).read_to_end(&mut tag)?;
+        Some(tag).filter(|t| t.starts_with(b"ID3"))
+    } else {
+        None
+    };
+
+    Ok(DsdInfo {
+        stream,
+        id3,
+        ..Default::default()
+    })
+}
+
+/// Read a DIIN text chunk (`DITI`, `DIAR`): a big-endian count followed by the text.
+fn diin_text(body: &[u8]) -> Option<String> {
+    let len = be_u32(body, 0)? as usize;
+    let text = body.get(4..4 + len)?;
+    let text = String::from_utf8_lossy(text).trim().to_string();
+    Some(text).filter(|t| !t.is_empty())
+}
+
+/// Iterate the `(id, body)` pairs of big-endian DSDIFF chunks held in memory.
+fn dff_chunks(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let id: [u8; 4] = data.get(0..4)?.try_into().ok()?;
+        let size = u64::from_be_bytes(data.get(4..12)?.try_into().ok()?) as usize;
+        let body = data.get(12..12usize.checked_add(size)?)?;
+        // chunks are padded to an even length
+        data = data.get(12 + size + size % 2..).unwrap_or(&[]);
+        Some((id, body))
+    })
+}
+
+/// Parse a DFF (DSDIFF) file: a big-endian `FRM8` form of `PROP`, `DSD `/`DST `,
+/// `DIIN` and unofficial `ID3 ` chunks. Sound data is skipped, not read.
+fn read_dff<R: Read + Seek>(r: &mut R) -> anyhow::Result<DsdInfo> {
+    let mut header = [0u8; 16];
+    r.read_exact(&mut header)?;
+    if &header[0..4] != b"FRM8" || &header[12..16] != b"DSD " {
+        anyhow::bail!("not a DFF file");
+    }
+
+    let mut info = DsdInfo::default();
+    // DST compressed audio gives its length as frames at a frame rate
+    let mut dst_frames: Option<(u32, u16)> = None;
+    let mut sound_bytes: Option<u64> = None;
+
+    loop {
+        let mut chunk = [0u8; 12];
+        if r.read_exact(&mut chunk).is_err() {
+            break;
+        }
+        let size = u64::from_be_bytes(chunk[4..12].try_into()?);
+        let padded = size + size % 2;
+
+        match &chunk[0..4] {
+            b"PROP" => {
+                let body = read_body(r, size)?;
+                if !body.starts_with(b"SND ") {
+                    anyhow::bail!("DFF property chunk is not SND");
+                }
+                for (id, sub) in dff_chunks(&body[4..]) {
+                    match &id {
+                        b"FS  " => info.stream.sample_rate = be_u32(sub, 0).unwrap_or(0),
+                        b"CHNL" => info.stream.num_channels = be_u16(sub, 0).unwrap_or(0) as u8,
+                        _ => {}
+                    }
+                }
+                r.seek(SeekFrom::Current((padded - size) as i64))?;
+            }
+            b"DSD " => {
+                sound_bytes = Some(size);
+                r.seek(SeekFrom::Current(padded as i64))?;
+            }
+            b"DST " => {
+                // FRTE is the first chunk inside DST, the frames that follow are skipped
+                let mut frte = [0u8; 18];
+                r.read_exact(&mut frte)?;
+                if &frte[0..4] == b"FRTE" {
+                    dst_frames = Some((
+                        be_u32(&frte, 12).unwrap_or(0),
+                        be_u16(&frte, 16).unwrap_or(0),
+                    ));
+                }
+                r.seek(SeekFrom::Current(padded as i64 - frte.len() as i64))?;
+            }
+            b"DIIN" => {
+                let body = read_body(r, size)?;
+                for (id, sub) in dff_chunks(&body) {
+                    match &id {
+                        b"DITI" => info.title = diin_text(sub),
+                        b"DIAR" => info.artist = diin_text(sub),
+                        _ => {}
+                    }
+                }
+                r.seek(SeekFrom::Current((padded - size) as i64))?;
+            }
+            b"ID3 " => {
+                info.id3 = Some(read_body(r, size)?);
+                r.seek(SeekFrom::Current((padded - size) as i64))?;
+            }
+            _ => {
+                r.seek(SeekFrom::Current(padded as i64))?;
+            }
+        }
+    }
+
+    let stream = &mut info.stream;
+    stream.total_samples = match (sound_bytes, dst_frames) {
+        // one bit per sample per channel, interleaved by byte
+        (Some(bytes), _) if stream.num_channels > 0 => bytes * 8 / stream.num_channels as u64,
+        (_, Some((frames, rate))) if rate > 0 => {
+            frames as u64 * stream.sample_rate as u64 / rate as u64
+        }
+        _ => 0,
+    };
+
+    Ok(info)
+}
+
+/// Build metadata from a DSD file's ID3v2 tag, falling back to DIIN text for DFF.
+fn dsd_metadata(
+    path: &std::path::Path,
+    info: DsdInfo,
+    cfg: &Config,
+) -> anyhow::Result<AudioMetadata> {
+    let tag = match &info.id3 {
+        Some(bytes) => partial_tag_ok(id3::Tag::read_from2(Cursor::new(bytes)))?,
+        None => id3::Tag::new(),
+    };
+    let stream = info.stream;
+    let duration_ms = samples_to_ms(stream.total_samples, stream.sample_rate);
+
+    let artists: Vec<String> = tag
+        .artists()
+        .map(|a| a.into_iter().map(|s| s.to_string()).collect())
+        .unwrap_or_else(|| {
+            split_artists(
+                &[tag
+                    .artist()
+                    .map(|a| a.to_owned())
+                    .or_else(|| info.artist.clone())
+                    .unwrap_or_default()]
+                .to_vec(),
+                &cfg.artist_split_exceptions,
+            )
+        });
+    let artist_tag = tag
+        .artist()
+        .map(|a| a.to_owned())
+        .or_else(|| info.artist.clone());
+
+    let txxx = |description: &str| {
+        tag.extended_texts()
+            .find(|t| t.description == description)
+            .map(|t| t.value.clone())
+    };
+
+    Ok(AudioMetadata {
+        name: tag
+            .title()
+            .map(|t| t.to_string())
+            .or(info.title)
+            .unwrap_or_default(),
+        number: tag.track().unwrap_or(0),
+        duration: duration_ms / 1000,
+        duration_ms,
+        album: tag.album().unwrap_or_default().to_string(),
+        album_artist: tag
+            .album_artist()
+            .map(|a| a.to_string())
+            .or(info.artist)
+            .unwrap_or_default(),
+        album_sort: sort_string(tag.album()),
+        artist_sort: tag.text_for_frame_id("TSOP").map(|s| s.to_string()),
+        album_artist_sort: tag.text_for_frame_id("TSO2").map(|s| s.to_string()),
+        artists,
+        artist_tag,
+        genre: tag.genre().map(|g| vec![g.to_string()]).or_else(|| {
+            tag.genres()
+                .map(|gs| gs.into_iter().map(|s: &str| s.to_string()).collect())
+        }),
+        picture: tag
+            .pictures()
+            .map(|p| Picture {
+                picture_type: p.picture_type.to_string(),
+                bytes: p.data.clone(),
+            })
+            .collect(),
+        path: path.to_path_buf(),
+        lossless: true,
+        sample_rate: Some(stream.sample_rate).filter(|&r| r > 0),
+        bits_per_sample: Some(1),
+        num_channels: Some(stream.num_channels).filter(|&c| c > 0),
+        bitrate: Some(stream.sample_rate / 1000 * stream.num_channels as u32).filter(|&b| b > 0),
+        total_samples: Some(stream.total_samples).filter(|&t| t > 0),
+        encoder_delay: None,
+        encoder_padding: None,
+        year: tag.year(),
+        disc: tag.disc(),
+        track_total: tag.total_tracks().filter(|&t| t > 0),
+        disc_total: tag.total_discs().filter(|&t| t > 0),
+        disc_subtitle: tag.text_for_frame_id("TSST").map(|s| s.to_string()),
+        mbid_artist: txxx("MusicBrainz Album Artist Id").or_else(|| txxx("MusicBrainz Artist Id")),
+        mbid_album: txxx("MusicBrainz Album Id"),
+        mbid_track: txxx("MusicBrainz Track Id"),
+        composer: tag.text_for_frame_id("TCOM").map(|s| s.to_string()),
+        isrc: tag.text_for_frame_id("TSRC").map(|s| s.to_string()),
+        bpm: tag
+            .text_for_frame_id("TBPM")
+            .and_then(|s| s.parse::<u32>().ok()),
+        copyright: tag.text_for_frame_id("TCOP").map(|s| s.to_string()),
+        label: tag.text_for_frame_id("TPUB").map(|s| s.to_string()),
+        lyrics: crate::metadata::lyrics::from_id3(&tag),
+        replay_gain: crate::metadata::replaygain::from_id3(&tag),
+        cue: None,
+        compilation: tag
+            .text_for_frame_id("TCMP")
+            .map(super::parse_flag)
+            .unwrap_or(false),
+        classical: crate::metadata::classical::from_id3(&tag),
+        credits: crate::metadata::credits::from_id3(&tag),
+        explicit: super::id3_advisory(&tag),
+    })
+}
+
+/// Scans a DSF file for metadata from its trailing ID3v2 tag and `fmt ` chunk.
+pub async fn scan_dsf(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Result<AudioMetadata> {
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let info = read_dsf(&mut file)
+        .map_err(|e| anyhow::anyhow!("failed to read DSF chunks from {:?}: {}", path, e))?;
+    dsd_metadata(path, info, cfg)
+}
+
+/// Scans a DFF (DSDIFF) file for metadata from its `ID3 `, `DIIN` and `PROP` chunks.
+pub async fn scan_dff(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Result<AudioMetadata> {
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let info = read_dff(&mut file)
+        .map_err(|e| anyhow::anyhow!("failed to read DFF chunks from {:?}: {}", path, e))?;
+    dsd_metadata(path, info, cfg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dsf(metadata_offset: u64) -> Vec<u8> {
+        let mut f = b"DSD ".to_vec();
+        f.extend_from_slice(&28u64.to_le_bytes());
+        f.extend_from_slice(&0u64.to_le_bytes());
+        f.extend_from_slice(&metadata_offset.to_le_bytes());
+        f.extend_from_slice(b"fmt ");
+        f.extend_from_slice(&52u64.to_le_bytes());
+        for v in [1u32, 0, 2, 2, 2_822_400, 1] {
+            f.extend_from_slice(&v.to_le_bytes());
+        }
+        f.extend_from_slice(&(2_822_400u64 * 3).to_le_bytes());
+        f.extend_from_slice(&4096u32.to_le_bytes());
+        f.extend_from_slice(&0u32.to_le_bytes());
+        f
+    }
+
+    #[test]
+    fn reads_dsf_fmt_chunk() {
+        let info = read_dsf(&mut Cursor::new(dsf(0))).unwrap();
+        assert_eq!(
+            info.stream,
+            DsdStream {
+                sample_rate: 2_822_400,
+                num_channels: 2,
+                total_samples: 2_822_400 * 3,
+            }
+        );
+        assert_eq!(info.id3, None);
+    }
+
+    fn dff_chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut c = id.to_vec();
+        c.extend_from_slice(&(body.len() as u64).to_be_bytes());
+        c.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            c.push(0);
+        }
+        c
+    }
+
+    #[test]
+    fn reads_dff_chunks() {
+        let mut prop = b"SND ".to_vec();
+        prop.extend(dff_chunk(b"FS  ", &5_644_800u32.to_be_bytes()));
+        prop.extend(dff_chunk(
+            b"CHNL",
+            &[0, 2, b'S', b'L', b'F', b'T', b'S', b'R', b'G', b'T'],
+        ));
+        let mut title = 5u32.to_be_bytes().to_vec();
+        title.extend_from_slice(b"Sound");
+        let diin = dff_chunk(b"DITI", &title);
+
+        let mut body = b"DSD ".to_vec();
+        body.extend(dff_chunk(b"FVER", &[1, 5, 0, 0]));
+        body.extend(dff_chunk(b"PROP", &prop));
+        body.extend(dff_chunk(b"DIIN", &diin));
+        // two seconds of stereo DSD128
+        body.extend(dff_chunk(b"DSD ", &vec![0x69; 5_644_800 / 8 * 2 * 2]));
+        let mut file = b"FRM8".to_vec();
+        file.extend_from_slice(&(body.len() as u64).to_be_bytes());
+        file.extend(body);
+
+        let info = read_dff(&mut Cursor::new(file)).unwrap();
+        assert_eq!(info.stream.sample_rate, 5_644_800);
+        assert_eq!(info.stream.num_channels, 2);
+        assert_eq!(info.stream.total_samples, 5_644_800 * 2);
+        assert_eq!(info.title.as_deref(), Some("Sound"));
+    }
+}
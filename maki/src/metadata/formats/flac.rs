@@ -3,7 +3,16 @@ use metaflac::block::PictureType;
 use crate::{
     config::Config,
     helpers::split_artists,
-    metadata::{AudioMetadata, Picture, StreamInfo},
+    metadata::{
+        classical::Classical,
+        credits,
+        formats::{
+            parse_index, parse_total_fields, samples_to_ms, DISC_TOTAL_KEYS, TRACK_TOTAL_KEYS,
+        },
+        lyrics::Lyrics,
+        replaygain::ReplayGain,
+        AudioMetadata, Picture, StreamInfo,
+    },
 };
 
 trait IntoStringPictureType {
@@ -57,10 +66,10 @@ pub async fn scan_flac(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Resul
         },
         _ => anyhow::bail!("failed to read FLAC stream info from {:?}", path),
     };
-    let duration = stream_info
+    let duration_ms = stream_info
         .total_samples
         .zip(stream_info.sample_rate)
-        .map(|(total, rate)| (total as u32).saturating_div(rate))
+        .map(|(total, rate)| samples_to_ms(total, rate))
         .unwrap_or(0);
 
     let parse_year = |v: &Vec<String>| -> Option<i32> {
@@ -121,7 +130,8 @@ pub async fn scan_flac(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Resul
             .and_then(|v| v.first().cloned())
             .unwrap_or_default(),
         number: vorbis.track().unwrap_or(0),
-        duration,
+        duration: duration_ms / 1000,
+        duration_ms,
         album: vorbis
             .album()
             .and_then(|v| v.first().cloned())
@@ -134,18 +144,26 @@ pub async fn scan_flac(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Resul
         album_sort: vorbis
             .get("ALBUMSORT")
             .and_then(|d| d[0].parse::<String>().ok()),
+        artist_sort: first_str("ARTISTSORT"),
+        album_artist_sort: first_str("ALBUMARTISTSORT"),
         artists,
+        artist_tag: first_str("ARTIST"),
         genre: vorbis.genre().map(|v| v.to_owned()),
         picture,
         path: path.to_owned(),
         year,
         lossless: true,
-        disc: vorbis
-            .get("DISCNUMBER")
-            .and_then(|d| d[0].parse::<u32>().ok()),
+        disc: first_str("DISCNUMBER").and_then(|d| parse_index(&d)),
+        track_total: parse_total_fields(first_str, &TRACK_TOTAL_KEYS, &["TRACKNUMBER"]),
+        disc_total: parse_total_fields(first_str, &DISC_TOTAL_KEYS, &["DISCNUMBER"]),
+        disc_subtitle: first_str("DISCSUBTITLE"),
         sample_rate: stream_info.sample_rate,
         bits_per_sample: stream_info.bits_per_sample,
         num_channels: stream_info.num_channels,
+        bitrate: None,
+        total_samples: stream_info.total_samples.filter(|&t| t > 0),
+        encoder_delay: None,
+        encoder_padding: None,
         mbid_artist,
         mbid_album,
         mbid_track,
@@ -154,6 +172,17 @@ pub async fn scan_flac(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Resul
         bpm: first_str("BPM").and_then(|s| s.parse::<u32>().ok()),
         copyright: first_str("COPYRIGHT"),
         label: first_str("LABEL").or_else(|| first_str("ORGANIZATION")),
+        lyrics: first_str("LYRICS")
+            .or_else(|| first_str("UNSYNCEDLYRICS"))
+            .and_then(|l| Lyrics::from_tag(&l)),
+        replay_gain: ReplayGain::from_fields(first_str),
+        cue: None,
+        compilation: first_str("COMPILATION")
+            .map(|c| super::parse_flag(&c))
+            .unwrap_or(false),
+        classical: Classical::from_fields(first_str),
+        credits: credits::from_fields(|key| vorbis.get(key).cloned()),
+        explicit: super::advisory_fields(first_str),
     };
 
     Ok(metadata)
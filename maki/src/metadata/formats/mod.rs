@@ -1,7 +1,93 @@
 pub mod aiff;
+pub mod apev2;
+pub mod dsd;
 pub mod flac;
+pub mod monkeys;
 pub mod mp3;
+pub mod mp4;
+pub mod musepack;
+pub mod ogg;
 pub mod wav;
+pub mod wavpack;
+
+/// Convert a sample count to milliseconds, rounded to the nearest millisecond.
+pub fn samples_to_ms(samples: u64, sample_rate: u32) -> u32 {
+    if sample_rate == 0 {
+        return 0;
+    }
+    ((samples * 1000 + sample_rate as u64 / 2) / sample_rate as u64) as u32
+}
+
+/// Parse a boolean tag such as `TCMP` or `COMPILATION`, which taggers write as `1`, `true` or `yes`.
+pub fn parse_flag(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes"
+    )
+}
+
+/// Parse a parental advisory: `Some(true)` for explicit, `Some(false)` for clean and `None`
+/// when unrated. iTunes writes `1` (or `4`) for explicit and `2` for clean; other taggers
+/// write words or booleans.
+pub fn parse_advisory(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "4" | "explicit" | "true" | "yes" => Some(true),
+        "2" | "clean" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Read the advisory from `ITUNESADVISORY` or `EXPLICIT` fields, as found in Vorbis
+/// comments and APEv2 items.
+pub fn advisory_fields<F: Fn(&str) -> Option<String>>(get: F) -> Option<bool> {
+    ["ITUNESADVISORY", "EXPLICIT"]
+        .iter()
+        .find_map(|k| get(k).and_then(|v| parse_advisory(&v)))
+}
+
+/// Read the advisory from the `TXXX:ITUNESADVISORY` frame written by iTunes and Picard.
+pub fn id3_advisory(tag: &id3::Tag) -> Option<bool> {
+    tag.extended_texts()
+        .find(|t| t.description.eq_ignore_ascii_case("ITUNESADVISORY"))
+        .and_then(|t| parse_advisory(&t.value))
+}
+
+/// Parse the leading number of a "3" or "3/12" style field.
+pub fn parse_index(v: &str) -> Option<u32> {
+    v.split('/')
+        .next()
+        .and_then(|n| n.trim().parse::<u32>().ok())
+}
+
+/// Parse the total of a "3/12" style field.
+pub fn parse_total(v: &str) -> Option<u32> {
+    v.split('/')
+        .nth(1)
+        .and_then(|n| n.trim().parse::<u32>().ok())
+}
+
+/// Fields holding the number of tracks on a disc, as written by different taggers.
+pub const TRACK_TOTAL_KEYS: [&str; 2] = ["TRACKTOTAL", "TOTALTRACKS"];
+/// Fields holding the number of discs in a release.
+pub const DISC_TOTAL_KEYS: [&str; 2] = ["DISCTOTAL", "TOTALDISCS"];
+
+/// Read a track or disc total from the first of `total_keys` holding a number, falling
+/// back to the total of a "3/12" style field in `index_keys`.
+pub fn parse_total_fields<F: Fn(&str) -> Option<String>>(
+    get: F,
+    total_keys: &[&str],
+    index_keys: &[&str],
+) -> Option<u32> {
+    total_keys
+        .iter()
+        .find_map(|k| get(k).and_then(|v| v.trim().parse::<u32>().ok()))
+        .or_else(|| {
+            index_keys
+                .iter()
+                .find_map(|k| get(k).and_then(|v| parse_total(&v)))
+        })
+        .filter(|&t| t > 0)
+}
 
 /// Convert seconds to a string in the format "hh:mm:ss"
 /// If the duration is less than an hour, it will be in the format "mm:ss"
@@ -23,3 +109,51 @@ pub fn s2hms(secs: u32) -> String {
 
     formatted_duration
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_boolean_flags() {
+        assert!(parse_flag("1"));
+        assert!(parse_flag(" True "));
+        assert!(!parse_flag("0"));
+        assert!(!parse_flag(""));
+    }
+
+    #[test]
+    fn parses_advisories() {
+        assert_eq!(parse_advisory("1"), Some(true));
+        assert_eq!(parse_advisory("4"), Some(true));
+        assert_eq!(parse_advisory(" Clean"), Some(false));
+        assert_eq!(parse_advisory("0"), None);
+        assert_eq!(
+            advisory_fields(|k| (k == "EXPLICIT").then(|| "yes".to_string())),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn reads_totals_from_either_field() {
+        fn total(fields: &[(&str, &str)]) -> Option<u32> {
+            parse_total_fields(
+                |key| {
+                    fields
+                        .iter()
+                        .find(|(k, _)| *k == key)
+                        .map(|(_, v)| v.to_string())
+                },
+                &TRACK_TOTAL_KEYS,
+                &["TRACKNUMBER"],
+            )
+        }
+        assert_eq!(total(&[("TOTALTRACKS", "12")]), Some(12));
+        assert_eq!(total(&[("TRACKNUMBER", "3/14")]), Some(14));
+        assert_eq!(
+            total(&[("TRACKTOTAL", "x"), ("TRACKNUMBER", "3/9")]),
+            Some(9)
+        );
+        assert_eq!(total(&[("TRACKNUMBER", "3"), ("TOTALTRACKS", "0")]), None);
+    }
+}
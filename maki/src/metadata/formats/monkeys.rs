@@ -0,0 +1,155 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::{
+    config::Config,
+    metadata::{
+        formats::apev2::{ape_metadata, skip_id3v2, ApeStream, ApeTag},
+        AudioMetadata,
+    },
+};
+
+/// First version with a separate descriptor ahead of the header.
+const DESCRIPTOR_VERSION: u16 = 3980;
+
+fn le_u16(b: &[u8], at: usize) -> Option<u16> {
+    b.get(at..at + 2).map(|s| u16::from_le_bytes([s[0], s[1]]))
+}
+
+fn le_u32(b: &[u8], at: usize) -> Option<u32> {
+    b.get(at..at + 4)
+        .map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+}
+
+/// Parse a `MAC ` header. Newer files describe their layout in a descriptor and
+/// store the frame size; older ones imply it from the version and compression level.
+fn parse_header(b: &[u8]) -> anyhow::Result<ApeStream> {
+    if !b.starts_with(b"MAC ") {
+        anyhow::bail!("missing MAC header");
+    }
+    let truncated = || anyhow::anyhow!("Monkey's Audio header is truncated");
+    let version = le_u16(b, 4).ok_or_else(truncated)?;
+
+    let (bits_per_sample, num_channels, sample_rate, blocks_per_frame, final_blocks, frames) =
+        if version >= DESCRIPTOR_VERSION {
+            let at = le_u32(b, 8).ok_or_else(truncated)? as usize;
+            let h = b.get(at..at + 24).ok_or_else(truncated)?;
+            (
+                le_u16(h, 16).unwrap_or_default(),
+                le_u16(h, 18).unwrap_or_default(),
+                le_u32(h, 20).unwrap_or_default(),
+                le_u32(h, 4).unwrap_or_default(),
+                le_u32(h, 8).unwrap_or_default(),
+                le_u32(h, 12).unwrap_or_default(),
+            )
+        } else {
+            let h = b.get(0..32).ok_or_else(truncated)?;
+            let compression = le_u16(h, 6).unwrap_or_default();
+            let flags = le_u16(h, 8).unwrap_or_default();
+            let bits = if flags & 0x01 != 0 {
+                8
+            } else if flags & 0x08 != 0 {
+                24
+            } else {
+                16
+            };
+            let blocks_per_frame = if version >= 3950 {
+                73_728 * 4
+            } else if version >= 3900 || (version >= 3800 && compression == 4000) {
+                73_728
+            } else {
+                9_216
+            };
+            (
+                bits,
+                le_u16(h, 10).unwrap_or_default(),
+                le_u32(h, 12).unwrap_or_default(),
+                blocks_per_frame,
+                le_u32(h, 28).unwrap_or_default(),
+                le_u32(h, 24).unwrap_or_default(),
+            )
+        };
+
+    // every frame is full except the last
+    let total_samples = match frames {
+        0 => 0,
+        n => (n as u64 - 1) * blocks_per_frame as u64 + final_blocks as u64,
+    };
+    Ok(ApeStream {
+        sample_rate,
+        bits_per_sample: Some(bits_per_sample as u8),
+        num_channels: num_channels as u8,
+        total_samples,
+        lossless: true,
+    })
+}
+
+/// Scans a Monkey's Audio file for metadata from its APEv2 tag and `MAC ` header.
+pub async fn scan_monkeys(
+    path: &std::path::PathBuf,
+    cfg: &Config,
+) -> anyhow::Result<AudioMetadata> {
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    skip_id3v2(&mut file)?;
+    let mut header = Vec::with_capacity(256);
+    (&mut file).take(256).read_to_end(&mut header)?;
+    let stream = parse_header(&header).map_err(|e| {
+        anyhow::anyhow!(
+            "failed to read Monkey's Audio header from {:?}: {}",
+            path,
+            e
+        )
+    })?;
+    let tag = ApeTag::read(&mut file)
+        .map_err(|e| anyhow::anyhow!("failed to read APE tag from {:?}: {}", path, e))?;
+    let file_len = file.seek(SeekFrom::End(0))?;
+    Ok(ape_metadata(path, tag, stream, file_len, cfg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_descriptor_header() {
+        let mut b = b"MAC ".to_vec();
+        b.extend_from_slice(&3990u16.to_le_bytes());
+        b.extend_from_slice(&0u16.to_le_bytes());
+        // descriptor length, then the rest of the 52 byte descriptor
+        b.extend_from_slice(&52u32.to_le_bytes());
+        b.resize(52, 0);
+        b.extend_from_slice(&2000u16.to_le_bytes());
+        b.extend_from_slice(&0u16.to_le_bytes());
+        for v in [73_728u32 * 4, 1_000, 3] {
+            b.extend_from_slice(&v.to_le_bytes());
+        }
+        b.extend_from_slice(&24u16.to_le_bytes());
+        b.extend_from_slice(&2u16.to_le_bytes());
+        b.extend_from_slice(&96_000u32.to_le_bytes());
+
+        assert_eq!(
+            parse_header(&b).unwrap(),
+            ApeStream {
+                sample_rate: 96_000,
+                bits_per_sample: Some(24),
+                num_channels: 2,
+                total_samples: 2 * 73_728 * 4 + 1_000,
+                lossless: true,
+            }
+        );
+    }
+
+    #[test]
+    fn reads_legacy_header() {
+        let mut b = b"MAC ".to_vec();
+        for v in [3950u16, 2000, 0, 1] {
+            b.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in [44_100u32, 0, 0, 2, 500] {
+            b.extend_from_slice(&v.to_le_bytes());
+        }
+        let stream = parse_header(&b).unwrap();
+        assert_eq!(stream.bits_per_sample, Some(16));
+        assert_eq!(stream.num_channels, 1);
+        assert_eq!(stream.total_samples, 73_728 * 4 + 500);
+    }
+}
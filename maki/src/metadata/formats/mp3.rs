@@ -1,13 +1,306 @@
-use std::time::Duration;
+use std::{
+    io::{Read, Seek, SeekFrom},
+    time::Duration,
+};
 
 use crate::{
     config::Config,
     helpers::{sort_string, split_artists},
-    metadata::{AudioMetadata, Picture},
+    metadata::{formats::samples_to_ms, AudioMetadata, Picture},
 };
 
 use id3::{partial_tag_ok, Tag, TagLike};
 
+/// Stream properties read from the MPEG frame headers and any Xing/Info/VBRI/LAME tag.
+#[derive(Debug, PartialEq)]
+pub struct MpegInfo {
+    pub sample_rate: u32,
+    pub num_channels: u8,
+    /// Average bitrate in kbps
+    pub bitrate: u32,
+    /// Playable samples per channel with encoder delay and padding removed, when known
+    pub total_samples: Option<u64>,
+    /// Samples to drop from the start of the decoded output (includes decoder delay)
+    pub encoder_delay: Option<u32>,
+    /// Samples to drop from the end of the decoded output
+    pub encoder_padding: Option<u32>,
+}
+
+/// A parsed MPEG audio frame header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FrameHeader {
+    /// 1 for MPEG-1, 2 for MPEG-2, 25 for MPEG-2.5
+    version: u8,
+    layer: u8,
+    bitrate: u32,
+    sample_rate: u32,
+    mono: bool,
+    frame_len: usize,
+    samples_per_frame: u32,
+}
+
+const BITRATES_V1: [[u32; 15]; 3] = [
+    [
+        0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+    ],
+    [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ],
+];
+const BITRATES_V2: [[u32; 15]; 2] = [
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+    ],
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+
+/// Decoder delay added by every standard MP3 decoder on top of the encoder delay.
+const DECODER_DELAY: u32 = 529;
+
+impl FrameHeader {
+    fn parse(b: &[u8]) -> Option<Self> {
+        if b.len() < 4 || b[0] != 0xFF || b[1] & 0xE0 != 0xE0 {
+            return None;
+        }
+        let version = match (b[1] >> 3) & 0x03 {
+            0 => 25,
+            2 => 2,
+            3 => 1,
+            _ => return None,
+        };
+        let layer = match (b[1] >> 1) & 0x03 {
+            1 => 3,
+            2 => 2,
+            3 => 1,
+            _ => return None,
+        };
+        let bitrate_index = (b[2] >> 4) as usize;
+        // free format (0) is legal but we cannot size frames without it
+        if bitrate_index == 0 || bitrate_index == 0x0F {
+            return None;
+        }
+        let bitrate = if version == 1 {
+            BITRATES_V1[layer as usize - 1][bitrate_index]
+        } else {
+            BITRATES_V2[if layer == 1 { 0 } else { 1 }][bitrate_index]
+        };
+        let base_rate = match (b[2] >> 2) & 0x03 {
+            0 => 44100,
+            1 => 48000,
+            2 => 32000,
+            _ => return None,
+        };
+        let sample_rate = match version {
+            1 => base_rate,
+            2 => base_rate / 2,
+            _ => base_rate / 4,
+        };
+        let padding = ((b[2] >> 1) & 0x01) as usize;
+        let mono = (b[3] >> 6) == 0x03;
+
+        let samples_per_frame = match (layer, version) {
+            (1, _) => 384,
+            (2, _) | (3, 1) => 1152,
+            _ => 576,
+        };
+        let frame_len = if layer == 1 {
+            (12 * bitrate as usize * 1000 / sample_rate as usize + padding) * 4
+        } else {
+            samples_per_frame as usize / 8 * bitrate as usize * 1000 / sample_rate as usize
+                + padding
+        };
+
+        Some(FrameHeader {
+            version,
+            layer,
+            bitrate,
+            sample_rate,
+            mono,
+            frame_len,
+            samples_per_frame,
+        })
+    }
+
+    /// Offset of a Xing/Info tag from the start of the frame (header plus side info).
+    fn xing_offset(&self) -> usize {
+        match (self.version == 1, self.mono) {
+            (true, false) => 4 + 32,
+            (true, true) => 4 + 17,
+            (false, false) => 4 + 17,
+            (false, true) => 4 + 9,
+        }
+    }
+}
+
+fn be_u32(b: &[u8], at: usize) -> Option<u32> {
+    b.get(at..at + 4)
+        .map(|s| u32::from_be_bytes([s[0], s[1], s[2], s[3]]))
+}
+
+/// Find the first frame header in `buf` that is followed by another valid frame,
+/// which weeds out false sync words inside album art or junk data.
+fn find_first_frame(buf: &[u8]) -> Option<(usize, FrameHeader)> {
+    (0..buf.len().saturating_sub(4)).find_map(|pos| {
+        let header = FrameHeader::parse(&buf[pos..])?;
+        match buf.get(pos + header.frame_len..) {
+            Some(next) if next.len() >= 4 => {
+                let next = FrameHeader::parse(next)?;
+                (next.version == header.version
+                    && next.layer == header.layer
+                    && next.sample_rate == header.sample_rate)
+                    .then(|| (pos, header))
+            }
+            // a single frame at the end of the buffer is the best we can do
+            _ => Some((pos, header)),
+        }
+    })
+}
+
+/// Parse the Xing/Info/VBRI header (and the LAME extension) from the first frame.
+/// Returns `(frames, bytes, encoder_delay, encoder_padding)`.
+fn parse_vbr_header(
+    frame: &[u8],
+    header: &FrameHeader,
+) -> Option<(Option<u32>, Option<u32>, Option<u32>, Option<u32>)> {
+    let xing_at = header.xing_offset();
+    if let Some(tag) = frame.get(xing_at..xing_at + 4) {
+        if tag == b"Xing" || tag == b"Info" {
+            let flags = be_u32(frame, xing_at + 4)?;
+            let mut at = xing_at + 8;
+            let mut frames = None;
+            let mut bytes = None;
+            if flags & 0x01 != 0 {
+                frames = be_u32(frame, at);
+                at += 4;
+            }
+            if flags & 0x02 != 0 {
+                bytes = be_u32(frame, at);
+                at += 4;
+            }
+            if flags & 0x04 != 0 {
+                at += 100;
+            }
+            if flags & 0x08 != 0 {
+                at += 4;
+            }
+
+            // LAME extension: encoder (9), revision (1), lowpass (1), peak (4),
+            // radio gain (2), audiophile gain (2), flags (1), abr (1), delay/padding (3)
+            let (delay, padding) = match frame.get(at..at + 24) {
+                Some(lame) if lame[0..4].iter().all(|b| b.is_ascii_alphanumeric()) => {
+                    let d = &lame[21..24];
+                    let delay = ((d[0] as u32) << 4) | ((d[1] as u32) >> 4);
+                    let padding = (((d[1] & 0x0F) as u32) << 8) | d[2] as u32;
+                    (Some(delay), Some(padding))
+                }
+                _ => (None, None),
+            };
+            return Some((frames, bytes, delay, padding));
+        }
+    }
+
+    // VBRI always lives 32 bytes after the header
+    if frame.get(36..40) == Some(&b"VBRI"[..]) {
+        let delay = frame
+            .get(42..44)
+            .map(|d| u16::from_be_bytes([d[0], d[1]]) as u32);
+        return Some((be_u32(frame, 50), be_u32(frame, 46), delay, None));
+    }
+
+    None
+}
+
+/// Read MPEG stream properties from an MP3 file.
+pub fn read_mpeg_info(path: &std::path::Path) -> anyhow::Result<MpegInfo> {
+    let mut file = std::fs::File::open(path)?;
+    let file_len = file.metadata()?.len();
+
+    // skip the ID3v2 tag, if any
+    let mut id3 = [0u8; 10];
+    file.read_exact(&mut id3)?;
+    let audio_start = if &id3[0..3] == b"ID3" {
+        let size = id3[6..10]
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 7) | (b & 0x7F) as u64);
+        let footer = if id3[5] & 0x10 != 0 { 10 } else { 0 };
+        10 + size + footer
+    } else {
+        0
+    };
+    file.seek(SeekFrom::Start(audio_start))?;
+
+    let mut buf = Vec::with_capacity(64 * 1024);
+    (&mut file).take(64 * 1024).read_to_end(&mut buf)?;
+    let (offset, header) =
+        find_first_frame(&buf).ok_or_else(|| anyhow::anyhow!("no MPEG frame found"))?;
+    let frame = &buf[offset..(offset + header.frame_len).min(buf.len())];
+    let first_frame_at = audio_start + offset as u64;
+
+    // an ID3v1 tag occupies the last 128 bytes
+    let mut tail = [0u8; 3];
+    let id3v1 = if file_len >= 128 {
+        file.seek(SeekFrom::End(-128))?;
+        file.read_exact(&mut tail)?;
+        if &tail == b"TAG" {
+            128
+        } else {
+            0
+        }
+    } else {
+        0
+    };
+    let audio_bytes = file_len.saturating_sub(first_frame_at + id3v1);
+
+    let spf = header.samples_per_frame as u64;
+    let info = match parse_vbr_header(frame, &header) {
+        Some((frames, bytes, delay, padding)) => {
+            // the Xing frame itself carries no audio
+            let frames = frames.map(|f| f as u64).unwrap_or_else(|| {
+                audio_bytes.saturating_sub(header.frame_len as u64) / header.frame_len as u64
+            });
+            let raw_samples = frames * spf;
+            let trim = delay.unwrap_or(0) as u64 + padding.unwrap_or(0) as u64;
+            let total_samples = raw_samples.saturating_sub(trim);
+            // LAME values are relative to the encoder input; shift them to decoded output
+            let delay = delay.map(|d| d + DECODER_DELAY);
+            let padding = padding.map(|p| p.saturating_sub(DECODER_DELAY));
+            let stream_bytes = bytes
+                .map(|b| b as u64)
+                .unwrap_or_else(|| audio_bytes.saturating_sub(header.frame_len as u64));
+            let bitrate = if raw_samples > 0 {
+                (stream_bytes * 8 * header.sample_rate as u64 / raw_samples / 1000) as u32
+            } else {
+                header.bitrate
+            };
+            MpegInfo {
+                sample_rate: header.sample_rate,
+                num_channels: if header.mono { 1 } else { 2 },
+                bitrate,
+                total_samples: Some(total_samples),
+                encoder_delay: delay,
+                encoder_padding: padding,
+            }
+        }
+        None => {
+            // plain CBR: count frames from the stream size
+            let frames = audio_bytes / header.frame_len as u64;
+            MpegInfo {
+                sample_rate: header.sample_rate,
+                num_channels: if header.mono { 1 } else { 2 },
+                bitrate: header.bitrate,
+                total_samples: Some(frames * spf),
+                encoder_delay: None,
+                encoder_padding: None,
+            }
+        }
+    };
+
+    Ok(info)
+}
+
 /// Scans an mp3 file for metadata and returns an `AudioMetadata` struct.
 pub async fn scan_mp3(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Result<AudioMetadata> {
     let tag_rs = Tag::read_from_path(path);
@@ -56,18 +349,39 @@ pub async fn scan_mp3(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Result
     let copyright = tag.text_for_frame_id("TCOP").map(|s| s.to_string());
     let label = tag.text_for_frame_id("TPUB").map(|s| s.to_string());
 
-    let meta = AudioMetadata {
-        name: tag.title().unwrap_or_default().to_string(),
-        number: tag.track().unwrap_or(25565),
-        duration: tag.duration().unwrap_or_else(|| {
+    let stream = match read_mpeg_info(path) {
+        Ok(info) => Some(info),
+        Err(e) => {
+            tracing::warn!("failed to read MPEG stream info from {:?}: {}", path, e);
+            None
+        }
+    };
+    // prefer the exact sample count, then TLEN (milliseconds), then estimate from the stream
+    let duration_ms = stream
+        .as_ref()
+        .and_then(|s| {
+            s.total_samples
+                .map(|total| samples_to_ms(total, s.sample_rate))
+        })
+        .or_else(|| tag.duration())
+        .unwrap_or_else(|| {
             mp3_duration::from_path(path)
                 .unwrap_or(Duration::ZERO)
-                .as_secs() as u32
-        }),
+                .as_millis() as u32
+        });
+
+    let meta = AudioMetadata {
+        name: tag.title().unwrap_or_default().to_string(),
+        number: tag.track().unwrap_or(0),
+        duration: duration_ms / 1000,
+        duration_ms,
         album: tag.album().unwrap_or_default().to_string(),
         album_artist: tag.album_artist().unwrap_or_default().to_string(),
         album_sort: sort_string(tag.album()),
+        artist_sort: tag.text_for_frame_id("TSOP").map(|s| s.to_string()),
+        album_artist_sort: tag.text_for_frame_id("TSO2").map(|s| s.to_string()),
         artists,
+        artist_tag: tag.artist().map(|s| s.to_string()),
         genre,
         picture: tag
             .pictures()
@@ -78,11 +392,18 @@ pub async fn scan_mp3(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Result
             .collect(),
         path: path.to_path_buf(),
         lossless: false,
-        sample_rate: None,
+        sample_rate: stream.as_ref().map(|s| s.sample_rate),
         bits_per_sample: None,
-        num_channels: None,
+        num_channels: stream.as_ref().map(|s| s.num_channels),
+        bitrate: stream.as_ref().map(|s| s.bitrate),
+        total_samples: stream.as_ref().and_then(|s| s.total_samples),
+        encoder_delay: stream.as_ref().and_then(|s| s.encoder_delay),
+        encoder_padding: stream.as_ref().and_then(|s| s.encoder_padding),
         year: tag.year(),
         disc: tag.disc(),
+        track_total: tag.total_tracks().filter(|&t| t > 0),
+        disc_total: tag.total_discs().filter(|&t| t > 0),
+        disc_subtitle: tag.text_for_frame_id("TSST").map(|s| s.to_string()),
         mbid_artist,
         mbid_album,
         mbid_track,
@@ -91,12 +412,22 @@ pub async fn scan_mp3(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Result
         bpm,
         copyright,
         label,
+        lyrics: crate::metadata::lyrics::from_id3(&tag),
+        replay_gain: crate::metadata::replaygain::from_id3(&tag),
+        cue: None,
+        compilation: tag
+            .text_for_frame_id("TCMP")
+            .map(super::parse_flag)
+            .unwrap_or(false),
+        classical: crate::metadata::classical::from_id3(&tag),
+        credits: crate::metadata::credits::from_id3(&tag),
+        explicit: super::id3_advisory(&tag),
     };
 
     Ok(meta)
 }
 
-const ID3V1_GENRES: [&str; 192] = [
+pub(crate) const ID3V1_GENRES: [&str; 192] = [
     "Blues",
     "Classic Rock",
     "Country",
@@ -306,3 +637,44 @@ fn match_id3v1_genres(input: &[&str]) -> Option<Vec<String>> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_frame_header() {
+        // MPEG-1 layer III, 128 kbps, 44.1 kHz, joint stereo
+        let header = FrameHeader::parse(&[0xFF, 0xFB, 0x90, 0x64]).unwrap();
+        assert_eq!(header.version, 1);
+        assert_eq!(header.layer, 3);
+        assert_eq!(header.bitrate, 128);
+        assert_eq!(header.sample_rate, 44100);
+        assert!(!header.mono);
+        assert_eq!(header.frame_len, 417);
+        assert_eq!(header.samples_per_frame, 1152);
+    }
+
+    #[test]
+    fn parses_lame_delay_and_padding() {
+        let header = FrameHeader::parse(&[0xFF, 0xFB, 0x90, 0x64]).unwrap();
+        let mut frame = vec![0u8; header.frame_len];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        let at = header.xing_offset();
+        frame[at..at + 4].copy_from_slice(b"Info");
+        // frames + bytes present
+        frame[at + 4..at + 8].copy_from_slice(&3u32.to_be_bytes());
+        frame[at + 8..at + 12].copy_from_slice(&1000u32.to_be_bytes());
+        frame[at + 12..at + 16].copy_from_slice(&417_000u32.to_be_bytes());
+        let lame = at + 16;
+        frame[lame..lame + 9].copy_from_slice(b"LAME3.100");
+        // delay 576 (0x240), padding 1260 (0x4EC)
+        frame[lame + 21..lame + 24].copy_from_slice(&[0x24, 0x04, 0xEC]);
+
+        let (frames, bytes, delay, padding) = parse_vbr_header(&frame, &header).unwrap();
+        assert_eq!(frames, Some(1000));
+        assert_eq!(bytes, Some(417_000));
+        assert_eq!(delay, Some(576));
+        assert_eq!(padding, Some(1260));
+    }
+}
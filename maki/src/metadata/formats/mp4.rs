@@ -0,0 +1,434 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::{
+    config::Config,
+    helpers::split_artists,
+    metadata::{
+        classical::Classical, credits, formats::mp3::ID3V1_GENRES, lyrics::Lyrics,
+        replaygain::ReplayGain, AudioMetadata, Picture,
+    },
+};
+
+/// Upper bound for the `moov` box we are willing to load. Embedded artwork lives
+/// in here, so allow generous headroom, but never buffer a whole `mdat`.
+const MAX_MOOV_BYTES: u64 = 128 * 1024 * 1024;
+
+/// Prefix used by iTunes-style freeform (`----`) atoms.
+const ITUNES_MEAN: &str = "com.apple.iTunes";
+
+/// Well-known data types for `data` atoms inside `ilst`.
+const DATA_TYPE_JPEG: u32 = 13;
+const DATA_TYPE_PNG: u32 = 14;
+const DATA_TYPE_BMP: u32 = 27;
+
+#[derive(Default)]
+struct SoundTrack {
+    codec: [u8; 4],
+    timescale: u32,
+    duration: u64,
+    sample_rate: Option<u32>,
+    bits_per_sample: Option<u8>,
+    num_channels: Option<u8>,
+}
+
+/// Iterate over the child boxes contained in `data`, yielding `(type, body)`.
+fn boxes(data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    let mut pos = 0usize;
+    std::iter::from_fn(move || {
+        if pos + 8 > data.len() {
+            return None;
+        }
+        let size32 = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
+        let kind = [data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]];
+        let (header, size) = match size32 {
+            0 => (8, (data.len() - pos) as u64),
+            1 if pos + 16 <= data.len() => {
+                let mut b = [0u8; 8];
+                b.copy_from_slice(&data[pos + 8..pos + 16]);
+                (16, u64::from_be_bytes(b))
+            }
+            1 => return None,
+            n => (8, n as u64),
+        };
+        let end = pos.checked_add(size as usize)?;
+        if size < header as u64 || end > data.len() {
+            return None;
+        }
+        let body = &data[pos + header..end];
+        pos = end;
+        Some((kind, body))
+    })
+}
+
+fn find_box<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(data).find(|(k, _)| k == kind).map(|(_, b)| b)
+}
+
+fn be_u16(b: &[u8], at: usize) -> Option<u16> {
+    b.get(at..at + 2).map(|s| u16::from_be_bytes([s[0], s[1]]))
+}
+
+fn be_u32(b: &[u8], at: usize) -> Option<u32> {
+    b.get(at..at + 4)
+        .map(|s| u32::from_be_bytes([s[0], s[1], s[2], s[3]]))
+}
+
+fn be_u64(b: &[u8], at: usize) -> Option<u64> {
+    b.get(at..at + 8).map(|s| {
+        let mut a = [0u8; 8];
+        a.copy_from_slice(s);
+        u64::from_be_bytes(a)
+    })
+}
+
+/// Locate the top-level `moov` box and read it into memory, skipping `mdat`.
+fn read_moov<R: Read + Seek>(r: &mut R) -> anyhow::Result<Vec<u8>> {
+    let len = r.seek(SeekFrom::End(0))?;
+    let mut pos = 0u64;
+
+    while pos + 8 <= len {
+        r.seek(SeekFrom::Start(pos))?;
+        let mut header = [0u8; 8];
+        r.read_exact(&mut header)?;
+        let size32 = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let (header_len, size) = match size32 {
+            0 => (8, len - pos),
+            1 => {
+                let mut large = [0u8; 8];
+                r.read_exact(&mut large)?;
+                (16, u64::from_be_bytes(large))
+            }
+            n => (8, n as u64),
+        };
+        if size < header_len {
+            anyhow::bail!("invalid box size {} at offset {}", size, pos);
+        }
+
+        if &header[4..8] == b"moov" {
+            let body_len = size - header_len;
+            if body_len > MAX_MOOV_BYTES {
+                anyhow::bail!("moov box is {} bytes, refusing to load", body_len);
+            }
+            let mut body = vec![0u8; body_len as usize];
+            r.read_exact(&mut body)?;
+            return Ok(body);
+        }
+        pos += size;
+    }
+
+    anyhow::bail!("no moov box found")
+}
+
+/// Extract stream properties from the first sound `trak`.
+fn read_sound_track(moov: &[u8]) -> Option<SoundTrack> {
+    boxes(moov)
+        .filter(|(k, _)| k == b"trak")
+        .find_map(|(_, trak)| {
+            let mdia = find_box(trak, b"mdia")?;
+            let hdlr = find_box(mdia, b"hdlr")?;
+            // version/flags (4), pre_defined (4), handler_type (4)
+            if hdlr.get(8..12)? != b"soun" {
+                return None;
+            }
+
+            let mut track = SoundTrack::default();
+            let mdhd = find_box(mdia, b"mdhd")?;
+            if *mdhd.first()? == 1 {
+                track.timescale = be_u32(mdhd, 20)?;
+                track.duration = be_u64(mdhd, 24)?;
+            } else {
+                track.timescale = be_u32(mdhd, 12)?;
+                track.duration = be_u32(mdhd, 16)? as u64;
+            }
+
+            let stbl = find_box(find_box(mdia, b"minf")?, b"stbl")?;
+            let stsd = find_box(stbl, b"stsd")?;
+            // version/flags (4), entry_count (4), then the first sample entry
+            let (codec, entry) = boxes(stsd.get(8..)?).next()?;
+            track.codec = codec;
+            // reserved (6), data_reference_index (2), version (2), revision (2), vendor (4)
+            let version = be_u16(entry, 8)?;
+            track.num_channels = be_u16(entry, 16).map(|c| c as u8);
+            track.bits_per_sample = be_u16(entry, 18).map(|b| b as u8);
+            track.sample_rate = be_u32(entry, 24).map(|r| r >> 16).filter(|&r| r > 0);
+
+            // QuickTime v1/v2 sound descriptions carry extra fields before child boxes
+            let children_at = match version {
+                1 => 28 + 16,
+                2 => 28 + 36,
+                _ => 28,
+            };
+
+            if &codec == b"alac" {
+                // ALACSpecificConfig, after version/flags
+                if let Some(cfg) = entry.get(children_at..).and_then(|c| find_box(c, b"alac")) {
+                    track.bits_per_sample = cfg.get(9).copied();
+                    track.num_channels = cfg.get(13).copied();
+                    track.sample_rate = be_u32(cfg, 24).filter(|&r| r > 0);
+                }
+            } else {
+                // bit depth is meaningless for lossy codecs
+                track.bits_per_sample = None;
+            }
+
+            // fall back to the media timescale, which is the sample rate for audio tracks
+            if track.sample_rate.is_none() {
+                track.sample_rate = Some(track.timescale);
+            }
+
+            Some(track)
+        })
+}
+
+/// A single `data` atom from an `ilst` item.
+struct IlstData {
+    kind: u32,
+    value: Vec<u8>,
+}
+
+/// `ilst` items keyed by atom name; freeform atoms are keyed as `----:mean:name`.
+struct Ilst {
+    items: HashMap<String, Vec<IlstData>>,
+}
+
+impl Ilst {
+    fn parse(moov: &[u8]) -> Self {
+        let mut items: HashMap<String, Vec<IlstData>> = HashMap::new();
+
+        let ilst = find_box(moov, b"udta")
+            .and_then(|udta| find_box(udta, b"meta"))
+            .and_then(|meta| {
+                // ISO meta is a full box, QuickTime meta is not
+                let body = if meta.get(4..8) == Some(&b"hdlr"[..]) {
+                    meta
+                } else {
+                    meta.get(4..)?
+                };
+                find_box(body, b"ilst")
+            });
+
+        for (kind, item) in ilst.into_iter().flat_map(boxes) {
+            // atom names are Latin-1, e.g. 0xA9 'n' 'a' 'm' for ©nam
+            let mut key: String = kind.iter().map(|&b| b as char).collect();
+            if &kind == b"----" {
+                let text = |k: &[u8; 4]| {
+                    find_box(item, k)
+                        .and_then(|b| b.get(4..))
+                        .map(|b| String::from_utf8_lossy(b).into_owned())
+                };
+                key = format!(
+                    "----:{}:{}",
+                    text(b"mean").unwrap_or_default(),
+                    text(b"name").unwrap_or_default()
+                );
+            }
+
+            let values = boxes(item)
+                .filter(|(k, _)| k == b"data")
+                .filter_map(|(_, data)| {
+                    // type indicator (4), locale (4)
+                    Some(IlstData {
+                        kind: be_u32(data, 0)? & 0x00ff_ffff,
+                        value: data.get(8..)?.to_vec(),
+                    })
+                });
+            items.entry(key).or_default().extend(values);
+        }
+
+        Ilst { items }
+    }
+
+    fn strings(&self, key: &str) -> Option<Vec<String>> {
+        let values: Vec<String> = self
+            .items
+            .get(key)?
+            .iter()
+            .map(|d| String::from_utf8_lossy(&d.value).trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if values.is_empty() {
+            None
+        } else {
+            Some(values)
+        }
+    }
+
+    fn text(&self, key: &str) -> Option<String> {
+        self.strings(key).and_then(|v| v.into_iter().next())
+    }
+
+    fn freeform(&self, name: &str) -> Option<String> {
+        self.freeform_strings(name)
+            .and_then(|v| v.into_iter().next())
+    }
+
+    fn freeform_strings(&self, name: &str) -> Option<Vec<String>> {
+        self.strings(&format!("----:{}:{}", ITUNES_MEAN, name))
+    }
+
+    /// Read a `trkn`/`disk` style pair: reserved (2), index (2), total (2).
+    fn index_pair(&self, key: &str) -> Option<(u32, u32)> {
+        let data = self.items.get(key)?.first()?;
+        let index = be_u16(&data.value, 2)? as u32;
+        let total = be_u16(&data.value, 4).unwrap_or(0) as u32;
+        Some((index, total))
+    }
+
+    /// Read a big-endian integer atom such as `tmpo`, `gnre`, `cpil`.
+    fn integer(&self, key: &str) -> Option<u32> {
+        let data = self.items.get(key)?.first()?;
+        match data.value.len() {
+            1 => Some(data.value[0] as u32),
+            2 => be_u16(&data.value, 0).map(|v| v as u32),
+            4 => be_u32(&data.value, 0),
+            _ => None,
+        }
+    }
+}
+
+/// Scans an MP4/M4A file (AAC or ALAC) for metadata and returns an `AudioMetadata` struct.
+pub async fn scan_mp4(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Result<AudioMetadata> {
+    let mut file = std::fs::File::open(path)?;
+    let moov = read_moov(&mut file)
+        .map_err(|e| anyhow::anyhow!("failed to read MP4 atoms from {:?}: {}", path, e))?;
+
+    let track =
+        read_sound_track(&moov).ok_or_else(|| anyhow::anyhow!("no audio track in {:?}", path))?;
+    let ilst = Ilst::parse(&moov);
+
+    let duration_ms = if track.timescale > 0 {
+        (track.duration * 1000 / track.timescale as u64) as u32
+    } else {
+        0
+    };
+
+    // iTunSMPB: " 00000000 <delay> <padding> <total samples> ...", all hex
+    let smpb: Vec<u64> = ilst
+        .freeform("iTunSMPB")
+        .map(|v| {
+            v.split_whitespace()
+                .filter_map(|t| u64::from_str_radix(t, 16).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    let total_samples = smpb.get(3).copied().filter(|&t| t > 0).or_else(|| {
+        let rate = track.sample_rate? as u64;
+        (track.timescale > 0).then(|| track.duration * rate / track.timescale as u64)
+    });
+    let bitrate = match (total_samples, track.sample_rate) {
+        (Some(total), Some(rate)) if total > 0 => {
+            let bits = file.metadata()?.len() * 8 * rate as u64;
+            Some((bits / total / 1000) as u32)
+        }
+        _ => None,
+    };
+
+    let year = ilst
+        .text("\u{a9}day")
+        .and_then(|d| d.split('-').next().and_then(|y| y.parse::<i32>().ok()))
+        .filter(|&y| y > 0);
+
+    // ©gen is free text, gnre is a 1-based ID3v1 index
+    let genre = ilst.strings("\u{a9}gen").or_else(|| {
+        ilst.integer("gnre")
+            .and_then(|g| ID3V1_GENRES.get((g as usize).checked_sub(1)?))
+            .map(|g| vec![g.to_string()])
+    });
+
+    let picture = ilst
+        .items
+        .get("covr")
+        .map(|covers| {
+            covers
+                .iter()
+                .filter(|d| matches!(d.kind, DATA_TYPE_JPEG | DATA_TYPE_PNG | DATA_TYPE_BMP | 0))
+                .map(|d| Picture {
+                    picture_type: "Cover (Front)".to_string(),
+                    bytes: d.value.clone(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let unk_vec = vec!["Unknown".to_string()];
+    let artists = match ilst.strings(&format!("----:{}:ARTISTS", ITUNES_MEAN)) {
+        Some(a) => a,
+        None => split_artists(
+            &ilst
+                .strings("\u{a9}ART")
+                .or_else(|| ilst.strings("aART"))
+                .unwrap_or(unk_vec),
+            &cfg.artist_split_exceptions,
+        ),
+    };
+
+    let metadata = AudioMetadata {
+        name: ilst.text("\u{a9}nam").unwrap_or_default(),
+        number: ilst.index_pair("trkn").map(|(n, _)| n).unwrap_or(0),
+        duration: duration_ms / 1000,
+        duration_ms,
+        album: ilst.text("\u{a9}alb").unwrap_or_default(),
+        album_artist: ilst
+            .text("aART")
+            .or_else(|| ilst.text("\u{a9}ART"))
+            .unwrap_or_default(),
+        album_sort: ilst.text("soal"),
+        artist_sort: ilst.text("soar"),
+        album_artist_sort: ilst.text("soaa"),
+        artists,
+        artist_tag: ilst.text("\u{a9}ART"),
+        genre,
+        picture,
+        path: path.to_owned(),
+        year,
+        lossless: &track.codec == b"alac",
+        disc: ilst.index_pair("disk").map(|(n, _)| n).filter(|&n| n > 0),
+        track_total: ilst.index_pair("trkn").map(|(_, t)| t).filter(|&t| t > 0),
+        disc_total: ilst.index_pair("disk").map(|(_, t)| t).filter(|&t| t > 0),
+        disc_subtitle: ilst.freeform("DISCSUBTITLE"),
+        sample_rate: track.sample_rate,
+        bits_per_sample: track.bits_per_sample,
+        num_channels: track.num_channels,
+        bitrate,
+        total_samples,
+        encoder_delay: smpb.get(1).map(|&d| d as u32),
+        encoder_padding: smpb.get(2).map(|&p| p as u32),
+        mbid_artist: ilst
+            .freeform("MusicBrainz Album Artist Id")
+            .or_else(|| ilst.freeform("MusicBrainz Artist Id")),
+        mbid_album: ilst.freeform("MusicBrainz Album Id"),
+        mbid_track: ilst.freeform("MusicBrainz Track Id"),
+        composer: ilst.text("\u{a9}wrt"),
+        isrc: ilst.freeform("ISRC"),
+        bpm: ilst.integer("tmpo").filter(|&b| b > 0),
+        copyright: ilst.text("cprt"),
+        label: ilst
+            .freeform("LABEL")
+            .or_else(|| ilst.freeform("publisher")),
+        lyrics: ilst.text("\u{a9}lyr").and_then(|l| Lyrics::from_tag(&l)),
+        // taggers disagree on the case of these freeform names
+        replay_gain: ReplayGain::from_fields(|key| {
+            ilst.freeform(&key.to_lowercase())
+                .or_else(|| ilst.freeform(key))
+        }),
+        cue: None,
+        compilation: ilst.integer("cpil").map(|c| c != 0).unwrap_or(false),
+        classical: Classical {
+            work: ilst.text("\u{a9}wrk"),
+            movement: ilst.text("\u{a9}mvn"),
+            movement_number: ilst.integer("\u{a9}mvi").filter(|&n| n > 0),
+            movement_total: ilst.integer("\u{a9}mvc").filter(|&n| n > 0),
+            conductor: ilst.freeform("CONDUCTOR"),
+            orchestra: ilst.freeform("ORCHESTRA"),
+        },
+        credits: credits::from_fields(|key| ilst.freeform_strings(key)),
+        explicit: ilst
+            .integer("rtng")
+            .and_then(|r| super::parse_advisory(&r.to_string())),
+    };
+
+    Ok(metadata)
+}
@@ -0,0 +1,164 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::{
+    config::Config,
+    metadata::{
+        formats::apev2::{ape_metadata, skip_id3v2, ApeStream, ApeTag},
+        AudioMetadata,
+    },
+};
+
+/// Samples per Musepack frame.
+const FRAME_LENGTH: u64 = 1152;
+/// Decoder delay trimmed from SV7 streams that predate true gapless support.
+const SYNTH_DELAY: u64 = 481;
+
+const SAMPLE_RATES: [u32; 4] = [44_100, 48_000, 37_800, 32_000];
+
+fn le_u32(b: &[u8], at: usize) -> Option<u32> {
+    b.get(at..at + 4)
+        .map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+}
+
+/// Read an SV8 variable length number: big-endian 7 bit groups, high bit set to continue.
+fn read_varint(b: &[u8], at: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for _ in 0..9 {
+        let byte = *b.get(*at)?;
+        *at += 1;
+        value = (value << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Parse an SV7 `MP+` header. Its fields are packed into little-endian words read
+/// from the most significant bit down.
+fn parse_sv7(b: &[u8]) -> anyhow::Result<ApeStream> {
+    let truncated = || anyhow::anyhow!("Musepack SV7 header is truncated");
+    let frames = le_u32(b, 4).ok_or_else(truncated)? as u64;
+    let flags = le_u32(b, 8).ok_or_else(truncated)?;
+    let gapless = le_u32(b, 20).ok_or_else(truncated)?;
+
+    let last_frame_samples = ((gapless >> 20) & 0x7ff) as u64;
+    let padding = if gapless >> 31 != 0 {
+        FRAME_LENGTH - last_frame_samples
+    } else {
+        SYNTH_DELAY
+    };
+    Ok(ApeStream {
+        sample_rate: SAMPLE_RATES[((flags >> 16) & 0x03) as usize],
+        bits_per_sample: None,
+        num_channels: 2,
+        total_samples: (frames * FRAME_LENGTH).saturating_sub(padding),
+        lossless: false,
+    })
+}
+
+/// Parse an SV8 `MPCK` stream by walking its packets to the `SH` stream header.
+fn parse_sv8(b: &[u8]) -> anyhow::Result<ApeStream> {
+    let mut at = 4usize;
+    while let Some(key) = b.get(at..at + 2) {
+        let start = at;
+        at += 2;
+        // the packet size includes its key and size fields
+        let size = read_varint(b, &mut at)
+            .ok_or_else(|| anyhow::anyhow!("Musepack packet size is truncated"))?
+            as usize;
+        if key != b"SH" {
+            if size < at - start {
+                anyhow::bail!("Musepack packet size {} is out of range", size);
+            }
+            at = start + size;
+            continue;
+        }
+
+        // CRC, then the stream version
+        at += 5;
+        let truncated = || anyhow::anyhow!("Musepack SV8 stream header is truncated");
+        let samples = read_varint(b, &mut at).ok_or_else(truncated)?;
+        let silence = read_varint(b, &mut at).ok_or_else(truncated)?;
+        let rate = *b.get(at).ok_or_else(truncated)?;
+        let channels = *b.get(at + 1).ok_or_else(truncated)?;
+        let sample_rate = *SAMPLE_RATES
+            .get((rate >> 5) as usize)
+            .ok_or_else(|| anyhow::anyhow!("unknown Musepack sample rate index {}", rate >> 5))?;
+        return Ok(ApeStream {
+            sample_rate,
+            bits_per_sample: None,
+            num_channels: (channels >> 4) + 1,
+            total_samples: samples.saturating_sub(silence),
+            lossless: false,
+        });
+    }
+    anyhow::bail!("no Musepack stream header found")
+}
+
+/// Parse the start of a Musepack stream. Versions before SV7 are not supported.
+fn parse_header(b: &[u8]) -> anyhow::Result<ApeStream> {
+    if b.starts_with(b"MPCK") {
+        parse_sv8(b)
+    } else if b.starts_with(b"MP+") && b.get(3).map(|v| v & 0x0f) == Some(7) {
+        parse_sv7(b)
+    } else {
+        anyhow::bail!("unsupported Musepack stream version")
+    }
+}
+
+/// Scans a Musepack file for metadata from its APEv2 tag and stream header.
+pub async fn scan_musepack(
+    path: &std::path::PathBuf,
+    cfg: &Config,
+) -> anyhow::Result<AudioMetadata> {
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    skip_id3v2(&mut file)?;
+    let mut header = Vec::with_capacity(4096);
+    (&mut file).take(4096).read_to_end(&mut header)?;
+    let stream = parse_header(&header)
+        .map_err(|e| anyhow::anyhow!("failed to read Musepack header from {:?}: {}", path, e))?;
+    let tag = ApeTag::read(&mut file)
+        .map_err(|e| anyhow::anyhow!("failed to read APE tag from {:?}: {}", path, e))?;
+    let file_len = file.seek(SeekFrom::End(0))?;
+    Ok(ape_metadata(path, tag, stream, file_len, cfg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_sv7_header() {
+        let mut b = b"MP+\x17".to_vec();
+        b.extend_from_slice(&100u32.to_le_bytes());
+        // 48 kHz
+        b.extend_from_slice(&(1u32 << 16).to_le_bytes());
+        b.extend_from_slice(&[0u8; 8]);
+        // true gapless, 1000 samples in the last frame
+        b.extend_from_slice(&((1u32 << 31) | (1000 << 20)).to_le_bytes());
+
+        let stream = parse_header(&b).unwrap();
+        assert_eq!(stream.sample_rate, 48_000);
+        assert_eq!(stream.total_samples, 99 * 1152 + 1000);
+        assert!(!stream.lossless);
+    }
+
+    #[test]
+    fn reads_sv8_stream_header() {
+        let mut sh = vec![0, 0, 0, 0, 8];
+        // 441000 samples as a varint, no leading silence
+        sh.extend_from_slice(&[0x9a, 0xf5, 0x28, 0x00]);
+        // 44.1 kHz, stereo
+        sh.extend_from_slice(&[0x00, 0x10]);
+        let mut b = b"MPCK".to_vec();
+        b.extend_from_slice(b"SH");
+        b.push(sh.len() as u8 + 3);
+        b.extend(sh);
+
+        let stream = parse_header(&b).unwrap();
+        assert_eq!(stream.sample_rate, 44_100);
+        assert_eq!(stream.num_channels, 2);
+        assert_eq!(stream.total_samples, 441_000);
+    }
+}
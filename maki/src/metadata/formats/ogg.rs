@@ -0,0 +1,780 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+};
+
+use base64::Engine;
+
+use crate::{
+    config::Config,
+    helpers::split_artists,
+    metadata::{
+        classical::Classical,
+        credits,
+        formats::{
+            parse_index, parse_total_fields, samples_to_ms, DISC_TOTAL_KEYS, TRACK_TOTAL_KEYS,
+        },
+        lyrics::Lyrics,
+        replaygain::ReplayGain,
+        AudioMetadata, Picture,
+    },
+};
+
+/// Upper bound for the header packets we are willing to buffer. Comment headers
+/// carrying several embedded pictures can span many pages, but never this many.
+const MAX_HEADER_BYTES: usize = 64 * 1024 * 1024;
+
+/// Opus always decodes at 48 kHz regardless of the input rate in OpusHead.
+const OPUS_SAMPLE_RATE: u32 = 48_000;
+
+fn read_u32_le<R: Read>(r: &mut R) -> std::io::Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_u32_be<R: Read>(r: &mut R) -> std::io::Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_be_bytes(b))
+}
+
+struct OggPage {
+    granule: i64,
+    serial: u32,
+    segments: Vec<u8>,
+    data: Vec<u8>,
+}
+
+/// Read a single Ogg page from the current position. Returns `None` at EOF.
+fn read_page<R: Read>(r: &mut R) -> std::io::Result<Option<OggPage>> {
+    let mut header = [0u8; 27];
+    match r.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    if &header[0..4] != b"OggS" {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "missing OggS capture pattern",
+        ));
+    }
+    let granule = i64::from_le_bytes([
+        header[6], header[7], header[8], header[9], header[10], header[11], header[12], header[13],
+    ]);
+    let serial = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
+    let mut segments = vec![0u8; header[26] as usize];
+    r.read_exact(&mut segments)?;
+    let body_len: usize = segments.iter().map(|&s| s as usize).sum();
+    let mut data = vec![0u8; body_len];
+    r.read_exact(&mut data)?;
+    Ok(Some(OggPage {
+        granule,
+        serial,
+        segments,
+        data,
+    }))
+}
+
+/// Reassemble the first `count` packets of the first logical stream in the file.
+/// Returns the stream serial alongside the packets.
+fn read_header_packets<R: Read>(r: &mut R, count: usize) -> anyhow::Result<(u32, Vec<Vec<u8>>)> {
+    let mut packets: Vec<Vec<u8>> = Vec::with_capacity(count);
+    let mut current: Vec<u8> = Vec::new();
+    let mut serial: Option<u32> = None;
+    let mut buffered = 0usize;
+
+    while packets.len() < count {
+        let page = read_page(r)?.ok_or_else(|| anyhow::anyhow!("unexpected end of Ogg stream"))?;
+        let stream = *serial.get_or_insert(page.serial);
+        // skip pages belonging to other multiplexed streams
+        if page.serial != stream {
+            continue;
+        }
+
+        let mut offset = 0usize;
+        for &len in &page.segments {
+            let len = len as usize;
+            current.extend_from_slice(&page.data[offset..offset + len]);
+            offset += len;
+            buffered += len;
+            // a lacing value below 255 terminates the packet
+            if len < 255 {
+                packets.push(std::mem::take(&mut current));
+                if packets.len() == count {
+                    break;
+                }
+            }
+        }
+
+        if buffered > MAX_HEADER_BYTES {
+            anyhow::bail!("Ogg header packets exceed {} bytes", MAX_HEADER_BYTES);
+        }
+    }
+
+    Ok((serial.unwrap_or_default(), packets))
+}
+
+/// Find the granule position of the last page of `serial` by scanning backwards
+/// from the end of the file, widening the window until a page is found.
+fn read_last_granule<R: Read + Seek>(r: &mut R, serial: u32) -> std::io::Result<Option<i64>> {
+    let len = r.seek(SeekFrom::End(0))?;
+    let mut window: u64 = 64 * 1024;
+
+    loop {
+        let start = len.saturating_sub(window);
+        r.seek(SeekFrom::Start(start))?;
+        let mut buf = Vec::with_capacity((len - start) as usize);
+        (&mut *r).take(len - start).read_to_end(&mut buf)?;
+        if buf.len() < 27 {
+            return Ok(None);
+        }
+
+        let mut pos = buf.len().saturating_sub(27);
+        loop {
+            if &buf[pos..pos + 4] == b"OggS" && buf.len() >= pos + 27 {
+                let page_serial = u32::from_le_bytes([
+                    buf[pos + 14],
+                    buf[pos + 15],
+                    buf[pos + 16],
+                    buf[pos + 17],
+                ]);
+                let granule = i64::from_le_bytes([
+                    buf[pos + 6],
+                    buf[pos + 7],
+                    buf[pos + 8],
+                    buf[pos + 9],
+                    buf[pos + 10],
+                    buf[pos + 11],
+                    buf[pos + 12],
+                    buf[pos + 13],
+                ]);
+                // -1 marks a page on which no packet finishes
+                if page_serial == serial && granule >= 0 {
+                    return Ok(Some(granule));
+                }
+            }
+            if pos == 0 {
+                break;
+            }
+            pos -= 1;
+        }
+
+        if start == 0 {
+            return Ok(None);
+        }
+        window *= 4;
+    }
+}
+
+enum Codec {
+    Vorbis { channels: u8, sample_rate: u32 },
+    Opus { channels: u8, pre_skip: u16 },
+}
+
+impl Codec {
+    /// Identify the codec from the first (identification) packet.
+    fn identify(packet: &[u8]) -> anyhow::Result<Self> {
+        if packet.len() >= 16 && &packet[0..7] == b"\x01vorbis" {
+            Ok(Codec::Vorbis {
+                channels: packet[11],
+                sample_rate: u32::from_le_bytes([packet[12], packet[13], packet[14], packet[15]]),
+            })
+        } else if packet.len() >= 19 && &packet[0..8] == b"OpusHead" {
+            Ok(Codec::Opus {
+                channels: packet[9],
+                pre_skip: u16::from_le_bytes([packet[10], packet[11]]),
+            })
+        } else {
+            anyhow::bail!("unsupported Ogg codec")
+        }
+    }
+
+    fn comment_prefix(&self) -> &'static [u8] {
+        match self {
+            Codec::Vorbis { .. } => b"\x03vorbis",
+            Codec::Opus { .. } => b"OpusTags",
+        }
+    }
+
+    /// Strip the codec-specific prefix from the comment header packet.
+    fn comment_body<'a>(&self, packet: &'a [u8]) -> anyhow::Result<&'a [u8]> {
+        packet
+            .strip_prefix(self.comment_prefix())
+            .ok_or_else(|| anyhow::anyhow!("missing comment header"))
+    }
+
+    /// Number of header packets before the audio: Vorbis adds a setup header.
+    fn header_packets(&self) -> usize {
+        match self {
+            Codec::Vorbis { .. } => 3,
+            Codec::Opus { .. } => 2,
+        }
+    }
+}
+
+/// Split a comment header body into its vendor string and raw `KEY=value` entries.
+fn comment_entries(data: &[u8]) -> anyhow::Result<(Vec<u8>, Vec<String>)> {
+    let mut cursor = std::io::Cursor::new(data);
+    let vendor_len = read_u32_le(&mut cursor)? as usize;
+    if vendor_len > data.len() {
+        anyhow::bail!("vendor length {} exceeds header size", vendor_len);
+    }
+    let mut vendor = vec![0u8; vendor_len];
+    cursor.read_exact(&mut vendor)?;
+    let count = read_u32_le(&mut cursor)?;
+
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let len = read_u32_le(&mut cursor)? as usize;
+        if len > data.len() {
+            anyhow::bail!("comment length {} exceeds header size", len);
+        }
+        let mut raw = vec![0u8; len];
+        cursor.read_exact(&mut raw)?;
+        entries.push(String::from_utf8_lossy(&raw).to_string());
+    }
+    Ok((vendor, entries))
+}
+
+/// Vorbis comments keyed by upper-cased field name. Shared by Vorbis and Opus.
+pub struct VorbisComments {
+    comments: HashMap<String, Vec<String>>,
+}
+
+impl VorbisComments {
+    pub fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        let (_, entries) = comment_entries(data)?;
+        let mut comments: HashMap<String, Vec<String>> = HashMap::new();
+        for entry in entries {
+            if let Some((key, value)) = entry.split_once('=') {
+                comments
+                    .entry(key.to_uppercase())
+                    .or_default()
+                    .push(value.to_string());
+            }
+        }
+
+        Ok(VorbisComments { comments })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Vec<String>> {
+        self.comments.get(key)
+    }
+
+    pub fn first(&self, key: &str) -> Option<String> {
+        self.get(key).and_then(|v| v.first().cloned())
+    }
+}
+
+/// Ogg's CRC-32: polynomial 0x04c11db7, unreflected, with no initial or final xor.
+fn ogg_crc(data: &[u8]) -> u32 {
+    let mut crc = 0u32;
+    for &b in data {
+        crc ^= (b as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Recompute a page's checksum, which covers the whole page with the CRC field zeroed.
+fn set_page_crc(page: &mut [u8]) {
+    page[22..26].copy_from_slice(&[0; 4]);
+    let crc = ogg_crc(page);
+    page[22..26].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// Lay header packets out on pages of `serial`, numbered from `sequence`.
+fn paginate(packets: &[Vec<u8>], serial: u32, sequence: u32) -> Vec<Vec<u8>> {
+    // lacing values, each flagged with whether it ends its packet
+    let mut lacing: Vec<(u8, bool)> = Vec::new();
+    for p in packets {
+        lacing.extend(std::iter::repeat((255u8, false)).take(p.len() / 255));
+        lacing.push(((p.len() % 255) as u8, true));
+    }
+    let data = packets.concat();
+
+    let mut pages = Vec::new();
+    let mut offset = 0usize;
+    let mut continued = false;
+    for (i, chunk) in lacing.chunks(255).enumerate() {
+        let len: usize = chunk.iter().map(|&(v, _)| v as usize).sum();
+        // header pages carry granule 0, or -1 when no packet finishes on them
+        let granule: i64 = if chunk.iter().any(|&(_, end)| end) {
+            0
+        } else {
+            -1
+        };
+        let mut page = b"OggS".to_vec();
+        page.push(0);
+        page.push(if continued { 0x01 } else { 0 });
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&serial.to_le_bytes());
+        page.extend_from_slice(&(sequence + i as u32).to_le_bytes());
+        page.extend_from_slice(&[0; 4]);
+        page.push(chunk.len() as u8);
+        page.extend(chunk.iter().map(|&(v, _)| v));
+        page.extend_from_slice(&data[offset..offset + len]);
+        set_page_crc(&mut page);
+        pages.push(page);
+
+        offset += len;
+        continued = !chunk.last().map_or(true, |&(_, end)| end);
+    }
+    pages
+}
+
+/// Rewrite the comment header of an Ogg Vorbis or Opus stream held in memory. `edit`
+/// receives the raw `KEY=value` entries. The comment (and Vorbis setup) packets are
+/// re-paginated and the pages after them renumbered, so the audio itself is untouched.
+pub fn rewrite_comments(
+    data: &[u8],
+    edit: impl FnOnce(&mut Vec<String>),
+) -> anyhow::Result<Vec<u8>> {
+    let truncated = || anyhow::anyhow!("truncated Ogg page");
+    let mut pages: Vec<(usize, usize)> = Vec::new();
+    let mut pos = 0usize;
+    while pos < data.len() {
+        let header = data.get(pos..pos + 27).ok_or_else(truncated)?;
+        if &header[0..4] != b"OggS" {
+            anyhow::bail!("missing OggS capture pattern");
+        }
+        let count = header[26] as usize;
+        let segments = data.get(pos + 27..pos + 27 + count).ok_or_else(truncated)?;
+        let len = 27 + count + segments.iter().map(|&s| s as usize).sum::<usize>();
+        if pos + len > data.len() {
+            return Err(truncated());
+        }
+        pages.push((pos, len));
+        pos += len;
+    }
+    let page_serial = |at: usize| {
+        u32::from_le_bytes([data[at + 14], data[at + 15], data[at + 16], data[at + 17]])
+    };
+    let page_sequence = |at: usize| {
+        u32::from_le_bytes([data[at + 18], data[at + 19], data[at + 20], data[at + 21]])
+    };
+    let &(first, _) = pages
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("no Ogg pages"))?;
+    let serial = page_serial(first);
+
+    // reassemble the header packets, which must end on a page boundary
+    let mut packets: Vec<Vec<u8>> = Vec::new();
+    let mut current: Vec<u8> = Vec::new();
+    let mut codec: Option<Codec> = None;
+    let mut header_pages = 0usize;
+    for (i, &(at, len)) in pages.iter().enumerate() {
+        if page_serial(at) != serial {
+            anyhow::bail!("multiplexed Ogg streams are not supported");
+        }
+        let count = data[at + 26] as usize;
+        let mut offset = at + 27 + count;
+        for &lace in &data[at + 27..at + 27 + count] {
+            current.extend_from_slice(&data[offset..offset + lace as usize]);
+            offset += lace as usize;
+            if lace < 255 {
+                packets.push(std::mem::take(&mut current));
+            }
+        }
+        debug_assert_eq!(offset, at + len);
+        // the first page is copied as is, so it must hold the identification header alone
+        if i == 0 && (packets.len() != 1 || !current.is_empty()) {
+            anyhow::bail!("the first Ogg page must hold only the identification header");
+        }
+        if codec.is_none() && !packets.is_empty() {
+            codec = Some(Codec::identify(&packets[0])?);
+        }
+        if let Some(c) = &codec {
+            if packets.len() >= c.header_packets() {
+                if packets.len() > c.header_packets() || !current.is_empty() {
+                    anyhow::bail!("audio shares a page with the Ogg header packets");
+                }
+                header_pages = i + 1;
+                break;
+            }
+        }
+    }
+    let codec = codec.ok_or_else(|| anyhow::anyhow!("no Ogg header packets"))?;
+    if header_pages == 0 {
+        anyhow::bail!("unexpected end of Ogg headers");
+    }
+
+    let (vendor, mut entries) = comment_entries(codec.comment_body(&packets[1])?)?;
+    edit(&mut entries);
+    let mut comment = codec.comment_prefix().to_vec();
+    comment.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    comment.extend_from_slice(&vendor);
+    comment.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for entry in &entries {
+        comment.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+        comment.extend_from_slice(entry.as_bytes());
+    }
+    if let Codec::Vorbis { .. } = codec {
+        // framing bit
+        comment.push(1);
+    }
+    packets[1] = comment;
+
+    let new_pages = paginate(&packets[1..], serial, page_sequence(first) + 1);
+    let shift = new_pages.len() as i64 - (header_pages as i64 - 1);
+    let mut out = Vec::with_capacity(data.len() + 4096);
+    out.extend_from_slice(&data[first..first + pages[0].1]);
+    for page in new_pages {
+        out.extend(page);
+    }
+    for &(at, len) in &pages[header_pages..] {
+        let mut page = data[at..at + len].to_vec();
+        if shift != 0 && page_serial(at) == serial {
+            let sequence = (page_sequence(at) as i64 + shift) as u32;
+            page[18..22].copy_from_slice(&sequence.to_le_bytes());
+            set_page_crc(&mut page);
+        }
+        out.extend(page);
+    }
+    Ok(out)
+}
+
+/// Human-readable name for a FLAC/ID3 picture type code, matching the FLAC scanner.
+fn picture_type_name(code: u32) -> &'static str {
+    match code {
+        1 => "Icon",
+        2 => "Other Icon",
+        3 => "Cover (Front)",
+        4 => "Cover (Back)",
+        5 => "Leaflet",
+        6 => "Media",
+        7 => "Lead Artist",
+        8 => "Artist",
+        9 => "Conductor",
+        10 => "Band",
+        11 => "Composer",
+        12 => "Lyricist",
+        13 => "Recording Location",
+        14 => "During Recording",
+        15 => "During Performance",
+        16 => "Screen Capture",
+        17 => "Bright Fish",
+        18 => "Illustration",
+        19 => "Band Logo",
+        20 => "Publisher Logo",
+        _ => "Other",
+    }
+}
+
+/// Decode a METADATA_BLOCK_PICTURE value (a base64 FLAC picture block).
+fn parse_picture_block(value: &str) -> Option<Picture> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(value.trim())
+        .ok()?;
+    let mut cursor = std::io::Cursor::new(bytes.as_slice());
+
+    let picture_type = read_u32_be(&mut cursor).ok()?;
+    let mime_len = read_u32_be(&mut cursor).ok()? as i64;
+    cursor.seek(SeekFrom::Current(mime_len)).ok()?;
+    let desc_len = read_u32_be(&mut cursor).ok()? as i64;
+    cursor.seek(SeekFrom::Current(desc_len)).ok()?;
+    // width, height, colour depth, indexed colours
+    cursor.seek(SeekFrom::Current(16)).ok()?;
+    let data_len = read_u32_be(&mut cursor).ok()? as usize;
+    let start = cursor.position() as usize;
+    let data = bytes.get(start..start + data_len)?.to_vec();
+
+    Some(Picture {
+        picture_type: picture_type_name(picture_type).to_string(),
+        bytes: data,
+    })
+}
+
+/// Scans an Ogg Vorbis or Ogg Opus file for metadata and returns an `AudioMetadata` struct.
+pub async fn scan_ogg(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Result<AudioMetadata> {
+    let mut file = std::fs::File::open(path)?;
+    let (serial, packets) = read_header_packets(&mut std::io::BufReader::new(&mut file), 2)
+        .map_err(|e| anyhow::anyhow!("failed to read Ogg headers from {:?}: {}", path, e))?;
+
+    let codec = Codec::identify(&packets[0]).map_err(|e| anyhow::anyhow!("{} in {:?}", e, path))?;
+    let vorbis = VorbisComments::parse(codec.comment_body(&packets[1])?)
+        .map_err(|e| anyhow::anyhow!("failed to parse comments in {:?}: {}", path, e))?;
+
+    let last_granule = read_last_granule(&mut file, serial)?.unwrap_or(0) as u64;
+    let (sample_rate, num_channels, total_samples, encoder_delay) = match codec {
+        Codec::Vorbis {
+            channels,
+            sample_rate,
+        } => (sample_rate, channels, last_granule, None),
+        Codec::Opus { channels, pre_skip } => (
+            OPUS_SAMPLE_RATE,
+            channels,
+            last_granule.saturating_sub(pre_skip as u64),
+            Some(pre_skip as u32),
+        ),
+    };
+    let duration_ms = samples_to_ms(total_samples, sample_rate);
+    let bitrate = if total_samples > 0 {
+        let bits = file.metadata()?.len() * 8 * sample_rate as u64;
+        Some((bits / total_samples / 1000) as u32)
+    } else {
+        None
+    };
+
+    let parse_year = |v: &Vec<String>| -> Option<i32> {
+        v.first()?
+            .split('-')
+            .next()
+            .and_then(|part| part.parse::<i32>().ok())
+            .filter(|&y| y > 0)
+    };
+    let year = vorbis
+        .get("YEAR")
+        .and_then(parse_year)
+        .or_else(|| vorbis.get("DATE").and_then(parse_year));
+
+    let mut picture: Vec<Picture> = vorbis
+        .get("METADATA_BLOCK_PICTURE")
+        .map(|v| v.iter().filter_map(|p| parse_picture_block(p)).collect())
+        .unwrap_or_default();
+    // legacy unofficial field: raw base64 image bytes
+    if let Some(legacy) = vorbis.get("COVERART") {
+        picture.extend(legacy.iter().filter_map(|v| {
+            base64::engine::general_purpose::STANDARD
+                .decode(v.trim())
+                .ok()
+                .map(|bytes| Picture {
+                    picture_type: "Cover (Front)".to_string(),
+                    bytes,
+                })
+        }));
+    }
+
+    // artists is either ARTISTS (one per field) or ARTIST (single but may be split elsewhere)
+    let unk_vec = vec!["Unknown".to_string()];
+    let artists = match vorbis.get("ARTISTS") {
+        Some(a) => a.to_owned(),
+        None => split_artists(
+            vorbis
+                .get("ARTIST")
+                .or_else(|| vorbis.get("ALBUMARTIST"))
+                .unwrap_or(&unk_vec),
+            &cfg.artist_split_exceptions,
+        ),
+    };
+
+    let metadata = AudioMetadata {
+        name: vorbis.first("TITLE").unwrap_or_default(),
+        number: vorbis
+            .first("TRACKNUMBER")
+            .and_then(|n| parse_index(&n))
+            .unwrap_or(0),
+        duration: duration_ms / 1000,
+        duration_ms,
+        album: vorbis.first("ALBUM").unwrap_or_default(),
+        album_artist: vorbis
+            .first("ALBUMARTIST")
+            .or_else(|| vorbis.first("ALBUM ARTIST"))
+            .or_else(|| vorbis.first("ARTIST"))
+            .unwrap_or_default(),
+        album_sort: vorbis.first("ALBUMSORT"),
+        artist_sort: vorbis.first("ARTISTSORT"),
+        album_artist_sort: vorbis.first("ALBUMARTISTSORT"),
+        artists,
+        artist_tag: vorbis.first("ARTIST"),
+        genre: vorbis.get("GENRE").map(|v| v.to_owned()),
+        picture,
+        path: path.to_owned(),
+        year,
+        lossless: false,
+        disc: vorbis.first("DISCNUMBER").and_then(|n| parse_index(&n)),
+        track_total: parse_total_fields(
+            |key| vorbis.first(key),
+            &TRACK_TOTAL_KEYS,
+            &["TRACKNUMBER"],
+        ),
+        disc_total: parse_total_fields(|key| vorbis.first(key), &DISC_TOTAL_KEYS, &["DISCNUMBER"]),
+        disc_subtitle: vorbis.first("DISCSUBTITLE"),
+        sample_rate: Some(sample_rate),
+        bits_per_sample: None,
+        num_channels: Some(num_channels),
+        bitrate,
+        total_samples: Some(total_samples).filter(|&t| t > 0),
+        encoder_delay,
+        encoder_padding: None,
+        mbid_artist: vorbis
+            .first("MUSICBRAINZ_ALBUMARTISTID")
+            .or_else(|| vorbis.first("MUSICBRAINZ_ARTISTID")),
+        mbid_album: vorbis.first("MUSICBRAINZ_ALBUMID"),
+        mbid_track: vorbis.first("MUSICBRAINZ_TRACKID"),
+        composer: vorbis.first("COMPOSER"),
+        isrc: vorbis.first("ISRC"),
+        bpm: vorbis
+            .first("BPM")
+            .and_then(|s| s.trim().parse::<f32>().ok())
+            .map(|b| b.round() as u32),
+        copyright: vorbis.first("COPYRIGHT"),
+        label: vorbis
+            .first("LABEL")
+            .or_else(|| vorbis.first("ORGANIZATION")),
+        lyrics: vorbis
+            .first("LYRICS")
+            .or_else(|| vorbis.first("UNSYNCEDLYRICS"))
+            .and_then(|l| Lyrics::from_tag(&l)),
+        replay_gain: ReplayGain::from_fields(|key| vorbis.first(key))
+            .or_r128(|key| vorbis.first(key)),
+        cue: None,
+        compilation: vorbis
+            .first("COMPILATION")
+            .map(|c| super::parse_flag(&c))
+            .unwrap_or(false),
+        classical: Classical::from_fields(|key| vorbis.first(key)),
+        credits: credits::from_fields(|key| vorbis.get(key).cloned()),
+        explicit: super::advisory_fields(|key| vorbis.first(key)),
+    };
+
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment_packet(entries: &[&str]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let vendor = b"test vendor";
+        out.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        out.extend_from_slice(vendor);
+        out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for e in entries {
+            out.extend_from_slice(&(e.len() as u32).to_le_bytes());
+            out.extend_from_slice(e.as_bytes());
+        }
+        out
+    }
+
+    fn page(serial: u32, granule: i64, packets: &[&[u8]]) -> Vec<u8> {
+        let mut segments = Vec::new();
+        let mut data = Vec::new();
+        for p in packets {
+            let mut remaining = p.len();
+            while remaining >= 255 {
+                segments.push(255u8);
+                remaining -= 255;
+            }
+            segments.push(remaining as u8);
+            data.extend_from_slice(p);
+        }
+        let mut out = b"OggS".to_vec();
+        out.push(0);
+        out.push(0);
+        out.extend_from_slice(&granule.to_le_bytes());
+        out.extend_from_slice(&serial.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.push(segments.len() as u8);
+        out.extend_from_slice(&segments);
+        out.extend_from_slice(&data);
+        out
+    }
+
+    #[test]
+    fn comments_are_case_insensitive_and_multi_valued() {
+        let packet = comment_packet(&["title=Song", "ARTIST=A", "Artist=B", "garbage"]);
+        let comments = VorbisComments::parse(&packet).unwrap();
+        assert_eq!(comments.first("TITLE").as_deref(), Some("Song"));
+        assert_eq!(
+            comments.get("ARTIST").unwrap(),
+            &vec!["A".to_string(), "B".to_string()]
+        );
+    }
+
+    #[test]
+    fn header_packets_span_pages() {
+        let long = vec![7u8; 600];
+        let mut file = page(9, 0, &[b"first"]);
+        // 600 bytes laced as 255 + 255 + 90 across one page
+        file.extend(page(9, 0, &[&long]));
+        let (serial, packets) = read_header_packets(&mut std::io::Cursor::new(file), 2).unwrap();
+        assert_eq!(serial, 9);
+        assert_eq!(packets[0], b"first");
+        assert_eq!(packets[1].len(), 600);
+    }
+
+    #[test]
+    fn last_granule_ignores_other_streams() {
+        let mut file = page(1, 0, &[b"a"]);
+        file.extend(page(1, 44_100, &[b"b"]));
+        file.extend(page(2, 99_999, &[b"c"]));
+        let granule = read_last_granule(&mut std::io::Cursor::new(file), 1).unwrap();
+        assert_eq!(granule, Some(44_100));
+    }
+
+    #[test]
+    fn crc_matches_ogg_check_value() {
+        assert_eq!(ogg_crc(b"123456789"), 0x89a1_897f);
+    }
+
+    #[test]
+    fn rewrites_comments_across_pages() {
+        let mut ident = b"\x01vorbis".to_vec();
+        ident.extend_from_slice(&0u32.to_le_bytes());
+        ident.push(2);
+        ident.extend_from_slice(&44_100u32.to_le_bytes());
+        let mut comment = b"\x03vorbis".to_vec();
+        comment.extend(comment_packet(&["TITLE=Old", "ARTIST=A"]));
+        comment.push(1);
+        let setup = b"\x05vorbis setup".to_vec();
+        let mut file = page(3, 0, &[&ident]);
+        file.extend(page(3, 0, &[&comment, &setup]));
+        file.extend(page(3, 44_100, &[b"audio"]));
+
+        // a long entry pushes the headers onto a second page
+        let long = format!("DESCRIPTION={}", "x".repeat(70_000));
+        let out = rewrite_comments(&file, |entries| {
+            entries.retain(|e| !e.starts_with("TITLE="));
+            entries.push("TITLE=New".to_string());
+            entries.push(long.clone());
+        })
+        .unwrap();
+
+        let (serial, packets) = read_header_packets(&mut std::io::Cursor::new(&out), 3).unwrap();
+        assert_eq!(serial, 3);
+        assert_eq!(packets[2], setup);
+        let codec = Codec::identify(&packets[0]).unwrap();
+        let comments = VorbisComments::parse(codec.comment_body(&packets[1]).unwrap()).unwrap();
+        assert_eq!(comments.first("TITLE").as_deref(), Some("New"));
+        assert_eq!(comments.first("ARTIST").as_deref(), Some("A"));
+
+        // the audio page moved back one sequence number and kept a valid checksum
+        let audio = &out[out.len() - 33..];
+        assert!(audio.starts_with(b"OggS"));
+        assert_eq!(
+            u32::from_le_bytes([audio[18], audio[19], audio[20], audio[21]]),
+            1
+        );
+        let mut check = audio.to_vec();
+        set_page_crc(&mut check);
+        assert_eq!(check, audio);
+    }
+
+    #[test]
+    fn opus_head_is_identified() {
+        let mut head = b"OpusHead".to_vec();
+        head.push(1); // version
+        head.push(2); // channels
+        head.extend_from_slice(&312u16.to_le_bytes());
+        head.extend_from_slice(&44_100u32.to_le_bytes());
+        head.extend_from_slice(&[0, 0, 0]);
+        match Codec::identify(&head).unwrap() {
+            Codec::Opus { channels, pre_skip } => {
+                assert_eq!(channels, 2);
+                assert_eq!(pre_skip, 312);
+            }
+            _ => panic!("expected opus"),
+        }
+    }
+}
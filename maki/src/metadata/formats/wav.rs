@@ -1,7 +1,7 @@
 use crate::{
     config::Config,
     helpers::{sort_string, split_artists},
-    metadata::{AudioMetadata, Picture},
+    metadata::{formats::samples_to_ms, AudioMetadata, Picture},
 };
 use id3::{partial_tag_ok, TagLike};
 
@@ -10,26 +10,22 @@ use id3::{partial_tag_ok, TagLike};
 pub async fn scan_wav(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Result<AudioMetadata> {
     let tag = partial_tag_ok(id3::Tag::read_from_path(path))?;
 
-    let (sample_rate, bits_per_sample, num_channels, duration) = match hound::WavReader::open(path)
-    {
-        Ok(reader) => {
-            let spec = reader.spec();
-            // duration() returns total sample frames (samples / channels)
-            let frames = reader.duration();
-            let secs = if spec.sample_rate > 0 {
-                frames / spec.sample_rate
-            } else {
-                0
-            };
-            (
-                Some(spec.sample_rate),
-                Some(spec.bits_per_sample as u8),
-                Some(spec.channels as u8),
-                secs,
-            )
-        }
-        Err(_) => (None, None, None, tag.duration().unwrap_or(0)),
-    };
+    let (sample_rate, bits_per_sample, num_channels, total_samples, duration_ms) =
+        match hound::WavReader::open(path) {
+            Ok(reader) => {
+                let spec = reader.spec();
+                // duration() returns total sample frames (samples / channels)
+                let frames = reader.duration();
+                (
+                    Some(spec.sample_rate),
+                    Some(spec.bits_per_sample as u8),
+                    Some(spec.channels as u8),
+                    Some(frames as u64),
+                    samples_to_ms(frames as u64, spec.sample_rate),
+                )
+            }
+            Err(_) => (None, None, None, None, tag.duration().unwrap_or(0)),
+        };
 
     let artists: Vec<String> = tag
         .artists()
@@ -43,12 +39,16 @@ pub async fn scan_wav(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Result
 
     let meta = AudioMetadata {
         name: tag.title().unwrap_or_default().to_string(),
-        number: tag.track().unwrap_or(25565),
-        duration,
+        number: tag.track().unwrap_or(0),
+        duration: duration_ms / 1000,
+        duration_ms,
         album: tag.album().unwrap_or_default().to_string(),
         album_artist: tag.album_artist().unwrap_or_default().to_string(),
         album_sort: sort_string(tag.album()),
+        artist_sort: tag.text_for_frame_id("TSOP").map(|s| s.to_string()),
+        album_artist_sort: tag.text_for_frame_id("TSO2").map(|s| s.to_string()),
         artists,
+        artist_tag: tag.artist().map(|s| s.to_string()),
         genre: tag.genre().map(|g| vec![g.to_string()]).or_else(|| {
             tag.genres()
                 .map(|gs| gs.into_iter().map(|s: &str| s.to_string()).collect())
@@ -65,8 +65,15 @@ pub async fn scan_wav(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Result
         sample_rate,
         bits_per_sample,
         num_channels,
+        bitrate: None,
+        total_samples,
+        encoder_delay: None,
+        encoder_padding: None,
         year: tag.year(),
         disc: tag.disc(),
+        track_total: tag.total_tracks().filter(|&t| t > 0),
+        disc_total: tag.total_discs().filter(|&t| t > 0),
+        disc_subtitle: tag.text_for_frame_id("TSST").map(|s| s.to_string()),
         mbid_artist: None,
         mbid_album: None,
         mbid_track: None,
@@ -77,6 +84,16 @@ pub async fn scan_wav(path: &std::path::PathBuf, cfg: &Config) -> anyhow::Result
             .and_then(|s| s.parse::<u32>().ok()),
         copyright: tag.text_for_frame_id("TCOP").map(|s| s.to_string()),
         label: tag.text_for_frame_id("TPUB").map(|s| s.to_string()),
+        lyrics: crate::metadata::lyrics::from_id3(&tag),
+        replay_gain: crate::metadata::replaygain::from_id3(&tag),
+        cue: None,
+        compilation: tag
+            .text_for_frame_id("TCMP")
+            .map(super::parse_flag)
+            .unwrap_or(false),
+        classical: crate::metadata::classical::from_id3(&tag),
+        credits: crate::metadata::credits::from_id3(&tag),
+        explicit: super::id3_advisory(&tag),
     };
 
     Ok(meta)
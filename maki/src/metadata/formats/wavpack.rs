@@ -0,0 +1,185 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::{
+    config::Config,
+    metadata::{
+        formats::apev2::{ape_metadata, skip_id3v2, ApeStream, ApeTag},
+        AudioMetadata,
+    },
+};
+
+/// Sample rates indexed by bits 23-26 of the block flags. Index 15 means the rate
+/// is stored in an `ID_SAMPLE_RATE` metadata sub-block instead.
+const SAMPLE_RATES: [u32; 15] = [
+    6000, 8000, 9600, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000,
+    192000,
+];
+
+const MONO_FLAG: u32 = 0x4;
+const HYBRID_FLAG: u32 = 0x8;
+const FLOAT_DATA: u32 = 0x80;
+const FINAL_BLOCK: u32 = 0x1000;
+const ID_SAMPLE_RATE: u8 = 0x27;
+
+/// Largest block WavPack writes, used to reject corrupt block sizes.
+const MAX_BLOCK_BYTES: u32 = 1024 * 1024;
+
+fn le_u32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]])
+}
+
+/// Find a non-standard sample rate in a block's metadata sub-blocks.
+fn custom_sample_rate(body: &[u8]) -> Option<u32> {
+    let mut at = 0usize;
+    while at + 2 <= body.len() {
+        let id = body[at];
+        // sizes are in 16-bit words; large sub-blocks use a 3 byte size
+        let (words, header) = if id & 0x80 != 0 {
+            let s = body.get(at + 1..at + 4)?;
+            (u32::from_le_bytes([s[0], s[1], s[2], 0]) as usize, 4)
+        } else {
+            (body[at + 1] as usize, 2)
+        };
+        let data = body.get(at + header..at + header + words * 2)?;
+        if id & 0x3f == ID_SAMPLE_RATE && data.len() >= 3 {
+            return Some(u32::from_le_bytes([data[0], data[1], data[2], 0]));
+        }
+        at += header + words * 2;
+    }
+    None
+}
+
+/// Read stream properties from the leading `wvpk` blocks. Multichannel audio is split
+/// into stereo and mono blocks up to the one flagged final, so channels are summed.
+fn read_wavpack<R: Read + Seek>(r: &mut R) -> anyhow::Result<ApeStream> {
+    let mut stream: Option<ApeStream> = None;
+    let mut channels = 0u8;
+
+    for _ in 0..64 {
+        let mut header = [0u8; 32];
+        r.read_exact(&mut header)?;
+        if &header[0..4] != b"wvpk" {
+            anyhow::bail!("missing wvpk block header");
+        }
+        // the block size excludes the id and size fields
+        let size = le_u32(&header, 4);
+        if !(24..=MAX_BLOCK_BYTES).contains(&size) {
+            anyhow::bail!("WavPack block size {} is out of range", size);
+        }
+        let block_samples = le_u32(&header, 20);
+        let flags = le_u32(&header, 24);
+        let mut body = vec![0u8; size as usize - 24];
+        r.read_exact(&mut body)?;
+        // blocks without samples only carry metadata
+        if block_samples == 0 {
+            continue;
+        }
+
+        if stream.is_none() {
+            let sample_rate = match SAMPLE_RATES.get(((flags >> 23) & 0x0f) as usize) {
+                Some(&rate) => rate,
+                None => custom_sample_rate(&body).unwrap_or(0),
+            };
+            // 40 bit count, where all ones in the low word means unknown
+            let total = le_u32(&header, 12);
+            let total_samples = if total == u32::MAX {
+                0
+            } else {
+                let high = header[11] as u64;
+                total as u64 + (high << 32) - high
+            };
+            let bits_per_sample = if flags & FLOAT_DATA != 0 {
+                32
+            } else {
+                (((flags & 0x03) + 1) * 8).saturating_sub((flags >> 13) & 0x1f)
+            };
+            stream = Some(ApeStream {
+                sample_rate,
+                bits_per_sample: Some(bits_per_sample as u8),
+                num_channels: 0,
+                total_samples,
+                lossless: flags & HYBRID_FLAG == 0,
+            });
+        }
+        channels += if flags & MONO_FLAG != 0 { 1 } else { 2 };
+        if flags & FINAL_BLOCK != 0 {
+            break;
+        }
+    }
+
+    let mut stream = stream.ok_or_else(|| anyhow::anyhow!("no audio blocks found"))?;
+    stream.num_channels = channels;
+    Ok(stream)
+}
+
+/// Scans a WavPack file for metadata from its APEv2 tag and block headers.
+pub async fn scan_wavpack(
+    path: &std::path::PathBuf,
+    cfg: &Config,
+) -> anyhow::Result<AudioMetadata> {
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    skip_id3v2(&mut file)?;
+    let mut stream = read_wavpack(&mut file)
+        .map_err(|e| anyhow::anyhow!("failed to read WavPack header from {:?}: {}", path, e))?;
+    // hybrid files are lossless only alongside their .wvc correction file
+    if !stream.lossless {
+        stream.lossless = path.with_extension("wvc").is_file();
+    }
+    let tag = ApeTag::read(&mut file)
+        .map_err(|e| anyhow::anyhow!("failed to read APE tag from {:?}: {}", path, e))?;
+    let file_len = file.seek(SeekFrom::End(0))?;
+    Ok(ape_metadata(path, tag, stream, file_len, cfg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(flags: u32, sub_blocks: &[u8]) -> Vec<u8> {
+        let mut b = b"wvpk".to_vec();
+        b.extend_from_slice(&(24 + sub_blocks.len() as u32).to_le_bytes());
+        b.extend_from_slice(&0x410u16.to_le_bytes());
+        b.push(0);
+        b.push(0);
+        b.extend_from_slice(&441_000u32.to_le_bytes());
+        b.extend_from_slice(&0u32.to_le_bytes());
+        b.extend_from_slice(&22_050u32.to_le_bytes());
+        b.extend_from_slice(&flags.to_le_bytes());
+        b.extend_from_slice(&0u32.to_le_bytes());
+        b.extend_from_slice(sub_blocks);
+        b
+    }
+
+    #[test]
+    fn reads_stereo_block() {
+        // 16 bit, 44.1 kHz, initial and final block
+        let flags = 0x01 | (9 << 23) | 0x800 | FINAL_BLOCK;
+        let stream = read_wavpack(&mut std::io::Cursor::new(block(flags, &[]))).unwrap();
+        assert_eq!(
+            stream,
+            ApeStream {
+                sample_rate: 44_100,
+                bits_per_sample: Some(16),
+                num_channels: 2,
+                total_samples: 441_000,
+                lossless: true,
+            }
+        );
+    }
+
+    #[test]
+    fn sums_multichannel_blocks_and_custom_rates() {
+        // 24 bit hybrid at a custom 22 kHz rate: a stereo block then a final mono block
+        let rate = [ID_SAMPLE_RATE | 0x40, 2, 0xF0, 0x55, 0x00, 0x00];
+        let mut file = block(0x02 | HYBRID_FLAG | (15 << 23) | 0x800, &rate);
+        file.extend(block(
+            0x02 | HYBRID_FLAG | (15 << 23) | MONO_FLAG | FINAL_BLOCK,
+            &rate,
+        ));
+        let stream = read_wavpack(&mut std::io::Cursor::new(file)).unwrap();
+        assert_eq!(stream.sample_rate, 22_000);
+        assert_eq!(stream.bits_per_sample, Some(24));
+        assert_eq!(stream.num_channels, 3);
+        assert!(!stream.lossless);
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+/// Fold a genre name into the key genres are matched on. Case, spaces and punctuation are
+/// ignored, so "Hip Hop", "Hip-Hop" and "hiphop" share a key.
+pub fn genre_key(name: &str) -> String {
+    let key: String = name
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if key.is_empty() {
+        // names made only of punctuation still need telling apart
+        name.trim().to_lowercase()
+    } else {
+        key
+    }
+}
+
+/// The name a genre is stored under: the canonical spelling from `aliases` when one of its
+/// keys folds to the same key as `name`, otherwise `name` itself. When several entries match,
+/// one matched by its alias wins over one matched by its canonical spelling, then the
+/// longest alias, then the first alias in lexical order, so the pick doesn't depend on the
+/// map's iteration order.
+pub fn canonical_name(name: &str, aliases: &HashMap<String, String>) -> String {
+    let key = genre_key(name);
+    aliases
+        .iter()
+        .filter_map(|(alias, canonical)| {
+            let by_alias = genre_key(alias) == key;
+            (by_alias || genre_key(canonical) == key).then(|| (!by_alias, alias, canonical))
+        })
+        .min_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then_with(|| b.1.len().cmp(&a.1.len()))
+                .then_with(|| a.1.cmp(b.1))
+        })
+        .map(|(_, _, canonical)| canonical.trim().to_string())
+        .unwrap_or_else(|| name.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folds_case_spacing_and_punctuation() {
+        assert_eq!(genre_key("Hip Hop"), "hiphop");
+        assert_eq!(genre_key("Hip-Hop"), "hiphop");
+        assert_eq!(genre_key("hiphop"), "hiphop");
+        assert_eq!(genre_key("R&B"), "rb");
+        assert_eq!(genre_key("J-Pop"), genre_key("jpop"));
+        assert_ne!(genre_key("?"), genre_key("!"));
+    }
+
+    #[test]
+    fn maps_aliases_to_their_canonical_spelling() {
+        let aliases: HashMap<String, String> = vec![
+            ("rap".to_string(), "Hip-Hop".to_string()),
+            ("Drum and Bass".to_string(), "Drum & Bass".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(canonical_name("Rap", &aliases), "Hip-Hop");
+        assert_eq!(canonical_name("hip hop", &aliases), "Hip-Hop");
+        assert_eq!(canonical_name("drum-and-bass", &aliases), "Drum & Bass");
+        assert_eq!(canonical_name(" Jazz ", &aliases), "Jazz");
+    }
+
+    #[test]
+    fn picks_among_matching_aliases_deterministically() {
+        let aliases: HashMap<String, String> = vec![
+            ("hip hop".to_string(), "Hip Hop".to_string()),
+            ("hip-hop".to_string(), "Hip-Hop".to_string()),
+            ("Hip-Hop/Rap".to_string(), "hiphop".to_string()),
+            ("r&b".to_string(), "R&B".to_string()),
+            ("r & b".to_string(), "Rhythm & Blues".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        // matched by alias rather than by the canonical "hiphop", and of the equally long
+        // aliases the first in lexical order
+        assert_eq!(canonical_name("HipHop", &aliases), "Hip Hop");
+        assert_eq!(canonical_name("R-B", &aliases), "Rhythm & Blues");
+    }
+}
@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+
+/// Lyrics gathered for a song, from embedded tags or a sidecar `.lrc` file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lyrics {
+    /// Unsynchronised text, one line per lyric line
+    pub text: Option<String>,
+    /// Time-synced lines, sorted by timestamp
+    pub synced: Vec<LyricLine>,
+    /// Where the lyrics came from: "embedded" or "sidecar"
+    pub source: &'static str,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct LyricLine {
+    pub time_ms: u32,
+    pub text: String,
+}
+
+impl Lyrics {
+    /// Build lyrics from a tag value, which may itself be LRC formatted.
+    pub fn from_tag(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        let synced = parse_lrc(value);
+        let text = if synced.is_empty() {
+            value.to_string()
+        } else {
+            plain_from_synced(&synced)
+        };
+        Some(Lyrics {
+            text: Some(text),
+            synced,
+            source: "embedded",
+        })
+    }
+
+    /// Build lyrics from already-timed lines, e.g. an ID3 SYLT frame.
+    pub fn from_synced(mut synced: Vec<LyricLine>, text: Option<String>) -> Option<Self> {
+        synced.sort_by_key(|l| l.time_ms);
+        let text = text
+            .filter(|t| !t.trim().is_empty())
+            .or_else(|| (!synced.is_empty()).then(|| plain_from_synced(&synced)));
+        if text.is_none() && synced.is_empty() {
+            return None;
+        }
+        Some(Lyrics {
+            text,
+            synced,
+            source: "embedded",
+        })
+    }
+}
+
+fn plain_from_synced(lines: &[LyricLine]) -> String {
+    lines
+        .iter()
+        .map(|l| l.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Read USLT and SYLT frames from an ID3 tag. SYLT frames timed in MPEG frames are skipped.
+pub fn from_id3(tag: &id3::Tag) -> Option<Lyrics> {
+    use id3::frame::TimestampFormat;
+
+    let text = tag.lyrics().map(|l| l.text.clone()).next();
+    let synced: Vec<LyricLine> = tag
+        .synchronised_lyrics()
+        .find(|s| s.timestamp_format == TimestampFormat::Ms)
+        .map(|s| {
+            s.content
+                .iter()
+                .map(|(time_ms, text)| LyricLine {
+                    time_ms: *time_ms,
+                    text: text.trim().to_string(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if synced.is_empty() {
+        text.as_deref().and_then(Lyrics::from_tag)
+    } else {
+        Lyrics::from_synced(synced, text)
+    }
+}
+
+/// Parse a `[mm:ss.xx]` timestamp (also accepts `mm:ss`, `mm:ss:xx` and 1-3 fraction digits).
+fn parse_timestamp(tag: &str) -> Option<u32> {
+    let (minutes, rest) = tag.split_once(':')?;
+    let minutes: u32 = minutes.trim().parse().ok()?;
+    let (seconds, fraction) = match rest.find(|c| c == '.' || c == ':') {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, ""),
+    };
+    let seconds: u32 = seconds.trim().parse().ok()?;
+    if seconds >= 60 {
+        return None;
+    }
+    let millis = match fraction.len() {
+        0 => 0,
+        1 => fraction.parse::<u32>().ok()? * 100,
+        2 => fraction.parse::<u32>().ok()? * 10,
+        _ => fraction.get(..3)?.parse::<u32>().ok()?,
+    };
+    Some(minutes * 60_000 + seconds * 1000 + millis)
+}
+
+/// Parse LRC formatted lyrics into sorted lines. Lines may carry several timestamps,
+/// and an `[offset:±ms]` header shifts every line. Returns an empty list for plain text.
+pub fn parse_lrc(input: &str) -> Vec<LyricLine> {
+    let mut offset: i64 = 0;
+    let mut lines = Vec::new();
+
+    for raw in input.lines() {
+        let mut rest = raw.trim();
+        let mut stamps = Vec::new();
+
+        while let Some(stripped) = rest.strip_prefix('[') {
+            let Some(end) = stripped.find(']') else { break };
+            let tag = &stripped[..end];
+            rest = &stripped[end + 1..];
+
+            if let Some(time) = parse_timestamp(tag) {
+                stamps.push(time);
+            } else if let Some(value) = tag.strip_prefix("offset:") {
+                offset = value.trim().parse().unwrap_or(0);
+            }
+            // other ID tags ([ar:], [ti:], ...) are ignored
+        }
+
+        let text = rest.trim();
+        for stamp in stamps {
+            // a positive offset makes lyrics appear sooner
+            let time = (stamp as i64 - offset).max(0) as u32;
+            lines.push(LyricLine {
+                time_ms: time,
+                text: text.to_string(),
+            });
+        }
+    }
+
+    lines.sort_by_key(|l| l.time_ms);
+    lines
+}
+
+/// Find a sidecar `.lrc` file with the same stem as the audio file.
+pub fn find_sidecar(path: &std::path::Path) -> Option<std::path::PathBuf> {
+    ["lrc", "LRC"]
+        .iter()
+        .map(|ext| path.with_extension(ext))
+        .find(|p| p.is_file())
+}
+
+/// Read and parse a sidecar `.lrc` file.
+pub fn read_sidecar(path: &std::path::Path) -> anyhow::Result<Option<Lyrics>> {
+    let bytes = std::fs::read(path)?;
+    let content = String::from_utf8_lossy(&bytes);
+    // strip a UTF-8 BOM left by some editors
+    let content = content.trim_start_matches('\u{feff}');
+    Ok(Lyrics::from_tag(content).map(|l| Lyrics {
+        source: "sidecar",
+        ..l
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lrc_with_multiple_stamps_and_offset() {
+        let lrc = "[ar:Someone]\n[offset:+500]\n[00:12.34][01:02.5]Hello\n[00:05]First\nplain line";
+        let lines = parse_lrc(lrc);
+        assert_eq!(
+            lines,
+            vec![
+                LyricLine {
+                    time_ms: 4_500,
+                    text: "First".to_string()
+                },
+                LyricLine {
+                    time_ms: 11_840,
+                    text: "Hello".to_string()
+                },
+                LyricLine {
+                    time_ms: 62_000,
+                    text: "Hello".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_text_is_not_synced() {
+        let lyrics = Lyrics::from_tag("just some words\nand more").unwrap();
+        assert!(lyrics.synced.is_empty());
+        assert_eq!(lyrics.text.as_deref(), Some("just some words\nand more"));
+    }
+
+    #[test]
+    fn timestamp_variants() {
+        assert_eq!(parse_timestamp("01:02.345"), Some(62_345));
+        assert_eq!(parse_timestamp("00:01:50"), Some(1_500));
+        assert_eq!(parse_timestamp("ti:Song"), None);
+    }
+}
@@ -1,22 +1,49 @@
-use formats::{aiff::scan_aiff, mp3::scan_mp3, s2hms, wav::scan_wav};
-use tracing::{debug, error, info};
+use formats::{
+    aiff::scan_aiff,
+    dsd::{scan_dff, scan_dsf},
+    monkeys::scan_monkeys,
+    mp3::scan_mp3,
+    mp4::scan_mp4,
+    musepack::scan_musepack,
+    ogg::scan_ogg,
+    s2hms,
+    wav::scan_wav,
+    wavpack::scan_wavpack,
+};
+use tracing::{debug, error, info, warn};
 
 use crate::{config::Config, metadata::formats::flac::scan_flac};
 
 // most of this likely stolen from https://github.com/agersant/polaris/blob/master/src/index/metadata.rs
+pub mod art;
+pub mod classical;
+pub mod credits;
+pub mod cue;
 pub mod deezer;
 pub mod fm;
 pub mod formats;
+pub mod genre;
+pub mod lyrics;
 pub mod musicbrainz;
+pub mod replaygain;
 pub mod spotify;
 pub mod theaudiodb;
+pub mod write;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum AudioFormat {
     Flac,
     Mp3,
     Wav,
     Aiff,
+    Ogg,
+    Opus,
+    Mp4,
+    Dsf,
+    Dff,
+    WavPack,
+    MonkeysAudio,
+    Musepack,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -25,20 +52,39 @@ pub struct AudioMetadata {
     pub name: String,
     pub number: u32,
     pub duration: u32,
+    /// Sample-accurate duration in milliseconds
+    pub duration_ms: u32,
     pub album: String,
     pub album_artist: String,
     pub album_sort: Option<String>,
+    /// Tagged sort names, e.g. "Beatles, The" (ARTISTSORT/TSOP, ALBUMARTISTSORT/TSO2)
+    pub artist_sort: Option<String>,
+    pub album_artist_sort: Option<String>,
     pub artists: Vec<String>,
+    /// The track artist as tagged, before it was split into `artists`
+    pub artist_tag: Option<String>,
     pub genre: Option<Vec<String>>,
     pub picture: Vec<Picture>,
     pub path: std::path::PathBuf,
     pub year: Option<i32>,
     pub disc: Option<u32>,
+    /// Tracks on this song's disc and discs in the release, when tagged
+    pub track_total: Option<u32>,
+    pub disc_total: Option<u32>,
+    /// Title of this song's disc in a multi-disc release (TSST, DISCSUBTITLE)
+    pub disc_subtitle: Option<String>,
     // Audio properties
     pub lossless: bool,
     pub sample_rate: Option<u32>,
     pub bits_per_sample: Option<u8>,
     pub num_channels: Option<u8>,
+    /// Average bitrate in kbps
+    pub bitrate: Option<u32>,
+    /// Samples per channel, excluding encoder delay and padding
+    pub total_samples: Option<u64>,
+    /// Gapless playback: samples to trim from the start and end of the decoded stream
+    pub encoder_delay: Option<u32>,
+    pub encoder_padding: Option<u32>,
     // MusicBrainz IDs
     pub mbid_artist: Option<String>,
     pub mbid_album: Option<String>,
@@ -49,6 +95,17 @@ pub struct AudioMetadata {
     pub bpm: Option<u32>,
     pub copyright: Option<String>,
     pub label: Option<String>,
+    pub lyrics: Option<lyrics::Lyrics>,
+    pub replay_gain: replaygain::ReplayGain,
+    /// Set for virtual tracks cut from a single-file rip by a CUE sheet
+    pub cue: Option<cue::CueSpan>,
+    /// Tagged as part of a compilation (TCMP, COMPILATION, cpil)
+    pub compilation: bool,
+    pub classical: classical::Classical,
+    /// Producers, engineers, session musicians and other contributors
+    pub credits: Vec<credits::Credit>,
+    /// Parental advisory: explicit, clean, or `None` when unrated
+    pub explicit: Option<bool>,
 }
 
 pub struct StreamInfo {
@@ -64,7 +121,137 @@ pub struct Picture {
     pub bytes: Vec<u8>,
 }
 
-pub fn get_filetype(path: &std::path::Path) -> Option<AudioFormat> {
+/// Result of inspecting the leading bytes of a file.
+#[derive(Debug, PartialEq)]
+enum Sniffed {
+    Format(AudioFormat),
+    /// Recognised audio, but in a container or codec we cannot scan
+    Unsupported(&'static str),
+    Unknown,
+}
+
+/// How many bytes past any ID3v2 tag we read when sniffing
+const SNIFF_LEN: usize = 4096;
+
+/// Skip the zero padding some taggers leave between an ID3v2 tag and the first frame.
+fn skip_padding(header: &[u8]) -> &[u8] {
+    match header.iter().position(|&b| b != 0) {
+        Some(start) => &header[start..],
+        None => &[],
+    }
+}
+
+/// Detect the audio format from magic bytes. `header` must start after any ID3v2 tag.
+fn sniff_format(header: &[u8]) -> Sniffed {
+    if header.starts_with(b"fLaC") {
+        return Sniffed::Format(AudioFormat::Flac);
+    }
+    if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WAVE" {
+        return Sniffed::Format(AudioFormat::Wav);
+    }
+    if header.starts_with(b"DSD ") {
+        return Sniffed::Format(AudioFormat::Dsf);
+    }
+    if header.len() >= 16 && &header[0..4] == b"FRM8" && &header[12..16] == b"DSD " {
+        return Sniffed::Format(AudioFormat::Dff);
+    }
+    if header.starts_with(b"wvpk") {
+        return Sniffed::Format(AudioFormat::WavPack);
+    }
+    if header.starts_with(b"MAC ") {
+        return Sniffed::Format(AudioFormat::MonkeysAudio);
+    }
+    if header.starts_with(b"MPCK") {
+        return Sniffed::Format(AudioFormat::Musepack);
+    }
+    if header.len() >= 4 && &header[0..3] == b"MP+" {
+        // the low nibble is the stream version, and only SV7 is readable
+        return match header[3] & 0x0f {
+            7 => Sniffed::Format(AudioFormat::Musepack),
+            _ => Sniffed::Unsupported("Musepack before SV7"),
+        };
+    }
+    if header.len() >= 12 && &header[0..4] == b"FORM" {
+        return match &header[8..12] {
+            b"AIFF" | b"AIFC" => Sniffed::Format(AudioFormat::Aiff),
+            _ => Sniffed::Unknown,
+        };
+    }
+    if header.starts_with(b"OggS") && header.len() > 27 {
+        // first packet follows the segment table of the first page
+        let packet = &header[(27 + header[26] as usize).min(header.len())..];
+        return if packet.starts_with(b"OpusHead") {
+            Sniffed::Format(AudioFormat::Opus)
+        } else if packet.starts_with(b"\x01vorbis") {
+            Sniffed::Format(AudioFormat::Ogg)
+        } else if packet.starts_with(b"\x7fFLAC") {
+            Sniffed::Unsupported("Ogg FLAC")
+        } else {
+            Sniffed::Unsupported("Ogg with an unknown codec")
+        };
+    }
+    if header.len() >= 12 && &header[4..8] == b"ftyp" {
+        // HEIF/AVIF images share the ISO base media container
+        return match &header[8..12] {
+            b"heic" | b"heix" | b"mif1" | b"msf1" | b"avif" => Sniffed::Unknown,
+            _ => Sniffed::Format(AudioFormat::Mp4),
+        };
+    }
+    if header.len() >= 3 && header[0] == 0xFF && header[1] & 0xE0 == 0xE0 {
+        // ADTS shares the 12 bit sync word but has a layer of 0
+        if header[1] & 0xF6 == 0xF0 {
+            return Sniffed::Unsupported("raw AAC (ADTS)");
+        }
+        let version = (header[1] >> 3) & 0x03;
+        let layer = (header[1] >> 1) & 0x03;
+        let bitrate = header[2] >> 4;
+        let sample_rate = (header[2] >> 2) & 0x03;
+        if version != 1 && layer != 0 && bitrate != 0x0F && sample_rate != 0x03 {
+            return Sniffed::Format(AudioFormat::Mp3);
+        }
+    }
+
+    Sniffed::Unknown
+}
+
+/// Read the start of a file, skipping any ID3v2 tag, and sniff the format.
+fn sniff_file(path: &std::path::Path) -> std::io::Result<Sniffed> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = std::fs::File::open(path)?;
+    let mut id3 = [0u8; 10];
+    let mut read = 0;
+    while read < id3.len() {
+        match file.read(&mut id3[read..])? {
+            0 => return Ok(Sniffed::Unknown),
+            n => read += n,
+        }
+    }
+
+    let tagged = &id3[0..3] == b"ID3";
+    if tagged {
+        // synchsafe size excludes the 10 byte header and the optional footer
+        let size = id3[6..10]
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 7) | (b & 0x7F) as u64);
+        let footer = if id3[5] & 0x10 != 0 { 10 } else { 0 };
+        file.seek(SeekFrom::Start(10 + size + footer))?;
+    } else {
+        file.seek(SeekFrom::Start(0))?;
+    }
+
+    let mut header = Vec::with_capacity(SNIFF_LEN);
+    file.take(SNIFF_LEN as u64).read_to_end(&mut header)?;
+    // MP4 boxes start with zero bytes, so padding is only skipped after a tag
+    if tagged {
+        Ok(sniff_format(skip_padding(&header)))
+    } else {
+        Ok(sniff_format(&header))
+    }
+}
+
+/// Map a file extension to the format it conventionally holds.
+fn format_from_extension(path: &std::path::Path) -> Option<AudioFormat> {
     // get extension
     let extension = match path.extension() {
         Some(e) => e,
@@ -76,56 +263,261 @@ pub fn get_filetype(path: &std::path::Path) -> Option<AudioFormat> {
         _ => return None,
     };
     // match extension string to string options
-    // TODO: add more formats and actual format detection past file extension
     match extension.to_lowercase().as_str() {
         "mp3" => Some(AudioFormat::Mp3),
         "wav" => Some(AudioFormat::Wav),
-        "aiff" => Some(AudioFormat::Aiff),
+        "aiff" | "aif" => Some(AudioFormat::Aiff),
         "flac" => Some(AudioFormat::Flac),
+        "ogg" | "oga" => Some(AudioFormat::Ogg),
+        "opus" => Some(AudioFormat::Opus),
+        "m4a" | "m4b" | "mp4" => Some(AudioFormat::Mp4),
+        "dsf" => Some(AudioFormat::Dsf),
+        "dff" => Some(AudioFormat::Dff),
+        "wv" => Some(AudioFormat::WavPack),
+        "ape" => Some(AudioFormat::MonkeysAudio),
+        "mpc" | "mp+" | "mpp" => Some(AudioFormat::Musepack),
         _ => None,
     }
 }
 
+/// Detect the format of a file from its contents, falling back to the extension
+/// when the magic bytes are inconclusive. Mismatches are logged.
+pub fn get_filetype(path: &std::path::Path) -> Option<AudioFormat> {
+    let by_extension = format_from_extension(path);
+
+    let sniffed = match sniff_file(path) {
+        Ok(s) => s,
+        Err(e) => {
+            debug!("could not sniff {}: {}", path.display(), e);
+            Sniffed::Unknown
+        }
+    };
+
+    match sniffed {
+        Sniffed::Format(format) => {
+            match &by_extension {
+                Some(ext) if *ext != format => warn!(
+                    "{} has a {:?} extension but contains {:?}, scanning as {:?}",
+                    path.display(),
+                    ext,
+                    format,
+                    format
+                ),
+                None => debug!(
+                    "{} has no known extension, detected {:?}",
+                    path.display(),
+                    format
+                ),
+                _ => {}
+            }
+            Some(format)
+        }
+        Sniffed::Unsupported(kind) => {
+            if by_extension.is_some() {
+                warn!(
+                    "{} contains {}, which is not supported",
+                    path.display(),
+                    kind
+                );
+            }
+            None
+        }
+        Sniffed::Unknown => {
+            if let Some(ext) = &by_extension {
+                warn!(
+                    "{} has a {:?} extension but its contents were not recognised, trying anyway",
+                    path.display(),
+                    ext
+                );
+            }
+            by_extension
+        }
+    }
+}
+
+/// Read a file's tags and stream properties with the scanner for its format.
+pub async fn scan_format(
+    format: AudioFormat,
+    path: &std::path::PathBuf,
+    cfg: &Config,
+) -> anyhow::Result<AudioMetadata> {
+    match format {
+        // Scan files with vorbis tags
+        AudioFormat::Flac => scan_flac(path, cfg).await,
+        AudioFormat::Ogg | AudioFormat::Opus => scan_ogg(path, cfg).await,
+        // Scan files with id3 tags
+        AudioFormat::Mp3 => scan_mp3(path, cfg).await,
+        AudioFormat::Wav => scan_wav(path, cfg).await,
+        AudioFormat::Aiff => scan_aiff(path, cfg).await,
+        // Scan files with MP4 atoms
+        AudioFormat::Mp4 => scan_mp4(path, cfg).await,
+        // Scan DSD files, ID3 tagged
+        AudioFormat::Dsf => scan_dsf(path, cfg).await,
+        AudioFormat::Dff => scan_dff(path, cfg).await,
+        // Scan files with APEv2 tags
+        AudioFormat::WavPack => scan_wavpack(path, cfg).await,
+        AudioFormat::MonkeysAudio => scan_monkeys(path, cfg).await,
+        AudioFormat::Musepack => scan_musepack(path, cfg).await,
+    }
+}
+
 pub async fn scan_file(
     path: &std::path::PathBuf,
     pool: sqlx::Pool<sqlx::Postgres>,
     dry_run: bool,
     cfg: &Config,
 ) {
+    // a tag edit in progress, about to be renamed over the real file
+    if path.extension().and_then(|e| e.to_str()) == Some(write::PARTIAL_EXTENSION) {
+        return;
+    }
     let m = match get_filetype(path) {
-        // Scan files with vorbis tags
-        Some(AudioFormat::Flac) => scan_flac(path, cfg).await,
-        // Scan files with id3 tags
-        Some(AudioFormat::Mp3) => scan_mp3(path, cfg).await,
-        Some(AudioFormat::Wav) => scan_wav(path, cfg).await,
-        Some(AudioFormat::Aiff) => scan_aiff(path, cfg).await,
+        Some(format) => scan_format(format, path, cfg).await,
         None => return,
     };
 
-    let meta = match m {
+    let mut meta = match m {
         Ok(meta) => meta,
         Err(e) => return error!("failed to scan {}: {}", path.display(), e),
     };
 
-    let fmtd = format!(
-        "{}. {} by {} ({})",
-        meta.number,
-        meta.name,
-        meta.album_artist,
-        s2hms(meta.duration)
-    );
+    // a sidecar .lrc next to the file takes precedence over embedded lyrics
+    if let Some(lrc) = lyrics::find_sidecar(path) {
+        match lyrics::read_sidecar(&lrc) {
+            Ok(Some(l)) => meta.lyrics = Some(l),
+            Ok(None) => {}
+            Err(e) => warn!("failed to read lyrics from {}: {}", lrc.display(), e),
+        }
+    }
+
+    // a CUE sheet turns a single-file rip into one virtual track per sheet track
+    let songs = match cue::find_for(path) {
+        Some((sheet_path, sheet)) => match sheet.file_for(path).filter(|f| f.tracks.len() > 1) {
+            Some(file) => {
+                debug!(
+                    "splitting {} into {} tracks with {}",
+                    path.display(),
+                    file.tracks.len(),
+                    sheet_path.display()
+                );
+                cue::split(&meta, &sheet, file, &cfg.artist_split_exceptions)
+            }
+            None => vec![meta],
+        },
+        None => vec![meta],
+    };
+    let cue_tracks: Vec<i32> = songs
+        .iter()
+        .filter_map(|s| s.cue.map(|c| c.track as i32))
+        .collect();
+
+    for meta in songs {
+        let fmtd = format!(
+            "{}. {} by {} ({})",
+            meta.number,
+            meta.name,
+            meta.album_artist,
+            s2hms(meta.duration)
+        );
+
+        if !dry_run {
+            crate::index::db::add_song(meta, pool.clone(), cfg).await;
+        } else {
+            info!("dry run: would have added song {}", fmtd);
+            debug!("Image count: {}", meta.picture.len());
+            debug!(
+                "Artist count: {} - {}",
+                meta.artists.len(),
+                meta.artists.join(", ")
+            );
+        }
+
+        debug!("sucessfully scanned {}", fmtd);
+    }
 
+    // drop virtual tracks the sheet no longer lists, or the whole-file song it replaced
     if !dry_run {
-        crate::index::db::add_song(meta, pool).await;
-    } else {
-        info!("dry run: would have added song {}", fmtd);
-        debug!("Image count: {}", meta.picture.len());
-        debug!(
-            "Artist count: {} - {}",
-            meta.artists.len(),
-            meta.artists.join(", ")
+        if let Err(e) = crate::index::db::prune_file_songs(path, &cue_tracks, &pool).await {
+            error!("failed to prune old tracks for {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_containers() {
+        assert_eq!(
+            sniff_format(b"fLaC\0\0\0\x22"),
+            Sniffed::Format(AudioFormat::Flac)
+        );
+        assert_eq!(
+            sniff_format(b"RIFF\x24\0\0\0WAVEfmt "),
+            Sniffed::Format(AudioFormat::Wav)
+        );
+        assert_eq!(
+            sniff_format(b"FORM\0\0\0\0AIFCFVER"),
+            Sniffed::Format(AudioFormat::Aiff)
+        );
+        assert_eq!(
+            sniff_format(b"\0\0\0\x20ftypM4A \0\0\0\0"),
+            Sniffed::Format(AudioFormat::Mp4)
+        );
+        assert_eq!(
+            sniff_format(b"DSD \x1c\0\0\0\0\0\0\0"),
+            Sniffed::Format(AudioFormat::Dsf)
+        );
+        assert_eq!(
+            sniff_format(b"FRM8\0\0\0\0\0\0\x10\0DSD FVER"),
+            Sniffed::Format(AudioFormat::Dff)
         );
+        assert_eq!(
+            sniff_format(b"wvpk\x2c\0\0\0\x10\x04"),
+            Sniffed::Format(AudioFormat::WavPack)
+        );
+        assert_eq!(
+            sniff_format(b"MAC \x96\x0f\0\0"),
+            Sniffed::Format(AudioFormat::MonkeysAudio)
+        );
+        assert_eq!(
+            sniff_format(b"MP+\x17\0\0"),
+            Sniffed::Format(AudioFormat::Musepack)
+        );
+        assert_eq!(
+            sniff_format(b"MP+\x06\0\0"),
+            Sniffed::Unsupported("Musepack before SV7")
+        );
+        assert_eq!(sniff_format(b"\0\0\0\x18ftypheic"), Sniffed::Unknown);
     }
 
-    debug!("sucessfully scanned {}", fmtd);
+    #[test]
+    fn sniffs_ogg_codecs() {
+        let mut page = b"OggS".to_vec();
+        page.extend_from_slice(&[0u8; 22]);
+        page.push(1);
+        page.push(19);
+        let mut opus = page.clone();
+        opus.extend_from_slice(b"OpusHead");
+        assert_eq!(sniff_format(&opus), Sniffed::Format(AudioFormat::Opus));
+        let mut vorbis = page;
+        vorbis.extend_from_slice(b"\x01vorbis");
+        assert_eq!(sniff_format(&vorbis), Sniffed::Format(AudioFormat::Ogg));
+    }
+
+    #[test]
+    fn sniffs_mpeg_sync() {
+        // MPEG-1 layer III, 128 kbps, 44.1 kHz
+        assert_eq!(
+            sniff_format(skip_padding(&[0, 0, 0xFF, 0xFB, 0x90, 0x64])),
+            Sniffed::Format(AudioFormat::Mp3)
+        );
+        assert_eq!(sniff_format(skip_padding(&[0, 0, 0])), Sniffed::Unknown);
+        assert_eq!(
+            sniff_format(&[0xFF, 0xF1, 0x50, 0x80]),
+            Sniffed::Unsupported("raw AAC (ADTS)")
+        );
+        assert_eq!(sniff_format(b"\x89PNG\r\n"), Sniffed::Unknown);
+    }
 }
@@ -2,10 +2,13 @@ use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
 use serde::Deserialize;
 use std::num::NonZeroU32;
 use std::sync::OnceLock;
-use tracing::{debug, error};
+use tracing::debug;
 
 static USER_AGENT: &str = "Muse/0.1.0 ( contact@muse.moe )";
 
+/// MusicBrainz's special purpose artist credited on compilations.
+pub const VARIOUS_ARTISTS_MBID: &str = "89ad4ac3-39f7-470e-963a-56509c546377";
+
 static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
 static RATE_LIMITER: OnceLock<DefaultDirectRateLimiter> = OnceLock::new();
 
@@ -34,11 +37,72 @@ pub struct MbArtist {
     pub name: String,
 }
 
+/// An artist's sort name and the other names they go by, from an artist lookup.
+#[derive(Debug, Deserialize)]
+pub struct MbArtistNames {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "sort-name")]
+    pub sort_name: Option<String>,
+    #[serde(default)]
+    pub aliases: Vec<MbAlias>,
+}
+
+/// Another name for an artist: a romanisation, a native-script spelling, a legal name or
+/// a common misspelling.
+#[derive(Debug, Deserialize)]
+pub struct MbAlias {
+    pub name: String,
+    #[serde(rename = "sort-name")]
+    pub sort_name: Option<String>,
+    pub locale: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct MbReleaseGroup {
     pub id: String,
     pub title: String,
     pub disambiguation: Option<String>,
+    #[serde(rename = "primary-type")]
+    pub primary_type: Option<String>,
+    #[serde(rename = "secondary-types", default)]
+    pub secondary_types: Option<Vec<String>>,
+    #[serde(rename = "first-release-date")]
+    pub first_release_date: Option<String>,
+}
+
+impl MbReleaseGroup {
+    /// Primary type, lowercased: `album`, `single`, `ep`, `broadcast` or `other`.
+    pub fn release_type(&self) -> Option<String> {
+        self.primary_type.as_deref().map(str::to_lowercase)
+    }
+
+    /// Secondary types such as `compilation`, `live` or `remix`, lowercased.
+    pub fn secondary_types(&self) -> Vec<String> {
+        self.secondary_types
+            .iter()
+            .flatten()
+            .map(|t| t.to_lowercase())
+            .collect()
+    }
+
+    /// First release date of the group, as precise as MusicBrainz knows it.
+    pub fn original_date(&self) -> Option<String> {
+        self.first_release_date
+            .as_deref()
+            .and_then(parse_partial_date)
+    }
+}
+
+/// Validate a MusicBrainz partial date: `YYYY`, `YYYY-MM` or `YYYY-MM-DD`.
+fn parse_partial_date(date: &str) -> Option<String> {
+    let date = date.trim();
+    let parts: Vec<&str> = date.split('-').collect();
+    let valid = parts.len() <= 3
+        && parts[0].len() == 4
+        && parts[1..].iter().all(|p| p.len() == 2)
+        && parts.iter().all(|p| p.chars().all(|c| c.is_ascii_digit()));
+    valid.then(|| date.to_string())
 }
 
 #[derive(Debug, Deserialize)]
@@ -47,7 +111,19 @@ pub struct MbRecording {
     pub title: String,
 }
 
-/// Search for an artist by name and return the best match MBID
+/// One artist in a recording's artist credit, e.g. "Daft Punk" with the join phrase
+/// " feat. " before "Pharrell Williams".
+#[derive(Debug, Deserialize)]
+pub struct MbArtistCredit {
+    /// The name as credited, which can differ from the artist's own name
+    pub name: String,
+    #[serde(default)]
+    pub joinphrase: String,
+    pub artist: MbArtist,
+}
+
+/// Search for an artist by name and return the best match MBID. None when nothing
+/// matches; a failed search, like rate limiting, is an error.
 pub async fn get_artist_mbid(name: &str) -> anyhow::Result<Option<String>> {
     limiter().until_ready().await;
 
@@ -62,8 +138,7 @@ pub async fn get_artist_mbid(name: &str) -> anyhow::Result<Option<String>> {
     let res = client().get(&url).send().await?;
 
     if !res.status().is_success() {
-        error!("MusicBrainz API error: {}", res.status());
-        return Ok(None);
+        anyhow::bail!("MusicBrainz API error: {}", res.status());
     }
 
     let body: SearchResponse<MbArtist> = res.json().await?;
@@ -77,7 +152,67 @@ pub async fn get_artist_mbid(name: &str) -> anyhow::Result<Option<String>> {
     }
 }
 
-/// Fetch the front cover art bytes for a release from the Cover Art Archive
+/// Look up an artist's sort name and aliases by MBID. None when MusicBrainz doesn't know
+/// the artist; other failures, like rate limiting, are errors.
+pub async fn get_artist_names(mbid: &str) -> anyhow::Result<Option<MbArtistNames>> {
+    limiter().until_ready().await;
+
+    let url = format!(
+        "https://musicbrainz.org/ws/2/artist/{}?inc=aliases&fmt=json",
+        urlencoding::encode(mbid)
+    );
+
+    debug!("Fetching MusicBrainz artist aliases: {}", mbid);
+
+    let res = client().get(&url).send().await?;
+
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        debug!("MusicBrainz artist not found for MBID: {}", mbid);
+        return Ok(None);
+    }
+    if !res.status().is_success() {
+        anyhow::bail!("MusicBrainz API error: {}", res.status());
+    }
+
+    Ok(Some(res.json().await?))
+}
+
+/// Look up the artists credited on a recording by its MBID, in credit order. None when
+/// MusicBrainz doesn't know the recording; other failures, like rate limiting, are errors.
+pub async fn get_recording_artist_credit(
+    mbid: &str,
+) -> anyhow::Result<Option<Vec<MbArtistCredit>>> {
+    limiter().until_ready().await;
+
+    #[derive(Deserialize)]
+    struct RecordingResponse {
+        #[serde(rename = "artist-credit", default)]
+        artist_credit: Vec<MbArtistCredit>,
+    }
+
+    let url = format!(
+        "https://musicbrainz.org/ws/2/recording/{}?inc=artist-credits&fmt=json",
+        urlencoding::encode(mbid)
+    );
+
+    debug!("Fetching MusicBrainz artist credit for recording: {}", mbid);
+
+    let res = client().get(&url).send().await?;
+
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        debug!("MusicBrainz recording not found for MBID: {}", mbid);
+        return Ok(None);
+    }
+    if !res.status().is_success() {
+        anyhow::bail!("MusicBrainz API error: {}", res.status());
+    }
+
+    let recording: RecordingResponse = res.json().await?;
+    Ok(Some(recording.artist_credit))
+}
+
+/// Fetch the front cover art bytes for a release from the Cover Art Archive. None when the
+/// release has no front cover; other failures are errors.
 pub async fn get_cover_art_bytes(mbid: &str) -> anyhow::Result<Option<Vec<u8>>> {
     limiter().until_ready().await;
 
@@ -87,16 +222,20 @@ pub async fn get_cover_art_bytes(mbid: &str) -> anyhow::Result<Option<Vec<u8>>>
 
     let res = client().get(&url).send().await?;
 
-    if !res.status().is_success() {
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
         debug!("No cover art found on CAA for MBID: {}", mbid);
         return Ok(None);
     }
+    if !res.status().is_success() {
+        anyhow::bail!("Cover Art Archive error: {}", res.status());
+    }
 
     let bytes = res.bytes().await?;
     Ok(Some(bytes.to_vec()))
 }
 
-/// Search for a recording (track) by title and artist name and return the best match MBID
+/// Search for a recording (track) by title and artist name and return the best match MBID.
+/// None when nothing matches; a failed search is an error.
 pub async fn get_track_mbid(title: &str, artist_name: &str) -> anyhow::Result<Option<String>> {
     limiter().until_ready().await;
 
@@ -114,8 +253,7 @@ pub async fn get_track_mbid(title: &str, artist_name: &str) -> anyhow::Result<Op
     let res = client().get(&url).send().await?;
 
     if !res.status().is_success() {
-        error!("MusicBrainz API error: {}", res.status());
-        return Ok(None);
+        anyhow::bail!("MusicBrainz API error: {}", res.status());
     }
 
     let body: SearchResponse<MbRecording> = res.json().await?;
@@ -133,7 +271,8 @@ pub async fn get_track_mbid(title: &str, artist_name: &str) -> anyhow::Result<Op
 }
 
 /// Search for a release group (album) by title and artist name.
-/// Returns the best match including its disambiguation string if present.
+/// Returns the best match including its disambiguation string if present, None when
+/// nothing matches; a failed search is an error.
 pub async fn get_album_info(
     title: &str,
     artist_name: &str,
@@ -154,8 +293,7 @@ pub async fn get_album_info(
     let res = client().get(&url).send().await?;
 
     if !res.status().is_success() {
-        error!("MusicBrainz API error: {}", res.status());
-        return Ok(None);
+        anyhow::bail!("MusicBrainz API error: {}", res.status());
     }
 
     let body: SearchResponse<MbReleaseGroup> = res.json().await?;
@@ -174,7 +312,8 @@ pub async fn get_album_info(
 
 /// Fetch release group info (including disambiguation) for a release MBID.
 /// File tags store the release MBID (MUSICBRAINZ_ALBUMID), not the release-group MBID,
-/// so we look up the release and extract the embedded release-group.
+/// so we look up the release and extract the embedded release-group. None when MusicBrainz
+/// doesn't know the release; other failures are errors.
 pub async fn get_release_group_info(release_mbid: &str) -> anyhow::Result<Option<MbReleaseGroup>> {
     limiter().until_ready().await;
 
@@ -193,11 +332,66 @@ pub async fn get_release_group_info(release_mbid: &str) -> anyhow::Result<Option
 
     let res = client().get(&url).send().await?;
 
-    if !res.status().is_success() {
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
         debug!("MusicBrainz release not found for MBID: {}", release_mbid);
         return Ok(None);
     }
+    if !res.status().is_success() {
+        anyhow::bail!("MusicBrainz API error: {}", res.status());
+    }
 
     let release: ReleaseResponse = res.json().await?;
     Ok(release.release_group)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_partial_dates() {
+        assert_eq!(parse_partial_date("1999"), Some("1999".to_string()));
+        assert_eq!(parse_partial_date("1999-05"), Some("1999-05".to_string()));
+        assert_eq!(
+            parse_partial_date("1999-05-03"),
+            Some("1999-05-03".to_string())
+        );
+        assert_eq!(parse_partial_date(""), None);
+        assert_eq!(parse_partial_date("99-05"), None);
+    }
+
+    #[test]
+    fn reads_release_group_types() {
+        let rg: MbReleaseGroup = serde_json::from_str(
+            r#"{"id":"x","title":"t","primary-type":"EP","secondary-types":["Live","Remix"],"first-release-date":"2004-02"}"#,
+        )
+        .unwrap();
+        assert_eq!(rg.release_type(), Some("ep".to_string()));
+        assert_eq!(rg.secondary_types(), vec!["live", "remix"]);
+        assert_eq!(rg.original_date(), Some("2004-02".to_string()));
+    }
+
+    #[test]
+    fn reads_artist_aliases() {
+        let artist: MbArtistNames = serde_json::from_str(
+            r#"{"id":"x","name":"宇多田ヒカル","sort-name":"Utada, Hikaru","aliases":[{"name":"Hikaru Utada","sort-name":"Utada, Hikaru","locale":"en","primary":true,"type":"Artist name"},{"name":"Utada","sort-name":"Utada","locale":null,"primary":null,"type":null}]}"#,
+        )
+        .unwrap();
+        assert_eq!(artist.sort_name.as_deref(), Some("Utada, Hikaru"));
+        assert_eq!(artist.aliases.len(), 2);
+        assert_eq!(artist.aliases[0].locale.as_deref(), Some("en"));
+        assert_eq!(artist.aliases[1].locale, None);
+    }
+
+    #[test]
+    fn reads_artist_credits() {
+        let credits: Vec<MbArtistCredit> = serde_json::from_str(
+            r#"[{"name":"Crosby, Stills, Nash & Young","joinphrase":" with ","artist":{"id":"a","name":"Crosby, Stills, Nash & Young"}},{"name":"Joni","artist":{"id":"b","name":"Joni Mitchell"}}]"#,
+        )
+        .unwrap();
+        assert_eq!(credits[0].name, "Crosby, Stills, Nash & Young");
+        assert_eq!(credits[0].joinphrase, " with ");
+        assert_eq!(credits[1].joinphrase, "");
+        assert_eq!(credits[1].artist.name, "Joni Mitchell");
+    }
+}
@@ -0,0 +1,252 @@
+use std::process::Stdio;
+
+use tokio::process::Command;
+
+use crate::metadata::cue::CueSpan;
+
+/// ReplayGain 2.0 reference loudness, in LUFS.
+pub const REFERENCE_LUFS: f32 = -18.0;
+
+/// ReplayGain values for a song. Gains are in dB, peaks are linear sample values (1.0 = full scale).
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct ReplayGain {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    /// Build from `REPLAYGAIN_*` style fields, as found in Vorbis comments, ID3 TXXX
+    /// frames and MP4 freeform atoms. `get` is called with upper-case keys.
+    pub fn from_fields<F: Fn(&str) -> Option<String>>(get: F) -> Self {
+        ReplayGain {
+            track_gain: get("REPLAYGAIN_TRACK_GAIN").and_then(|v| parse_gain(&v)),
+            track_peak: get("REPLAYGAIN_TRACK_PEAK").and_then(|v| parse_peak(&v)),
+            album_gain: get("REPLAYGAIN_ALBUM_GAIN").and_then(|v| parse_gain(&v)),
+            album_peak: get("REPLAYGAIN_ALBUM_PEAK").and_then(|v| parse_peak(&v)),
+        }
+    }
+
+    /// Fill gains missing here from Opus `R128_*_GAIN` tags, which are Q7.8 dB
+    /// relative to -23 LUFS.
+    pub fn or_r128<F: Fn(&str) -> Option<String>>(self, get: F) -> Self {
+        let r128 = |key: &str| {
+            get(key)
+                .and_then(|v| v.trim().parse::<i16>().ok())
+                .map(|q| q as f32 / 256.0 + (REFERENCE_LUFS + 23.0))
+        };
+        ReplayGain {
+            track_gain: self.track_gain.or_else(|| r128("R128_TRACK_GAIN")),
+            album_gain: self.album_gain.or_else(|| r128("R128_ALBUM_GAIN")),
+            ..self
+        }
+    }
+}
+
+/// Parse a gain such as `-6.48 dB`.
+pub fn parse_gain(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let value = match value.len().checked_sub(2) {
+        Some(i) if value.is_char_boundary(i) && value[i..].eq_ignore_ascii_case("db") => {
+            &value[..i]
+        }
+        _ => value,
+    };
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|g| g.is_finite() && g.abs() < 100.0)
+}
+
+/// Parse a linear peak such as `0.988553`.
+pub fn parse_peak(value: &str) -> Option<f32> {
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|p| p.is_finite() && *p >= 0.0)
+}
+
+/// Read ReplayGain from TXXX frames, falling back to RVA2 frames for anything missing.
+pub fn from_id3(tag: &id3::Tag) -> ReplayGain {
+    use id3::frame::Content;
+
+    let rg = ReplayGain::from_fields(|key| {
+        tag.extended_texts()
+            .find(|t| t.description.eq_ignore_ascii_case(key))
+            .map(|t| t.value.clone())
+    });
+
+    let rva2 = tag
+        .frames()
+        .filter(|f| f.id() == "RVA2")
+        .filter_map(|f| match f.content() {
+            Content::Unknown(u) => parse_rva2(&u.data),
+            _ => None,
+        });
+    // an RVA2 frame with any other identification is treated as the track adjustment
+    let (mut track, mut album) = (None, None);
+    for (ident, gain, peak) in rva2 {
+        if ident.eq_ignore_ascii_case("album") {
+            album.get_or_insert((gain, peak));
+        } else {
+            track.get_or_insert((gain, peak));
+        }
+    }
+
+    ReplayGain {
+        track_gain: rg.track_gain.or(track.map(|t| t.0)),
+        track_peak: rg.track_peak.or(track.and_then(|t| t.1)),
+        album_gain: rg.album_gain.or(album.map(|a| a.0)),
+        album_peak: rg.album_peak.or(album.and_then(|a| a.1)),
+    }
+}
+
+/// Parse an RVA2 frame body into `(identification, gain, peak)` using the master volume channel.
+fn parse_rva2(data: &[u8]) -> Option<(String, f32, Option<f32>)> {
+    let nul = data.iter().position(|&b| b == 0)?;
+    let ident = String::from_utf8_lossy(&data[..nul]).to_string();
+    let mut rest = &data[nul + 1..];
+
+    // channel (1), adjustment (2, signed /512 dB), peak bits (1), peak (bits rounded up to bytes)
+    while rest.len() >= 4 {
+        let channel = rest[0];
+        let gain = i16::from_be_bytes([rest[1], rest[2]]) as f32 / 512.0;
+        let bits = rest[3] as usize;
+        let len = (bits + 7) / 8;
+        let peak_bytes = rest.get(4..4 + len)?;
+        rest = &rest[4 + len..];
+
+        if channel != 1 {
+            continue;
+        }
+        let peak = (bits > 0 && len <= 8).then(|| {
+            let raw = peak_bytes
+                .iter()
+                .fold(0u64, |acc, &b| (acc << 8) | b as u64);
+            // the value is left-aligned in its bytes when bits is not a multiple of 8
+            let raw = raw >> (len * 8 - bits);
+            raw as f32 / (1u64 << (bits - 1)) as f32
+        });
+        return Some((ident, gain, peak));
+    }
+    None
+}
+
+/// Measure a file's loudness with ffmpeg's EBU R128 filter and return its track gain and peak.
+/// `span` limits the measurement to one CUE virtual track.
+pub async fn measure(path: &std::path::Path, span: Option<&CueSpan>) -> anyhow::Result<ReplayGain> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostats"])
+        .args(span.map(|s| s.input_args()).unwrap_or_default())
+        .arg("-i")
+        .arg(path)
+        .args([
+            "-map",
+            "0:a:0",
+            "-af",
+            "ebur128=peak=true",
+            "-f",
+            "null",
+            "-",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .await?;
+    if !output.status.success() {
+        anyhow::bail!("ffmpeg exited with status: {}", output.status);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let (loudness, peak_db) = parse_ebur128_summary(&stderr)
+        .ok_or_else(|| anyhow::anyhow!("no loudness summary in ffmpeg output"))?;
+    Ok(ReplayGain {
+        track_gain: Some(REFERENCE_LUFS - loudness),
+        track_peak: peak_db.map(|db| 10f32.powf(db / 20.0)),
+        ..Default::default()
+    })
+}
+
+/// Pull integrated loudness (LUFS) and true peak (dBFS) out of the ebur128 filter summary.
+fn parse_ebur128_summary(stderr: &str) -> Option<(f32, Option<f32>)> {
+    let summary = &stderr[stderr.rfind("Summary:")?..];
+    let value = |label: &str| {
+        summary
+            .lines()
+            .map(str::trim)
+            .find_map(|l| l.strip_prefix(label))
+            .and_then(|v| v.split_whitespace().next())
+            .and_then(|v| v.parse::<f32>().ok())
+            .filter(|v| v.is_finite())
+    };
+    Some((value("I:")?, value("Peak:")))
+}
+
+/// Derive album gain and peak from per-track values, weighting each track's
+/// loudness by its duration. Takes `(track_gain, track_peak, duration_ms)`.
+pub fn album_from_tracks(tracks: &[(f32, Option<f32>, u32)]) -> Option<(f32, Option<f32>)> {
+    let total_ms: f64 = tracks.iter().map(|t| t.2 as f64).sum();
+    if tracks.is_empty() || total_ms <= 0.0 {
+        return None;
+    }
+    // loudness is logarithmic, so average the underlying energy
+    let energy: f64 = tracks
+        .iter()
+        .map(|&(gain, _, ms)| {
+            let loudness = (REFERENCE_LUFS - gain) as f64;
+            10f64.powf(loudness / 10.0) * ms as f64
+        })
+        .sum::<f64>()
+        / total_ms;
+    let loudness = 10.0 * energy.log10();
+    let peak = tracks
+        .iter()
+        .filter_map(|t| t.1)
+        .fold(None, |max: Option<f32>, p| {
+            Some(max.map_or(p, |m| m.max(p)))
+        });
+    Some((REFERENCE_LUFS - loudness as f32, peak))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gain_and_peak_strings() {
+        assert_eq!(parse_gain("-6.48 dB"), Some(-6.48));
+        assert_eq!(parse_gain("+2.10dB"), Some(2.10));
+        assert_eq!(parse_gain("loud"), None);
+        assert_eq!(parse_peak(" 0.988553 "), Some(0.988553));
+    }
+
+    #[test]
+    fn parses_rva2_master_channel() {
+        // "track\0", master channel, -3 dB (-1536/512), 16 bit peak of 0x4000
+        let mut data = b"track\0".to_vec();
+        data.extend_from_slice(&[1, 0xFA, 0x00, 16, 0x40, 0x00]);
+        assert_eq!(
+            parse_rva2(&data),
+            Some(("track".to_string(), -3.0, Some(0.5)))
+        );
+    }
+
+    #[test]
+    fn parses_ebur128_summary() {
+        let stderr = "[Parsed_ebur128_0 @ 0x0] Summary:\n\n  Integrated loudness:\n    I:         -14.2 LUFS\n    Threshold: -24.6 LUFS\n\n  True peak:\n    Peak:        -0.5 dBFS\n";
+        assert_eq!(parse_ebur128_summary(stderr), Some((-14.2, Some(-0.5))));
+    }
+
+    #[test]
+    fn album_gain_weights_by_duration() {
+        let (gain, peak) =
+            album_from_tracks(&[(-4.0, Some(0.9), 60_000), (-4.0, Some(0.7), 120_000)]).unwrap();
+        assert!((gain + 4.0).abs() < 1e-4);
+        assert_eq!(peak, Some(0.9));
+        assert_eq!(album_from_tracks(&[]), None);
+    }
+}
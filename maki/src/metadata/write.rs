@@ -0,0 +1,553 @@
+use std::{
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use id3::{partial_tag_ok, TagLike};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::metadata::{
+    formats::{
+        apev2::{ApeItem, ApeItems},
+        ogg::rewrite_comments,
+    },
+    AudioFormat, AudioMetadata,
+};
+
+/// Tag changes for a track. Fields left out are untouched; an empty string, an empty
+/// list, a zero number or `false` removes the tag from the file.
+#[derive(Debug, Default, Clone, Deserialize, ToSchema)]
+pub struct TagEdit {
+    pub title: Option<String>,
+    pub artists: Option<Vec<String>>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track: Option<u32>,
+    pub disc: Option<u32>,
+    pub year: Option<i32>,
+    pub genre: Option<Vec<String>>,
+    pub composer: Option<String>,
+    pub isrc: Option<String>,
+    pub bpm: Option<u32>,
+    pub copyright: Option<String>,
+    pub label: Option<String>,
+    pub compilation: Option<bool>,
+}
+
+/// Tag changes applied to every track of an album, limited to album-wide fields.
+#[derive(Debug, Default, Clone, Deserialize, ToSchema)]
+pub struct AlbumTagEdit {
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub year: Option<i32>,
+    pub genre: Option<Vec<String>>,
+    pub copyright: Option<String>,
+    pub label: Option<String>,
+    pub compilation: Option<bool>,
+}
+
+impl From<AlbumTagEdit> for TagEdit {
+    fn from(edit: AlbumTagEdit) -> Self {
+        TagEdit {
+            album: edit.album,
+            album_artist: edit.album_artist,
+            year: edit.year,
+            genre: edit.genre,
+            copyright: edit.copyright,
+            label: edit.label,
+            compilation: edit.compilation,
+            ..Default::default()
+        }
+    }
+}
+
+/// A field whose value in the file differs from the requested one. Multiple values
+/// are joined with `; `, and a missing tag is `null`.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct TagChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Title,
+    Artists,
+    Album,
+    AlbumArtist,
+    Track,
+    Disc,
+    Year,
+    Genre,
+    Composer,
+    Isrc,
+    Bpm,
+    Copyright,
+    Label,
+    Compilation,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Field::Title => "title",
+            Field::Artists => "artists",
+            Field::Album => "album",
+            Field::AlbumArtist => "album_artist",
+            Field::Track => "track",
+            Field::Disc => "disc",
+            Field::Year => "year",
+            Field::Genre => "genre",
+            Field::Composer => "composer",
+            Field::Isrc => "isrc",
+            Field::Bpm => "bpm",
+            Field::Copyright => "copyright",
+            Field::Label => "label",
+            Field::Compilation => "compilation",
+        }
+    }
+
+    fn id3_frame(self) -> &'static str {
+        match self {
+            Field::Title => "TIT2",
+            Field::Artists => "TPE1",
+            Field::Album => "TALB",
+            Field::AlbumArtist => "TPE2",
+            Field::Track => "TRCK",
+            Field::Disc => "TPOS",
+            Field::Year => "TYER",
+            Field::Genre => "TCON",
+            Field::Composer => "TCOM",
+            Field::Isrc => "TSRC",
+            Field::Bpm => "TBPM",
+            Field::Copyright => "TCOP",
+            Field::Label => "TPUB",
+            Field::Compilation => "TCMP",
+        }
+    }
+
+    /// Vorbis comment key, followed by keys the scanners also read for this field,
+    /// which are removed so they can't shadow the new value.
+    fn vorbis_keys(self) -> &'static [&'static str] {
+        match self {
+            Field::Title => &["TITLE"],
+            Field::Artists => &["ARTIST", "ARTISTS"],
+            Field::Album => &["ALBUM"],
+            Field::AlbumArtist => &["ALBUMARTIST", "ALBUM ARTIST"],
+            Field::Track => &["TRACKNUMBER"],
+            Field::Disc => &["DISCNUMBER"],
+            Field::Year => &["DATE", "YEAR"],
+            Field::Genre => &["GENRE"],
+            Field::Composer => &["COMPOSER"],
+            Field::Isrc => &["ISRC"],
+            Field::Bpm => &["BPM"],
+            Field::Copyright => &["COPYRIGHT"],
+            Field::Label => &["LABEL", "ORGANIZATION"],
+            Field::Compilation => &["COMPILATION"],
+        }
+    }
+
+    /// APEv2 item key, followed by aliases as for [`Field::vorbis_keys`].
+    fn ape_keys(self) -> &'static [&'static str] {
+        match self {
+            Field::Title => &["Title"],
+            Field::Artists => &["Artist", "Artists"],
+            Field::Album => &["Album"],
+            Field::AlbumArtist => &["Album Artist", "AlbumArtist"],
+            Field::Track => &["Track", "TrackNumber"],
+            Field::Disc => &["Disc", "DiscNumber"],
+            Field::Year => &["Year", "Date"],
+            Field::Genre => &["Genre"],
+            Field::Composer => &["Composer"],
+            Field::Isrc => &["ISRC"],
+            Field::Bpm => &["BPM"],
+            Field::Copyright => &["Copyright"],
+            Field::Label => &["Label", "Publisher"],
+            Field::Compilation => &["Compilation"],
+        }
+    }
+
+    /// The value `meta` was scanned with, in the same form as [`TagEdit::values`].
+    fn current(self, meta: &AudioMetadata) -> Vec<String> {
+        let text = |s: &str| match s.trim() {
+            "" => vec![],
+            s => vec![s.to_string()],
+        };
+        let opt = |s: &Option<String>| s.as_deref().map(text).unwrap_or_default();
+        let number = |n: Option<u32>| n.filter(|&n| n > 0).map(|n| vec![n.to_string()]);
+        match self {
+            Field::Title => text(&meta.name),
+            Field::Artists => meta.artists.clone(),
+            Field::Album => text(&meta.album),
+            Field::AlbumArtist => text(&meta.album_artist),
+            Field::Track => number(Some(meta.number)).unwrap_or_default(),
+            Field::Disc => number(meta.disc).unwrap_or_default(),
+            Field::Year => meta.year.map(|y| vec![y.to_string()]).unwrap_or_default(),
+            Field::Genre => meta.genre.clone().unwrap_or_default(),
+            Field::Composer => opt(&meta.composer),
+            Field::Isrc => opt(&meta.isrc),
+            Field::Bpm => number(meta.bpm).unwrap_or_default(),
+            Field::Copyright => opt(&meta.copyright),
+            Field::Label => opt(&meta.label),
+            Field::Compilation => match meta.compilation {
+                true => vec!["1".to_string()],
+                false => vec![],
+            },
+        }
+    }
+}
+
+impl TagEdit {
+    /// The requested fields as tag values. An empty list removes the field.
+    fn values(&self) -> Vec<(Field, Vec<String>)> {
+        let text = |s: &Option<String>| {
+            s.as_deref().map(|s| match s.trim() {
+                "" => vec![],
+                s => vec![s.to_string()],
+            })
+        };
+        let list = |l: &Option<Vec<String>>| {
+            l.as_ref().map(|l| {
+                l.iter()
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+        };
+        let number = |n: Option<u32>| {
+            n.map(|n| match n {
+                0 => vec![],
+                n => vec![n.to_string()],
+            })
+        };
+        vec![
+            (Field::Title, text(&self.title)),
+            (Field::Artists, list(&self.artists)),
+            (Field::Album, text(&self.album)),
+            (Field::AlbumArtist, text(&self.album_artist)),
+            (Field::Track, number(self.track)),
+            (Field::Disc, number(self.disc)),
+            (
+                Field::Year,
+                self.year.map(|y| match y {
+                    0 => vec![],
+                    y => vec![y.to_string()],
+                }),
+            ),
+            (Field::Genre, list(&self.genre)),
+            (Field::Composer, text(&self.composer)),
+            (Field::Isrc, text(&self.isrc)),
+            (Field::Bpm, number(self.bpm)),
+            (Field::Copyright, text(&self.copyright)),
+            (Field::Label, text(&self.label)),
+            (
+                Field::Compilation,
+                self.compilation.map(|c| match c {
+                    true => vec!["1".to_string()],
+                    false => vec![],
+                }),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(field, values)| values.map(|v| (field, v)))
+        .collect()
+    }
+
+    /// Requested fields that differ from the file's current tags.
+    pub fn diff(&self, current: &AudioMetadata) -> Vec<TagChange> {
+        let render = |v: &[String]| match v.is_empty() {
+            true => None,
+            false => Some(v.join("; ")),
+        };
+        self.values()
+            .into_iter()
+            .filter_map(|(field, new)| {
+                let old = field.current(current);
+                (old != new).then(|| TagChange {
+                    field: field.name().to_string(),
+                    old: render(&old),
+                    new: render(&new),
+                })
+            })
+            .collect()
+    }
+
+    /// Only the fields listed in `changes`, so unchanged tags are left byte-for-byte alone.
+    fn changed_values(&self, changes: &[TagChange]) -> Vec<(Field, Vec<String>)> {
+        self.values()
+            .into_iter()
+            .filter(|(field, _)| changes.iter().any(|c| c.field == field.name()))
+            .collect()
+    }
+}
+
+/// Whether tags can be written back to files of this format. MP4/M4A, DSF and DFF files
+/// are read-only for now: growing an MP4 `ilst` means moving `mdat` and rewriting every
+/// chunk offset in `stco`/`co64`, a DSF keeps its ID3v2 tag at an offset recorded in its
+/// header, and DFF stores text in its own chunks. Edits to these files are refused whole.
+pub fn supports(format: &AudioFormat) -> bool {
+    !matches!(
+        format,
+        AudioFormat::Mp4 | AudioFormat::Dsf | AudioFormat::Dff
+    )
+}
+
+/// Write the fields of `edit` listed in `changes` into the file's native tags.
+pub fn write_tags(
+    path: &Path,
+    format: &AudioFormat,
+    edit: &TagEdit,
+    changes: &[TagChange],
+) -> anyhow::Result<()> {
+    let values = edit.changed_values(changes);
+    if values.is_empty() {
+        return Ok(());
+    }
+    match format {
+        AudioFormat::Mp3 | AudioFormat::Wav | AudioFormat::Aiff => write_id3(path, &values),
+        AudioFormat::Flac => write_flac(path, &values),
+        AudioFormat::Ogg | AudioFormat::Opus => write_ogg(path, &values),
+        AudioFormat::WavPack | AudioFormat::MonkeysAudio | AudioFormat::Musepack => {
+            write_ape(path, &values)
+        }
+        AudioFormat::Mp4 | AudioFormat::Dsf | AudioFormat::Dff => {
+            anyhow::bail!("writing tags to {:?} files is not supported", format)
+        }
+    }
+}
+
+fn write_id3(path: &Path, values: &[(Field, Vec<String>)]) -> anyhow::Result<()> {
+    let mut tag = match partial_tag_ok(id3::Tag::read_from_path(path)) {
+        Ok(tag) => tag,
+        Err(id3::Error {
+            kind: id3::ErrorKind::NoTag,
+            ..
+        }) => id3::Tag::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let version = match tag.version() {
+        id3::Version::Id3v23 => id3::Version::Id3v23,
+        _ => id3::Version::Id3v24,
+    };
+    for (field, values) in values {
+        match (field, values.first()) {
+            (_, None) => {
+                tag.remove(field.id3_frame());
+                if *field == Field::Year {
+                    tag.remove("TDRC");
+                }
+            }
+            // keep any track or disc total
+            (Field::Track, Some(n)) => tag.set_track(n.parse()?),
+            (Field::Disc, Some(n)) => tag.set_disc(n.parse()?),
+            (Field::Year, Some(y)) => {
+                // TYER is what the scanners read; TDRC is its ID3v2.4 replacement
+                tag.set_year(y.parse()?);
+                tag.set_text("TDRC", y.as_str());
+            }
+            (_, Some(_)) => tag.set_text(field.id3_frame(), values.join(id3_separator(version))),
+        }
+    }
+
+    tag.write_to_path(path, version)?;
+    Ok(())
+}
+
+/// Separator between multiple values of a text frame: NUL in ID3v2.4, and the `/` that
+/// ID3v2.3 readers expect, since NULs there end the frame.
+fn id3_separator(version: id3::Version) -> &'static str {
+    match version {
+        id3::Version::Id3v24 => "\0",
+        _ => "/",
+    }
+}
+
+fn write_flac(path: &Path, values: &[(Field, Vec<String>)]) -> anyhow::Result<()> {
+    let mut tag = metaflac::Tag::read_from_path(path)?;
+    for (field, values) in values {
+        let (key, aliases) = field.vorbis_keys().split_first().expect("vorbis key");
+        for alias in aliases {
+            tag.remove_vorbis(alias);
+        }
+        if values.is_empty() {
+            tag.remove_vorbis(key);
+        } else {
+            tag.set_vorbis(*key, values.clone());
+        }
+    }
+    tag.save()?;
+    Ok(())
+}
+
+fn write_ogg(path: &Path, values: &[(Field, Vec<String>)]) -> anyhow::Result<()> {
+    let data = std::fs::read(path)?;
+    let out = rewrite_comments(&data, |entries| {
+        for (field, values) in values {
+            let keys = field.vorbis_keys();
+            entries.retain(|e| {
+                let key = e.split('=').next().unwrap_or_default();
+                !keys.iter().any(|k| k.eq_ignore_ascii_case(key))
+            });
+            entries.extend(values.iter().map(|v| format!("{}={}", keys[0], v)));
+        }
+    })?;
+    replace_file(path, &out)
+}
+
+fn write_ape(path: &Path, values: &[(Field, Vec<String>)]) -> anyhow::Result<()> {
+    let mut file = std::fs::File::open(path)?;
+    let mut tag = ApeItems::read(&mut file)?;
+    for (field, values) in values {
+        let keys = field.ape_keys();
+        tag.items
+            .retain(|i| !keys.iter().any(|k| k.eq_ignore_ascii_case(&i.key)));
+        if !values.is_empty() {
+            tag.items.push(ApeItem::text(keys[0], values));
+        }
+    }
+
+    // everything before the old tag, the new tag, then anything after it (an ID3v1 tag)
+    let mut out = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    (&mut file).take(tag.start).read_to_end(&mut out)?;
+    out.extend(tag.encode());
+    file.seek(SeekFrom::Start(tag.end))?;
+    file.read_to_end(&mut out)?;
+    replace_file(path, &out)
+}
+
+/// Extension of the temporary file a rewrite is staged in, which the scanner skips.
+pub const PARTIAL_EXTENSION: &str = "maki-part";
+
+/// Replace a file's contents by writing beside it and renaming over it, so a failed
+/// write never leaves a truncated audio file behind.
+fn replace_file(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".");
+    partial.push(PARTIAL_EXTENSION);
+    let partial = std::path::PathBuf::from(partial);
+    if let Err(e) = std::fs::write(&partial, contents) {
+        std::fs::remove_file(&partial).ok();
+        return Err(e.into());
+    }
+    if let Ok(meta) = std::fs::metadata(path) {
+        std::fs::set_permissions(&partial, meta.permissions()).ok();
+    }
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scanned() -> AudioMetadata {
+        AudioMetadata {
+            name: "Tpyo".to_string(),
+            number: 3,
+            duration: 0,
+            duration_ms: 0,
+            album: "Album".to_string(),
+            album_artist: "Band".to_string(),
+            album_sort: None,
+            artist_sort: None,
+            album_artist_sort: None,
+            artists: vec!["Band".to_string()],
+            artist_tag: Some("Band".to_string()),
+            genre: Some(vec!["Rock".to_string()]),
+            picture: vec![],
+            path: "/m/song.flac".into(),
+            year: Some(1999),
+            disc: None,
+            track_total: None,
+            disc_total: None,
+            disc_subtitle: None,
+            lossless: true,
+            sample_rate: None,
+            bits_per_sample: None,
+            num_channels: None,
+            bitrate: None,
+            total_samples: None,
+            encoder_delay: None,
+            encoder_padding: None,
+            mbid_artist: None,
+            mbid_album: None,
+            mbid_track: None,
+            composer: None,
+            isrc: None,
+            bpm: None,
+            copyright: None,
+            label: Some("Label".to_string()),
+            lyrics: None,
+            replay_gain: Default::default(),
+            cue: None,
+            compilation: false,
+            classical: Default::default(),
+            credits: vec![],
+            explicit: None,
+        }
+    }
+
+    #[test]
+    fn diff_lists_only_changed_fields() {
+        let edit = TagEdit {
+            title: Some("Typo".to_string()),
+            track: Some(3),
+            genre: Some(vec!["Rock".to_string(), "Pop".to_string()]),
+            label: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(
+            edit.diff(&scanned()),
+            vec![
+                TagChange {
+                    field: "title".to_string(),
+                    old: Some("Tpyo".to_string()),
+                    new: Some("Typo".to_string()),
+                },
+                TagChange {
+                    field: "genre".to_string(),
+                    old: Some("Rock".to_string()),
+                    new: Some("Rock; Pop".to_string()),
+                },
+                TagChange {
+                    field: "label".to_string(),
+                    old: Some("Label".to_string()),
+                    new: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn album_edits_leave_track_fields_alone() {
+        let edit: TagEdit = AlbumTagEdit {
+            album: Some("Album".to_string()),
+            compilation: Some(true),
+            ..Default::default()
+        }
+        .into();
+        let changes = edit.diff(&scanned());
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].field, "compilation");
+        assert_eq!(edit.changed_values(&changes).len(), 1);
+    }
+
+    #[test]
+    fn id3v23_values_are_separated_with_slashes() {
+        assert_eq!(id3_separator(id3::Version::Id3v24), "\0");
+        assert_eq!(id3_separator(id3::Version::Id3v23), "/");
+    }
+
+    #[test]
+    fn mp4_and_dsd_are_read_only() {
+        assert!(supports(&AudioFormat::Flac));
+        assert!(supports(&AudioFormat::Mp3));
+        assert!(!supports(&AudioFormat::Mp4));
+        assert!(!supports(&AudioFormat::Dsf));
+        assert!(!supports(&AudioFormat::Dff));
+    }
+}